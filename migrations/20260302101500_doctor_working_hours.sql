@@ -0,0 +1,27 @@
+ALTER TABLE doctors ADD COLUMN working_hours JSONB NOT NULL DEFAULT '[]';
+ALTER TABLE doctors ADD COLUMN slot_minutes INT NOT NULL DEFAULT 30;
+
+-- Carry over visiting hours written as "HH:MM-HH:MM"; anything else has to be re-entered
+UPDATE doctors
+SET working_hours = (
+    SELECT COALESCE(
+        jsonb_agg(jsonb_build_object(
+            'day', d,
+            'start', trim(split_part(visiting_hours, '-', 1)),
+            'end', trim(split_part(visiting_hours, '-', 2))
+        )),
+        '[]'
+    )
+    FROM unnest(available_days) AS d
+)
+WHERE visiting_hours ~ '^\s*\d{2}:\d{2}\s*-\s*\d{2}:\d{2}\s*$';
+
+ALTER TABLE doctors DROP COLUMN visiting_hours;
+
+ALTER TABLE appointments ADD COLUMN duration_minutes INT NOT NULL DEFAULT 30;
+
+-- Backstop for the row lock taken while booking: a doctor can only hold one
+-- active appointment per slot start
+CREATE UNIQUE INDEX IF NOT EXISTS appointments_doctor_active_slot
+    ON appointments (doctor_id, time)
+    WHERE status = 'Scheduled';
@@ -0,0 +1,18 @@
+CREATE TABLE IF NOT EXISTS appointment_events (
+    id UUID PRIMARY KEY,
+    appointment_id UUID NOT NULL REFERENCES appointments(id) ON DELETE CASCADE,
+    from_status VARCHAR(100), -- NULL when the appointment is first booked
+    to_status VARCHAR(100) NOT NULL,
+    actor_id UUID REFERENCES users(id) ON DELETE SET NULL,
+    reason TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS appointment_events_appointment_id
+    ON appointment_events (appointment_id, created_at);
+
+-- Checked-in and in-progress appointments keep holding their slot
+DROP INDEX IF EXISTS appointments_doctor_active_slot;
+CREATE UNIQUE INDEX IF NOT EXISTS appointments_doctor_active_slot
+    ON appointments (doctor_id, time)
+    WHERE status IN ('Scheduled', 'CheckedIn', 'InProgress');
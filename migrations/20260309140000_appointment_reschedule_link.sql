@@ -0,0 +1,4 @@
+-- A rescheduled appointment is closed and a new one is booked pointing back at it
+ALTER TABLE appointments ADD COLUMN rescheduled_from UUID REFERENCES appointments(id) ON DELETE SET NULL;
+
+CREATE INDEX IF NOT EXISTS appointments_rescheduled_from ON appointments (rescheduled_from);
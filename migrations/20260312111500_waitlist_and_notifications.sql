@@ -0,0 +1,25 @@
+CREATE TABLE IF NOT EXISTS waitlist_entries (
+    id UUID PRIMARY KEY,
+    patient_id UUID NOT NULL REFERENCES patients(id) ON DELETE CASCADE,
+    specialization VARCHAR(100), -- NULL accepts any doctor
+    purpose TEXT NOT NULL,
+    window_start TIMESTAMPTZ NOT NULL, -- Local midnight of the requested day
+    window_end TIMESTAMPTZ NOT NULL,
+    status VARCHAR(50) NOT NULL,
+    appointment_id UUID REFERENCES appointments(id) ON DELETE SET NULL,
+    expires_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS waitlist_entries_waiting
+    ON waitlist_entries (window_start, created_at)
+    WHERE status = 'Waiting';
+
+CREATE TABLE IF NOT EXISTS notifications (
+    id UUID PRIMARY KEY,
+    patient_id UUID NOT NULL REFERENCES patients(id) ON DELETE CASCADE,
+    message TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS notifications_patient_id ON notifications (patient_id, created_at);
@@ -0,0 +1,15 @@
+CREATE TABLE IF NOT EXISTS appointment_series (
+    id UUID PRIMARY KEY,
+    patient_id UUID NOT NULL REFERENCES patients(id) ON DELETE CASCADE,
+    doctor_id UUID NOT NULL REFERENCES doctors(id) ON DELETE CASCADE,
+    purpose TEXT NOT NULL,
+    interval_weeks INT NOT NULL,
+    occurrences INT NOT NULL,
+    starts_at TIMESTAMPTZ NOT NULL,
+    status VARCHAR(50) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+ALTER TABLE appointments ADD COLUMN series_id UUID REFERENCES appointment_series(id) ON DELETE SET NULL;
+
+CREATE INDEX IF NOT EXISTS appointments_series_id ON appointments (series_id);
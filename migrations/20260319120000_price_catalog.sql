@@ -0,0 +1,20 @@
+CREATE TABLE IF NOT EXISTS price_catalog (
+    id UUID PRIMARY KEY,
+    hospital_id UUID NOT NULL REFERENCES hospitals(id) ON DELETE CASCADE,
+    specialization VARCHAR(100) NOT NULL,
+    visit_type VARCHAR(50) NOT NULL,
+    doctor_id UUID REFERENCES doctors(id) ON DELETE CASCADE, -- NULL applies to every doctor
+    price NUMERIC(10, 2) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS price_catalog_entry
+    ON price_catalog (
+        hospital_id,
+        LOWER(specialization),
+        visit_type,
+        COALESCE(doctor_id, '00000000-0000-0000-0000-000000000000')
+    );
+
+ALTER TABLE appointments ADD COLUMN visit_type VARCHAR(50) NOT NULL DEFAULT 'Consultation';
+ALTER TABLE appointments ADD COLUMN price_catalog_id UUID REFERENCES price_catalog(id) ON DELETE SET NULL;
@@ -0,0 +1,11 @@
+-- Every webhook we have acted on; a redelivered event hits the unique index and is skipped
+CREATE TABLE IF NOT EXISTS payment_events (
+    id UUID PRIMARY KEY,
+    event VARCHAR(100) NOT NULL,
+    reference VARCHAR(100) NOT NULL,
+    payload JSONB NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (event, reference)
+);
+
+ALTER TABLE bills ADD COLUMN paid_at TIMESTAMPTZ;
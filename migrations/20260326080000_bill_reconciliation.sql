@@ -0,0 +1,3 @@
+ALTER TABLE bills ADD COLUMN created_at TIMESTAMPTZ NOT NULL DEFAULT NOW();
+
+CREATE INDEX IF NOT EXISTS bills_pending_created_at ON bills (created_at) WHERE status = 'Pending';
@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS refunds (
+    id UUID PRIMARY KEY,
+    bill_id UUID NOT NULL REFERENCES bills(id) ON DELETE CASCADE,
+    amount NUMERIC(10, 2) NOT NULL,
+    reason TEXT,
+    provider_refund_id VARCHAR(100) NOT NULL,
+    status VARCHAR(50) NOT NULL, -- As reported by the payment gateway
+    requested_by UUID, -- NULL when issued automatically
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS refunds_bill_id ON refunds (bill_id);
@@ -0,0 +1,38 @@
+-- Billable work ordered during a visit (lab tests, drugs, bed days), picked up by the next bill
+CREATE TABLE IF NOT EXISTS appointment_orders (
+    id UUID PRIMARY KEY,
+    appointment_id UUID NOT NULL REFERENCES appointments(id) ON DELETE CASCADE,
+    kind VARCHAR(50) NOT NULL,
+    description TEXT NOT NULL,
+    quantity INT NOT NULL,
+    unit_price NUMERIC(10, 2) NOT NULL,
+    discount NUMERIC(10, 2) NOT NULL DEFAULT 0,
+    taxable BOOLEAN NOT NULL DEFAULT FALSE,
+    bill_id UUID REFERENCES bills(id) ON DELETE SET NULL, -- NULL until billed
+    ordered_by UUID NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS appointment_orders_unbilled
+    ON appointment_orders (appointment_id) WHERE bill_id IS NULL;
+
+CREATE TABLE IF NOT EXISTS bill_items (
+    id UUID PRIMARY KEY,
+    bill_id UUID NOT NULL REFERENCES bills(id) ON DELETE CASCADE,
+    order_id UUID REFERENCES appointment_orders(id) ON DELETE SET NULL,
+    kind VARCHAR(50) NOT NULL,
+    description TEXT NOT NULL,
+    quantity INT NOT NULL,
+    unit_price NUMERIC(10, 2) NOT NULL,
+    discount NUMERIC(10, 2) NOT NULL,
+    tax NUMERIC(10, 2) NOT NULL,
+    total NUMERIC(10, 2) NOT NULL -- quantity * unit_price - discount + tax
+);
+
+CREATE INDEX IF NOT EXISTS bill_items_bill_id ON bill_items (bill_id);
+
+-- Existing bills were a single undiscounted, untaxed amount
+ALTER TABLE bills ADD COLUMN subtotal NUMERIC(10, 2) NOT NULL DEFAULT 0;
+ALTER TABLE bills ADD COLUMN discount NUMERIC(10, 2) NOT NULL DEFAULT 0;
+ALTER TABLE bills ADD COLUMN tax NUMERIC(10, 2) NOT NULL DEFAULT 0;
+UPDATE bills SET subtotal = amount;
@@ -0,0 +1,43 @@
+-- Each attempt to pay a bill, online or otherwise, with its own reference. A bill can be
+-- settled by several payments.
+CREATE TABLE IF NOT EXISTS payments (
+    id UUID PRIMARY KEY,
+    bill_id UUID NOT NULL REFERENCES bills(id) ON DELETE CASCADE,
+    reference VARCHAR(100) UNIQUE NOT NULL,
+    amount NUMERIC(10, 2) NOT NULL,
+    currency VARCHAR(10) NOT NULL,
+    method VARCHAR(50) NOT NULL,
+    status VARCHAR(50) NOT NULL,
+    refunded NUMERIC(10, 2) NOT NULL DEFAULT 0,
+    paid_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS payments_bill_id ON payments (bill_id);
+CREATE INDEX IF NOT EXISTS payments_pending_created_at ON payments (created_at) WHERE status = 'Pending';
+
+ALTER TABLE bills ADD COLUMN amount_paid NUMERIC(10, 2) NOT NULL DEFAULT 0;
+
+-- Until now a bill was paid in one go under its own reference; carry those charges over
+INSERT INTO payments (id, bill_id, reference, amount, currency, method, status, refunded, paid_at, created_at)
+SELECT
+    gen_random_uuid(),
+    b.id,
+    b.reference,
+    b.amount,
+    b.currency,
+    'Online',
+    CASE WHEN b.status IN ('Paid', 'PartiallyRefunded', 'Refunded') THEN 'Succeeded' ELSE 'Pending' END,
+    COALESCE((SELECT SUM(r.amount) FROM refunds r WHERE r.bill_id = b.id), 0),
+    b.paid_at,
+    b.created_at
+FROM bills b
+WHERE b.status IN ('Pending', 'Paid', 'PartiallyRefunded', 'Refunded');
+
+UPDATE bills SET amount_paid = amount WHERE status IN ('Paid', 'PartiallyRefunded', 'Refunded');
+
+-- Failed and expired attempts are now tracked on payments; the bill is simply still owed
+UPDATE bills SET status = 'Pending' WHERE status IN ('Failed', 'Expired');
+
+ALTER TABLE refunds ADD COLUMN payment_id UUID REFERENCES payments(id) ON DELETE CASCADE;
+UPDATE refunds r SET payment_id = p.id FROM payments p WHERE p.bill_id = r.bill_id;
@@ -0,0 +1,10 @@
+-- Cash, POS and bank-transfer collections recorded at the front desk
+ALTER TABLE payments ADD COLUMN receipt_number VARCHAR(100);
+ALTER TABLE payments ADD COLUMN collected_by UUID;
+
+-- The same receipt cannot be recorded twice
+CREATE UNIQUE INDEX IF NOT EXISTS payments_receipt_number
+    ON payments (method, receipt_number) WHERE receipt_number IS NOT NULL;
+
+-- Money returned over the counter has no gateway refund id
+ALTER TABLE refunds ALTER COLUMN provider_refund_id DROP NOT NULL;
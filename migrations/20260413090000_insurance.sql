@@ -0,0 +1,48 @@
+-- HMOs a hospital bills on behalf of its insured patients
+CREATE TABLE IF NOT EXISTS insurance_providers (
+    id UUID PRIMARY KEY,
+    hospital_id UUID NOT NULL REFERENCES hospitals(id) ON DELETE CASCADE,
+    name VARCHAR(200) NOT NULL,
+    code VARCHAR(50) NOT NULL,
+    email VARCHAR(200),
+    phone VARCHAR(50),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (hospital_id, code)
+);
+
+CREATE TABLE IF NOT EXISTS insurance_policies (
+    id UUID PRIMARY KEY,
+    patient_id UUID NOT NULL REFERENCES patients(id) ON DELETE CASCADE,
+    provider_id UUID NOT NULL REFERENCES insurance_providers(id) ON DELETE CASCADE,
+    policy_number VARCHAR(100) NOT NULL,
+    coverage JSONB NOT NULL, -- [{"kind": "LabTest", "percent": "80"}, ...]
+    annual_limit NUMERIC(12, 2), -- NULL for no limit
+    valid_from DATE NOT NULL,
+    valid_until DATE,
+    is_active BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (provider_id, policy_number)
+);
+
+CREATE INDEX IF NOT EXISTS insurance_policies_patient_id ON insurance_policies (patient_id);
+
+-- What the insurer owes on a bill; the patient owes amount - insurer_amount
+ALTER TABLE bills ADD COLUMN insurer_amount NUMERIC(10, 2) NOT NULL DEFAULT 0;
+ALTER TABLE bills ADD COLUMN policy_id UUID REFERENCES insurance_policies(id) ON DELETE SET NULL;
+
+CREATE TABLE IF NOT EXISTS insurance_claims (
+    id UUID PRIMARY KEY,
+    bill_id UUID NOT NULL UNIQUE REFERENCES bills(id) ON DELETE CASCADE,
+    policy_id UUID NOT NULL REFERENCES insurance_policies(id) ON DELETE CASCADE,
+    provider_id UUID NOT NULL REFERENCES insurance_providers(id) ON DELETE CASCADE,
+    amount NUMERIC(10, 2) NOT NULL,
+    approved_amount NUMERIC(10, 2),
+    status VARCHAR(50) NOT NULL,
+    reason TEXT, -- Why the insurer rejected or cut the claim
+    submitted_at TIMESTAMPTZ,
+    decided_at TIMESTAMPTZ,
+    paid_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS insurance_claims_provider ON insurance_claims (provider_id, created_at);
@@ -0,0 +1,10 @@
+-- Each hospital's own payment configuration, managed by its admins
+CREATE TABLE IF NOT EXISTS hospital_payment_settings (
+    hospital_id UUID PRIMARY KEY REFERENCES hospitals(id) ON DELETE CASCADE,
+    paystack_secret_key TEXT, -- AES-256-GCM encrypted; NULL uses the platform key
+    paystack_subaccount VARCHAR(100), -- Settles the hospital's payments into its subaccount
+    callback_url TEXT, -- Where Paystack sends patients after paying
+    default_currency VARCHAR(3) NOT NULL DEFAULT 'NGN',
+    updated_by UUID,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,15 @@
+-- Every appointment and bill belongs to one hospital, so queries can be scoped to the caller's
+ALTER TABLE appointments ADD COLUMN IF NOT EXISTS hospital_id UUID REFERENCES hospitals(id) ON DELETE SET NULL;
+UPDATE appointments a SET hospital_id = COALESCE(d.hospital_id, p.hospital_id)
+FROM doctors d, patients p
+WHERE d.id = a.doctor_id AND p.id = a.patient_id AND a.hospital_id IS NULL;
+
+ALTER TABLE bills ADD COLUMN IF NOT EXISTS hospital_id UUID REFERENCES hospitals(id) ON DELETE SET NULL;
+UPDATE bills b SET hospital_id = a.hospital_id
+FROM appointments a
+WHERE a.id = b.appointment_id AND b.hospital_id IS NULL;
+
+CREATE INDEX IF NOT EXISTS idx_patients_hospital ON patients (hospital_id);
+CREATE INDEX IF NOT EXISTS idx_doctors_hospital ON doctors (hospital_id);
+CREATE INDEX IF NOT EXISTS idx_appointments_hospital_time ON appointments (hospital_id, time);
+CREATE INDEX IF NOT EXISTS idx_bills_hospital ON bills (hospital_id);
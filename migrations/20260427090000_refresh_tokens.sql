@@ -0,0 +1,24 @@
+-- Refresh tokens, stored as SHA-256 hashes. Each refresh replaces the token with a new one in the
+-- same family; presenting a replaced token again revokes the whole family.
+CREATE TABLE IF NOT EXISTS refresh_tokens (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    family_id UUID NOT NULL, -- Shared by every token descended from one login
+    token_hash VARCHAR(64) NOT NULL UNIQUE,
+    access_jti UUID NOT NULL, -- The access token issued alongside it
+    expires_at TIMESTAMPTZ NOT NULL,
+    used_at TIMESTAMPTZ, -- Set once it has been exchanged for a new pair
+    revoked_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS refresh_tokens_family ON refresh_tokens (family_id);
+CREATE INDEX IF NOT EXISTS refresh_tokens_access_jti ON refresh_tokens (access_jti);
+
+-- Access tokens revoked before they expire; rows can go once the token would have expired anyway
+CREATE TABLE IF NOT EXISTS revoked_tokens (
+    jti UUID PRIMARY KEY,
+    expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS revoked_tokens_expires_at ON revoked_tokens (expires_at);
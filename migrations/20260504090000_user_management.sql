@@ -0,0 +1,6 @@
+ALTER TABLE users ADD COLUMN IF NOT EXISTS is_active BOOLEAN NOT NULL DEFAULT TRUE;
+
+CREATE INDEX IF NOT EXISTS idx_users_hospital ON users (hospital_id);
+-- A login account belongs to at most one doctor and one patient record
+CREATE UNIQUE INDEX IF NOT EXISTS doctors_user_id_unique ON doctors (user_id) WHERE user_id IS NOT NULL;
+CREATE UNIQUE INDEX IF NOT EXISTS patients_user_id_unique ON patients (user_id) WHERE user_id IS NOT NULL;
@@ -0,0 +1,6 @@
+-- When the gateway was last asked about a payment. The sweep waits PENDING_BILL_MINUTES after
+-- each check, and only expires a payment that was still unpaid on two checks that far apart.
+ALTER TABLE payments ADD COLUMN IF NOT EXISTS last_verified_at TIMESTAMPTZ;
+
+DROP INDEX IF EXISTS payments_pending_created_at;
+CREATE INDEX IF NOT EXISTS payments_pending_checked_at ON payments (COALESCE(last_verified_at, created_at)) WHERE status = 'Pending';
@@ -0,0 +1,3 @@
+-- Keys are only taken for signed-in users now. Drop what anonymous requests left behind under
+-- the nil user, which includes the tokens returned by logins and refreshes.
+DELETE FROM idempotency_keys WHERE user_id = '00000000-0000-0000-0000-000000000000';
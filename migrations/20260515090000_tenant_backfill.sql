@@ -0,0 +1,9 @@
+-- Appointments booked with a doctor of no hospital now take the patient's; give the ones booked
+-- before that a hospital too, and their bills with them
+UPDATE appointments a SET hospital_id = p.hospital_id
+FROM patients p
+WHERE p.id = a.patient_id AND a.hospital_id IS NULL;
+
+UPDATE bills b SET hospital_id = a.hospital_id
+FROM appointments a
+WHERE a.id = b.appointment_id AND b.hospital_id IS NULL;
@@ -1,12 +1,17 @@
-use crate::admin::models::{CreateHospital, UpdateHospital};
+use crate::admin::models::{
+    CreateHospital, CreatePriceCatalogEntry, InviteUser, LinkUserRecord, UpdateHospital,
+    UpdatePaymentSettings, UpdatePriceCatalogEntry, UpdateUserRole, UserRole,
+};
 use crate::admin::service;
 use crate::app_state::SharedState;
 use crate::auth::headers::ClaimsHeader;
 use crate::errors::AppError;
-use axum::extract::Path;
-use axum::response::IntoResponse;
+use axum::extract::{Path, Query};
+use axum::response::{IntoResponse, Response};
 use axum::{Json, extract::State, http::StatusCode};
 use serde_json::json;
+use std::collections::HashMap;
+use uuid::Uuid;
 
 pub async fn create_hospital_and_admin_handler(
     State(state): State<SharedState>,
@@ -25,9 +30,18 @@ pub async fn create_hospital_and_admin_handler(
 
 pub async fn get_hospital_info_handler(
     State(state): State<SharedState>,
+    claims: ClaimsHeader,
     Path(hospital_id): Path<String>,
 ) -> impl IntoResponse {
     let hospital_id = match uuid::Uuid::parse_str(&hospital_id) {
+        // Other hospitals are reported as missing rather than forbidden
+        Ok(id) if claims.tenant().is_some_and(|tenant| tenant != id) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(json!({"error": format!("Hospital with id {} not found", id)})),
+            )
+                .into_response();
+        }
         Ok(id) => id,
         Err(_) => {
             return (
@@ -72,20 +86,330 @@ pub async fn update_hospital_info_handler(
     let result = service::update_hospital_info(state, hospital_id, data, claims).await;
     match result {
         Ok(data) => Json(data).into_response(),
-        Err(e) => match e {
-            AppError::Unauthorized(e) => (
-                StatusCode::UNAUTHORIZED,
-                Json(json!({"error": e.to_string()})),
+        Err(e) => admin_error_response(e),
+    }
+}
+
+pub async fn get_price_catalog_handler(
+    State(state): State<SharedState>,
+    claims: ClaimsHeader,
+    Path(hospital_id): Path<String>,
+) -> impl IntoResponse {
+    let hospital_id = match uuid::Uuid::parse_str(&hospital_id) {
+        Ok(id) => id,
+        Err(_) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": format!("Invalid hospital_id")})),
             )
-                .into_response(),
-            AppError::NotFound(e) => {
-                (StatusCode::NOT_FOUND, Json(json!({"error": e.to_string()}))).into_response()
-            }
-            _ => (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({"error": e.to_string()})),
+                .into_response();
+        }
+    };
+    let result = service::get_price_catalog(state, hospital_id, claims).await;
+    match result {
+        Ok(data) => Json(data).into_response(),
+        Err(e) => admin_error_response(e),
+    }
+}
+
+pub async fn create_price_catalog_entry_handler(
+    State(state): State<SharedState>,
+    claims: ClaimsHeader,
+    Path(hospital_id): Path<String>,
+    Json(data): Json<CreatePriceCatalogEntry>,
+) -> impl IntoResponse {
+    let hospital_id = match uuid::Uuid::parse_str(&hospital_id) {
+        Ok(id) => id,
+        Err(_) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": format!("Invalid hospital_id")})),
             )
-                .into_response(),
-        },
+                .into_response();
+        }
+    };
+    let result = service::create_price_catalog_entry(state, hospital_id, data, claims).await;
+    match result {
+        Ok(data) => (StatusCode::CREATED, Json(data)).into_response(),
+        Err(e) => admin_error_response(e),
+    }
+}
+
+pub async fn update_price_catalog_entry_handler(
+    State(state): State<SharedState>,
+    claims: ClaimsHeader,
+    Path((hospital_id, entry_id)): Path<(String, String)>,
+    Json(data): Json<UpdatePriceCatalogEntry>,
+) -> impl IntoResponse {
+    let (hospital_id, entry_id) = match (
+        uuid::Uuid::parse_str(&hospital_id),
+        uuid::Uuid::parse_str(&entry_id),
+    ) {
+        (Ok(hospital_id), Ok(entry_id)) => (hospital_id, entry_id),
+        _ => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": format!("Invalid hospital_id or price id")})),
+            )
+                .into_response();
+        }
+    };
+    let result =
+        service::update_price_catalog_entry(state, hospital_id, entry_id, data, claims).await;
+    match result {
+        Ok(data) => Json(data).into_response(),
+        Err(e) => admin_error_response(e),
+    }
+}
+
+pub async fn delete_price_catalog_entry_handler(
+    State(state): State<SharedState>,
+    claims: ClaimsHeader,
+    Path((hospital_id, entry_id)): Path<(String, String)>,
+) -> impl IntoResponse {
+    let (hospital_id, entry_id) = match (
+        uuid::Uuid::parse_str(&hospital_id),
+        uuid::Uuid::parse_str(&entry_id),
+    ) {
+        (Ok(hospital_id), Ok(entry_id)) => (hospital_id, entry_id),
+        _ => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": format!("Invalid hospital_id or price id")})),
+            )
+                .into_response();
+        }
+    };
+    let result = service::delete_price_catalog_entry(state, hospital_id, entry_id, claims).await;
+    match result {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => admin_error_response(e),
+    }
+}
+
+pub async fn get_payment_settings_handler(
+    State(state): State<SharedState>,
+    claims: ClaimsHeader,
+    Path(hospital_id): Path<String>,
+) -> impl IntoResponse {
+    let hospital_id = match uuid::Uuid::parse_str(&hospital_id) {
+        Ok(id) => id,
+        Err(_) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "Invalid hospital_id"})),
+            )
+                .into_response();
+        }
+    };
+    match service::get_payment_settings(state, hospital_id, claims).await {
+        Ok(data) => Json(data).into_response(),
+        Err(e) => admin_error_response(e),
+    }
+}
+
+pub async fn update_payment_settings_handler(
+    State(state): State<SharedState>,
+    claims: ClaimsHeader,
+    Path(hospital_id): Path<String>,
+    Json(data): Json<UpdatePaymentSettings>,
+) -> impl IntoResponse {
+    let hospital_id = match uuid::Uuid::parse_str(&hospital_id) {
+        Ok(id) => id,
+        Err(_) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "Invalid hospital_id"})),
+            )
+                .into_response();
+        }
+    };
+    match service::update_payment_settings(state, hospital_id, data, claims).await {
+        Ok(data) => Json(data).into_response(),
+        Err(e) => admin_error_response(e),
+    }
+}
+
+fn admin_error_response(e: AppError) -> Response {
+    match e {
+        AppError::Unauthorized(e) => (
+            StatusCode::UNAUTHORIZED,
+            Json(json!({"error": e.to_string()})),
+        )
+            .into_response(),
+        AppError::Forbidden(e) => {
+            (StatusCode::FORBIDDEN, Json(json!({"error": e.to_string()}))).into_response()
+        }
+        AppError::NotFound(e) => {
+            (StatusCode::NOT_FOUND, Json(json!({"error": e.to_string()}))).into_response()
+        }
+        AppError::Conflict(e) => {
+            (StatusCode::CONFLICT, Json(json!({"error": e.to_string()}))).into_response()
+        }
+        AppError::UnProcessableEntity { field, message } => (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(json!({"error": format!("{}: {}", field, message)})),
+        )
+            .into_response(),
+        _ => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn get_users_handler(
+    State(state): State<SharedState>,
+    claims: ClaimsHeader,
+    Path(hospital_id): Path<String>,
+    Query(params): Query<HashMap<String, String>>,
+) -> impl IntoResponse {
+    let hospital_id = match Uuid::parse_str(&hospital_id) {
+        Ok(id) => id,
+        Err(_) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "Invalid hospital_id"})),
+            )
+                .into_response();
+        }
+    };
+    let role = match params
+        .get("role")
+        .map(|s| serde_json::from_value::<UserRole>(json!(s)))
+    {
+        Some(Ok(role)) => Some(role),
+        Some(Err(_)) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "Invalid role"})),
+            )
+                .into_response();
+        }
+        None => None,
+    };
+    let is_active = match params.get("active").map(|s| s.parse::<bool>()) {
+        Some(Ok(active)) => Some(active),
+        Some(Err(_)) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "active must be true or false"})),
+            )
+                .into_response();
+        }
+        None => None,
+    };
+    match service::get_users(state, hospital_id, role, is_active, claims).await {
+        Ok(data) => Json(data).into_response(),
+        Err(e) => admin_error_response(e),
+    }
+}
+
+pub async fn invite_user_handler(
+    State(state): State<SharedState>,
+    claims: ClaimsHeader,
+    Path(hospital_id): Path<String>,
+    Json(data): Json<InviteUser>,
+) -> impl IntoResponse {
+    let hospital_id = match Uuid::parse_str(&hospital_id) {
+        Ok(id) => id,
+        Err(_) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "Invalid hospital_id"})),
+            )
+                .into_response();
+        }
+    };
+    match service::invite_user(state, hospital_id, data, claims).await {
+        Ok(user) => (StatusCode::CREATED, Json(user)).into_response(),
+        Err(e) => admin_error_response(e),
+    }
+}
+
+pub async fn update_user_role_handler(
+    State(state): State<SharedState>,
+    claims: ClaimsHeader,
+    Path((hospital_id, user_id)): Path<(String, String)>,
+    Json(data): Json<UpdateUserRole>,
+) -> impl IntoResponse {
+    let (hospital_id, user_id) = match (Uuid::parse_str(&hospital_id), Uuid::parse_str(&user_id)) {
+        (Ok(hospital_id), Ok(user_id)) => (hospital_id, user_id),
+        _ => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "Invalid hospital_id or user id"})),
+            )
+                .into_response();
+        }
+    };
+    match service::update_user_role(state, hospital_id, user_id, data, claims).await {
+        Ok(user) => Json(user).into_response(),
+        Err(e) => admin_error_response(e),
+    }
+}
+
+pub async fn deactivate_user_handler(
+    State(state): State<SharedState>,
+    claims: ClaimsHeader,
+    Path((hospital_id, user_id)): Path<(String, String)>,
+) -> impl IntoResponse {
+    let (hospital_id, user_id) = match (Uuid::parse_str(&hospital_id), Uuid::parse_str(&user_id)) {
+        (Ok(hospital_id), Ok(user_id)) => (hospital_id, user_id),
+        _ => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "Invalid hospital_id or user id"})),
+            )
+                .into_response();
+        }
+    };
+    match service::set_user_active(state, hospital_id, user_id, false, claims).await {
+        Ok(user) => Json(user).into_response(),
+        Err(e) => admin_error_response(e),
+    }
+}
+
+pub async fn reactivate_user_handler(
+    State(state): State<SharedState>,
+    claims: ClaimsHeader,
+    Path((hospital_id, user_id)): Path<(String, String)>,
+) -> impl IntoResponse {
+    let (hospital_id, user_id) = match (Uuid::parse_str(&hospital_id), Uuid::parse_str(&user_id)) {
+        (Ok(hospital_id), Ok(user_id)) => (hospital_id, user_id),
+        _ => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "Invalid hospital_id or user id"})),
+            )
+                .into_response();
+        }
+    };
+    match service::set_user_active(state, hospital_id, user_id, true, claims).await {
+        Ok(user) => Json(user).into_response(),
+        Err(e) => admin_error_response(e),
+    }
+}
+
+pub async fn link_user_record_handler(
+    State(state): State<SharedState>,
+    claims: ClaimsHeader,
+    Path((hospital_id, user_id)): Path<(String, String)>,
+    Json(data): Json<LinkUserRecord>,
+) -> impl IntoResponse {
+    let (hospital_id, user_id) = match (Uuid::parse_str(&hospital_id), Uuid::parse_str(&user_id)) {
+        (Ok(hospital_id), Ok(user_id)) => (hospital_id, user_id),
+        _ => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "Invalid hospital_id or user id"})),
+            )
+                .into_response();
+        }
+    };
+    match service::link_user_record(state, hospital_id, user_id, data, claims).await {
+        Ok(user) => Json(user).into_response(),
+        Err(e) => admin_error_response(e),
     }
 }
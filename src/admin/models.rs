@@ -1,3 +1,5 @@
+use crate::appointments::models::VisitType;
+use crate::money::Money;
 use crate::utils::hash_password;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
@@ -13,11 +15,15 @@ pub struct Hospital {
     pub created_at: DateTime<Utc>,
 }
 
-#[derive(Serialize, Deserialize, Type)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
 #[sqlx(type_name = "VARCHAR", rename_all = "PascalCase")]
 pub enum UserRole {
+    SuperAdmin, // Runs the platform; not limited to one hospital
     Admin,
     Doctor,
+    Nurse,
+    Cashier,
+    Receptionist,
     Patient,
 }
 
@@ -26,12 +32,40 @@ pub struct User {
     pub id: Uuid,
     pub name: String,
     pub email: String,
+    #[serde(skip_serializing)]
     pub password_hash: String,
     pub role: UserRole,
     pub hospital_id: Uuid,
+    pub is_active: bool, // Deactivated users cannot log in
+    pub email_verified_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
 }
 
+#[derive(Serialize)]
+pub struct UserList {
+    pub users: Vec<User>,
+}
+
+// A staff member or patient added by a hospital admin. They get an email to choose a password.
+#[derive(Deserialize)]
+pub struct InviteUser {
+    pub name: String,
+    pub email: String,
+    pub role: UserRole,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateUserRole {
+    pub role: UserRole,
+}
+
+// Exactly one of the two: the doctor or patient record the user logs in as
+#[derive(Deserialize)]
+pub struct LinkUserRecord {
+    pub doctor_id: Option<Uuid>,
+    pub patient_id: Option<Uuid>,
+}
+
 pub struct HospitalData {
     pub hospital: Hospital,
     pub admin: User,
@@ -60,6 +94,75 @@ pub struct HospitalWithAdminEmail {
     pub admin_email: String,
 }
 
+// Price of a visit in a hospital. Entries with a doctor override the specialization-wide price.
+#[derive(Serialize, FromRow)]
+pub struct PriceCatalogEntry {
+    pub id: Uuid,
+    pub hospital_id: Uuid,
+    pub specialization: String,
+    pub visit_type: VisitType,
+    pub doctor_id: Option<Uuid>,
+    pub price: Money,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct CreatePriceCatalogEntry {
+    pub specialization: String,
+    pub visit_type: VisitType,
+    pub doctor_id: Option<Uuid>,
+    pub price: Money,
+}
+
+#[derive(Deserialize)]
+pub struct UpdatePriceCatalogEntry {
+    pub price: Money,
+}
+
+#[derive(Serialize)]
+pub struct PriceCatalog {
+    pub entries: Vec<PriceCatalogEntry>,
+}
+
+// A hospital's own payment configuration. The Paystack secret key is kept encrypted and is never
+// sent back to clients.
+#[derive(FromRow)]
+pub struct PaymentSettings {
+    pub hospital_id: Uuid,
+    pub paystack_secret_key: Option<String>, // Encrypted with SETTINGS_ENCRYPTION_KEY
+    pub paystack_subaccount: Option<String>,
+    pub callback_url: Option<String>,
+    pub default_currency: String,
+    pub updated_by: Option<Uuid>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+pub struct PaymentSettingsResponse {
+    pub hospital_id: Uuid,
+    pub has_paystack_secret_key: bool,
+    pub paystack_subaccount: Option<String>,
+    pub callback_url: Option<String>,
+    pub default_currency: String,
+    pub updated_by: Option<Uuid>,
+    pub updated_at: Option<DateTime<Utc>>, // None until the settings are first saved
+}
+
+#[derive(Deserialize)]
+pub struct UpdatePaymentSettings {
+    pub paystack_secret_key: Option<String>, // Left out keeps the current key, "" removes it
+    pub paystack_subaccount: Option<String>,
+    pub callback_url: Option<String>,
+    pub default_currency: Option<String>,
+}
+
+// The price charged for an appointment and the catalog entry it came from, if any
+#[derive(Clone, Copy)]
+pub struct AppliedPrice {
+    pub amount: Money,
+    pub catalog_id: Option<Uuid>,
+}
+
 impl HospitalData {
     pub fn new(data: CreateHospital) -> Self {
         let password_hash =
@@ -79,6 +182,8 @@ impl HospitalData {
             password_hash,
             role: UserRole::Admin,
             hospital_id: hospital.id,
+            is_active: true,
+            email_verified_at: None,
             created_at: Utc::now(),
         };
 
@@ -1,17 +1,82 @@
 use crate::admin::handlers::{
-    create_hospital_and_admin_handler, get_hospital_info_handler, update_hospital_info_handler,
+    create_hospital_and_admin_handler, create_price_catalog_entry_handler, deactivate_user_handler,
+    delete_price_catalog_entry_handler, get_hospital_info_handler, get_payment_settings_handler,
+    get_price_catalog_handler, get_users_handler, invite_user_handler, link_user_record_handler,
+    reactivate_user_handler, update_hospital_info_handler, update_payment_settings_handler,
+    update_price_catalog_entry_handler, update_user_role_handler,
 };
 use crate::app_state::{AppState, SharedState};
+use crate::auth::permissions::{Permission, require};
+use crate::idempotency::idempotent;
 use axum::Router;
-use axum::routing::{get, post};
+use axum::routing::{get, post, put};
 use std::sync::Arc;
 
 pub fn admin_router(state: SharedState) -> Router<Arc<AppState>> {
     Router::new()
+        // Sign-up for a new hospital and its first admin
         .route("/hospitals", post(create_hospital_and_admin_handler))
-        .route(
-            "/hospitals/{hospital_id}",
-            get(get_hospital_info_handler).put(update_hospital_info_handler),
-        )
+        .merge(require(
+            &state,
+            Permission::ViewHospital,
+            Router::new()
+                .route("/hospitals/{hospital_id}", get(get_hospital_info_handler))
+                .route(
+                    "/hospitals/{hospital_id}/prices",
+                    get(get_price_catalog_handler),
+                ),
+        ))
+        .merge(require(
+            &state,
+            Permission::ManageHospital,
+            Router::new()
+                .route(
+                    "/hospitals/{hospital_id}",
+                    put(update_hospital_info_handler),
+                )
+                .merge(idempotent(
+                    &state,
+                    Router::new().route(
+                        "/hospitals/{hospital_id}/prices",
+                        post(create_price_catalog_entry_handler),
+                    ),
+                ))
+                .route(
+                    "/hospitals/{hospital_id}/prices/{price_id}",
+                    put(update_price_catalog_entry_handler)
+                        .delete(delete_price_catalog_entry_handler),
+                )
+                .route(
+                    "/hospitals/{hospital_id}/payment-settings",
+                    get(get_payment_settings_handler).put(update_payment_settings_handler),
+                ),
+        ))
+        .merge(require(
+            &state,
+            Permission::ManageUsers,
+            Router::new()
+                .route("/hospitals/{hospital_id}/users", get(get_users_handler))
+                .merge(idempotent(
+                    &state,
+                    Router::new()
+                        .route("/hospitals/{hospital_id}/users", post(invite_user_handler)),
+                ))
+                .route(
+                    "/hospitals/{hospital_id}/users/{user_id}/role",
+                    put(update_user_role_handler),
+                )
+                .route(
+                    "/hospitals/{hospital_id}/users/{user_id}/deactivate",
+                    post(deactivate_user_handler),
+                )
+                .route(
+                    "/hospitals/{hospital_id}/users/{user_id}/reactivate",
+                    post(reactivate_user_handler),
+                )
+                .route(
+                    "/hospitals/{hospital_id}/users/{user_id}/link",
+                    put(link_user_record_handler),
+                ),
+        ))
         .with_state(state)
 }
@@ -1,10 +1,22 @@
 use crate::admin::models::{
-    Hospital, HospitalData, HospitalWithAdminEmail, UpdateHospital, User, UserRole,
+    AppliedPrice, CreatePriceCatalogEntry, Hospital, HospitalData, HospitalWithAdminEmail,
+    InviteUser, LinkUserRecord, PaymentSettings, PaymentSettingsResponse, PriceCatalog,
+    PriceCatalogEntry, UpdateHospital, UpdatePaymentSettings, UpdatePriceCatalogEntry,
+    UpdateUserRole, User, UserList, UserRole,
 };
+use crate::appointments::models::VisitType;
 use crate::auth::headers::ClaimsHeader;
+use crate::auth::service::{revoke_user_sessions, send_email_verification, send_invitation};
+use crate::config::{DEFAULT_APPOINTMENT_PRICE, DEFAULT_CURRENCY, OPAQUE_TOKEN_LENGTH};
+use crate::doctor::models::Doctor;
+use crate::doctor::service::get_doctor_by_id;
 use crate::errors::AppError;
+use crate::money::{Money, minor_unit_exponent};
+use crate::utils::{create_random_string, hash_password};
 use crate::{admin::models::CreateHospital, app_state::SharedState};
-use sqlx::Error as SqlxError;
+use reqwest::Url;
+use sqlx::{Error as SqlxError, PgConnection};
+use tracing::warn;
 use uuid::Uuid;
 
 pub async fn create_new_hospital_and_admin(
@@ -28,11 +40,18 @@ pub async fn create_new_hospital_and_admin(
         .bind(&admin.name)
         .bind(&admin.email)
         .bind(&admin.password_hash)
-        .bind(&admin.role)
-        .bind(&admin.hospital_id)
+        .bind(admin.role)
+        .bind(admin.hospital_id)
         .execute(&state.db_pool)
         .await
         .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    // The admin can ask for another email, so a failed delivery does not undo the sign-up
+    if let Err(e) = send_email_verification(&state, &admin).await {
+        warn!(
+            "Failed to send verification email to {}: {}",
+            admin.email, e
+        );
+    }
 
     Ok(HospitalWithAdminEmail {
         hospital,
@@ -84,19 +103,7 @@ pub async fn update_hospital_info(
     update_data: UpdateHospital,
     claim: ClaimsHeader,
 ) -> Result<Hospital, AppError> {
-    match claim.role {
-        UserRole::Admin => (),
-        _ => {
-            return Err(AppError::Unauthorized(
-                "Only admin users can update hospital information".to_string(),
-            ));
-        }
-    }
-    if claim.hospital_id != hospital_id {
-        return Err(AppError::Unauthorized(
-            "You cannot update information for a hospital you do not belong to".to_string(),
-        ));
-    }
+    ensure_hospital_admin(&claim, hospital_id, "update hospital information")?;
 
     let hospital = sqlx::query_as::<_, Hospital>("UPDATE hospitals SET name = COALESCE($1, name), address = COALESCE($2, address), phone = COALESCE($3, phone) WHERE id = $4 RETURNING *")
         .bind(update_data.name)
@@ -114,3 +121,717 @@ pub async fn update_hospital_info(
 
     Ok(hospital)
 }
+
+// A login account linked to a doctor or patient record must belong to the same hospital
+pub async fn validate_user_link(
+    state: &SharedState,
+    user_id: Option<Uuid>,
+    hospital_id: Option<Uuid>,
+) -> Result<(), AppError> {
+    let Some(user_id) = user_id else {
+        return Ok(());
+    };
+    let user_hospital =
+        sqlx::query_scalar::<_, Option<Uuid>>("SELECT hospital_id FROM users WHERE id = $1")
+            .bind(user_id)
+            .fetch_optional(&state.db_pool)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    match user_hospital {
+        Some(user_hospital) if user_hospital == hospital_id => Ok(()),
+        _ => Err(AppError::UnProcessableEntity {
+            field: "user_id".to_string(),
+            message: "No user with this id belongs to the hospital".to_string(),
+        }),
+    }
+}
+
+fn ensure_hospital_admin(
+    claim: &ClaimsHeader,
+    hospital_id: Uuid,
+    action: &str,
+) -> Result<(), AppError> {
+    match claim.role {
+        UserRole::SuperAdmin => return Ok(()),
+        UserRole::Admin => (),
+        _ => {
+            return Err(AppError::Forbidden(format!(
+                "Only admin users can {action}"
+            )));
+        }
+    }
+    if claim.hospital_id != hospital_id {
+        return Err(AppError::Forbidden(format!(
+            "You cannot {action} for a hospital you do not belong to"
+        )));
+    }
+    Ok(())
+}
+
+pub async fn get_price_catalog(
+    state: SharedState,
+    hospital_id: Uuid,
+    claim: ClaimsHeader,
+) -> Result<PriceCatalog, AppError> {
+    if claim.tenant().is_some_and(|id| id != hospital_id) {
+        return Err(AppError::Forbidden(
+            "You cannot view prices for a hospital you do not belong to".to_string(),
+        ));
+    }
+    let entries = sqlx::query_as::<_, PriceCatalogEntry>(
+        "SELECT * FROM price_catalog WHERE hospital_id = $1 ORDER BY specialization, visit_type, doctor_id NULLS FIRST",
+    )
+    .bind(hospital_id)
+    .fetch_all(&state.db_pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    Ok(PriceCatalog { entries })
+}
+
+pub async fn create_price_catalog_entry(
+    state: SharedState,
+    hospital_id: Uuid,
+    data: CreatePriceCatalogEntry,
+    claim: ClaimsHeader,
+) -> Result<PriceCatalogEntry, AppError> {
+    ensure_hospital_admin(&claim, hospital_id, "manage prices")?;
+    validate_price(data.price)?;
+    if let Some(doctor_id) = data.doctor_id {
+        let doctor = get_doctor_by_id(state.clone(), doctor_id.to_string())
+            .await
+            .map_err(|_| AppError::NotFound(format!("Doctor with id {doctor_id} not found")))?;
+        if doctor.hospital_id.is_some_and(|id| id != hospital_id) {
+            return Err(AppError::UnProcessableEntity {
+                field: "doctor_id".to_string(),
+                message: "The doctor does not work at this hospital".to_string(),
+            });
+        }
+    }
+
+    sqlx::query_as::<_, PriceCatalogEntry>(
+        "INSERT INTO price_catalog (id, hospital_id, specialization, visit_type, doctor_id, price) VALUES ($1, $2, $3, $4, $5, $6) RETURNING *",
+    )
+    .bind(Uuid::new_v4())
+    .bind(hospital_id)
+    .bind(data.specialization.trim())
+    .bind(data.visit_type)
+    .bind(data.doctor_id)
+    .bind(data.price)
+    .fetch_one(&state.db_pool)
+    .await
+    .map_err(|e| match e.as_database_error() {
+        Some(db_error) if db_error.is_unique_violation() => AppError::Conflict(
+            "A price for this specialization, visit type and doctor already exists".to_string(),
+        ),
+        _ => AppError::DatabaseError(e.to_string()),
+    })
+}
+
+pub async fn update_price_catalog_entry(
+    state: SharedState,
+    hospital_id: Uuid,
+    entry_id: Uuid,
+    data: UpdatePriceCatalogEntry,
+    claim: ClaimsHeader,
+) -> Result<PriceCatalogEntry, AppError> {
+    ensure_hospital_admin(&claim, hospital_id, "manage prices")?;
+    validate_price(data.price)?;
+
+    sqlx::query_as::<_, PriceCatalogEntry>(
+        "UPDATE price_catalog SET price = $1 WHERE id = $2 AND hospital_id = $3 RETURNING *",
+    )
+    .bind(data.price)
+    .bind(entry_id)
+    .bind(hospital_id)
+    .fetch_one(&state.db_pool)
+    .await
+    .map_err(|e| match e {
+        SqlxError::RowNotFound => AppError::NotFound(format!(
+            "Price catalog entry with id {} not found",
+            entry_id
+        )),
+        _ => AppError::DatabaseError(e.to_string()),
+    })
+}
+
+pub async fn delete_price_catalog_entry(
+    state: SharedState,
+    hospital_id: Uuid,
+    entry_id: Uuid,
+    claim: ClaimsHeader,
+) -> Result<(), AppError> {
+    ensure_hospital_admin(&claim, hospital_id, "manage prices")?;
+
+    let result = sqlx::query("DELETE FROM price_catalog WHERE id = $1 AND hospital_id = $2")
+        .bind(entry_id)
+        .bind(hospital_id)
+        .execute(&state.db_pool)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound(format!(
+            "Price catalog entry with id {} not found",
+            entry_id
+        )));
+    }
+    Ok(())
+}
+
+fn validate_price(price: Money) -> Result<(), AppError> {
+    if !price.is_positive() {
+        return Err(AppError::UnProcessableEntity {
+            field: "price".to_string(),
+            message: "Price must be greater than zero".to_string(),
+        });
+    }
+    // Catalog prices are charged as-is, so they must be payable in the default currency
+    price.to_minor_units(DEFAULT_CURRENCY).map(|_| ())
+}
+
+// Price for a visit with `doctor`: the doctor's own catalog entry, then the hospital's entry for
+// the specialization, then DEFAULT_APPOINTMENT_PRICE when the hospital has not priced it. The
+// catalog is the doctor's hospital's, or `hospital_id` (the patient's) for a doctor without one.
+pub async fn resolve_appointment_price(
+    state: SharedState,
+    hospital_id: Option<Uuid>,
+    doctor: &Doctor,
+    visit_type: VisitType,
+) -> Result<AppliedPrice, AppError> {
+    let Some(hospital_id) = doctor.hospital_id.or(hospital_id) else {
+        return Ok(AppliedPrice {
+            amount: DEFAULT_APPOINTMENT_PRICE,
+            catalog_id: None,
+        });
+    };
+
+    let entry = sqlx::query_as::<_, PriceCatalogEntry>(
+        "SELECT * FROM price_catalog WHERE hospital_id = $1 AND LOWER(specialization) = LOWER($2) AND visit_type = $3 AND (doctor_id = $4 OR doctor_id IS NULL) ORDER BY doctor_id NULLS LAST LIMIT 1",
+    )
+    .bind(hospital_id)
+    .bind(&doctor.specialization)
+    .bind(visit_type)
+    .bind(doctor.id)
+    .fetch_optional(&state.db_pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    Ok(match entry {
+        Some(entry) => AppliedPrice {
+            amount: entry.price,
+            catalog_id: Some(entry.id),
+        },
+        None => AppliedPrice {
+            amount: DEFAULT_APPOINTMENT_PRICE,
+            catalog_id: None,
+        },
+    })
+}
+
+pub async fn get_payment_settings(
+    state: SharedState,
+    hospital_id: Uuid,
+    claim: ClaimsHeader,
+) -> Result<PaymentSettingsResponse, AppError> {
+    ensure_hospital_admin(&claim, hospital_id, "view payment settings")?;
+    let settings = payment_settings_for_hospital(&state, hospital_id).await?;
+    Ok(payment_settings_response(hospital_id, settings))
+}
+
+// Creates or changes a hospital's payment settings. Fields left out keep their current value;
+// an empty string removes an optional one.
+pub async fn update_payment_settings(
+    state: SharedState,
+    hospital_id: Uuid,
+    data: UpdatePaymentSettings,
+    claim: ClaimsHeader,
+) -> Result<PaymentSettingsResponse, AppError> {
+    ensure_hospital_admin(&claim, hospital_id, "manage payment settings")?;
+    let current = payment_settings_for_hospital(&state, hospital_id).await?;
+
+    let secret_key = match data.paystack_secret_key.as_deref().map(str::trim) {
+        None => current.as_ref().and_then(|s| s.paystack_secret_key.clone()),
+        Some("") => None,
+        Some(key) if key.starts_with("sk_") => Some(state.settings_cipher.encrypt(key)?),
+        Some(_) => {
+            return Err(AppError::UnProcessableEntity {
+                field: "paystack_secret_key".to_string(),
+                message: "A Paystack secret key starts with sk_".to_string(),
+            });
+        }
+    };
+    let subaccount = match data.paystack_subaccount.as_deref().map(str::trim) {
+        None => current.as_ref().and_then(|s| s.paystack_subaccount.clone()),
+        Some("") => None,
+        Some(code) if code.starts_with("ACCT_") => Some(code.to_string()),
+        Some(_) => {
+            return Err(AppError::UnProcessableEntity {
+                field: "paystack_subaccount".to_string(),
+                message: "A Paystack subaccount code starts with ACCT_".to_string(),
+            });
+        }
+    };
+    let callback_url = match data.callback_url.as_deref().map(str::trim) {
+        None => current.as_ref().and_then(|s| s.callback_url.clone()),
+        Some("") => None,
+        Some(url) => match Url::parse(url) {
+            Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => Some(url.to_string()),
+            _ => {
+                return Err(AppError::UnProcessableEntity {
+                    field: "callback_url".to_string(),
+                    message: "Callback URL must be an http or https URL".to_string(),
+                });
+            }
+        },
+    };
+    let default_currency = match data.default_currency {
+        Some(currency) => {
+            let currency = currency.trim().to_uppercase();
+            minor_unit_exponent(&currency)?;
+            currency
+        }
+        None => current
+            .map(|s| s.default_currency)
+            .unwrap_or_else(|| DEFAULT_CURRENCY.to_string()),
+    };
+
+    let settings = sqlx::query_as::<_, PaymentSettings>(
+        "INSERT INTO hospital_payment_settings (hospital_id, paystack_secret_key, paystack_subaccount, callback_url, default_currency, updated_by, updated_at) \
+         VALUES ($1, $2, $3, $4, $5, $6, NOW()) \
+         ON CONFLICT (hospital_id) DO UPDATE SET paystack_secret_key = EXCLUDED.paystack_secret_key, \
+         paystack_subaccount = EXCLUDED.paystack_subaccount, callback_url = EXCLUDED.callback_url, \
+         default_currency = EXCLUDED.default_currency, updated_by = EXCLUDED.updated_by, updated_at = NOW() \
+         RETURNING *",
+    )
+    .bind(hospital_id)
+    .bind(secret_key)
+    .bind(subaccount)
+    .bind(callback_url)
+    .bind(default_currency)
+    .bind(claim.sub)
+    .fetch_one(&state.db_pool)
+    .await
+    .map_err(|e| match e.as_database_error() {
+        Some(db_error) if db_error.is_foreign_key_violation() => {
+            AppError::NotFound(format!("Hospital with id {} not found", hospital_id))
+        }
+        _ => AppError::DatabaseError(e.to_string()),
+    })?;
+
+    Ok(payment_settings_response(hospital_id, Some(settings)))
+}
+
+fn payment_settings_response(
+    hospital_id: Uuid,
+    settings: Option<PaymentSettings>,
+) -> PaymentSettingsResponse {
+    match settings {
+        Some(settings) => PaymentSettingsResponse {
+            hospital_id: settings.hospital_id,
+            has_paystack_secret_key: settings.paystack_secret_key.is_some(),
+            paystack_subaccount: settings.paystack_subaccount,
+            callback_url: settings.callback_url,
+            default_currency: settings.default_currency,
+            updated_by: settings.updated_by,
+            updated_at: Some(settings.updated_at),
+        },
+        None => PaymentSettingsResponse {
+            hospital_id,
+            has_paystack_secret_key: false,
+            paystack_subaccount: None,
+            callback_url: None,
+            default_currency: DEFAULT_CURRENCY.to_string(),
+            updated_by: None,
+            updated_at: None,
+        },
+    }
+}
+
+pub async fn payment_settings_for_hospital(
+    state: &SharedState,
+    hospital_id: Uuid,
+) -> Result<Option<PaymentSettings>, AppError> {
+    sqlx::query_as::<_, PaymentSettings>(
+        "SELECT * FROM hospital_payment_settings WHERE hospital_id = $1",
+    )
+    .bind(hospital_id)
+    .fetch_optional(&state.db_pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))
+}
+
+// Settings of the hospital an appointment belongs to
+pub async fn payment_settings_for_appointment(
+    state: &SharedState,
+    appointment_id: Uuid,
+) -> Result<Option<PaymentSettings>, AppError> {
+    sqlx::query_as::<_, PaymentSettings>(
+        "SELECT s.* FROM appointments a \
+         JOIN hospital_payment_settings s ON s.hospital_id = a.hospital_id \
+         WHERE a.id = $1",
+    )
+    .bind(appointment_id)
+    .fetch_optional(&state.db_pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))
+}
+
+// Users of a hospital, optionally only those with `role` or with the given active state
+pub async fn get_users(
+    state: SharedState,
+    hospital_id: Uuid,
+    role: Option<UserRole>,
+    is_active: Option<bool>,
+    claim: ClaimsHeader,
+) -> Result<UserList, AppError> {
+    ensure_hospital_admin(&claim, hospital_id, "view users")?;
+    let users = sqlx::query_as::<_, User>(
+        "SELECT * FROM users WHERE hospital_id = $1 AND ($2::VARCHAR IS NULL OR role = $2) \
+         AND ($3::BOOLEAN IS NULL OR is_active = $3) ORDER BY name",
+    )
+    .bind(hospital_id)
+    .bind(role)
+    .bind(is_active)
+    .fetch_all(&state.db_pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    Ok(UserList { users })
+}
+
+// Creates a login account in the hospital and emails the user a token to choose their password
+pub async fn invite_user(
+    state: SharedState,
+    hospital_id: Uuid,
+    data: InviteUser,
+    claim: ClaimsHeader,
+) -> Result<User, AppError> {
+    ensure_hospital_admin(&claim, hospital_id, "invite users")?;
+    ensure_role_assignable(&claim, data.role)?;
+    let name = data.name.trim();
+    if name.is_empty() {
+        return Err(AppError::UnProcessableEntity {
+            field: "name".to_string(),
+            message: "Name is required".to_string(),
+        });
+    }
+    let email = data.email.trim();
+    if !email.contains('@') {
+        return Err(AppError::UnProcessableEntity {
+            field: "email".to_string(),
+            message: "Not a valid email address".to_string(),
+        });
+    }
+    let hospital = get_hospital_by_id(state.clone(), hospital_id).await?;
+
+    // Nobody knows this password; the user sets their own from the invitation
+    let password_hash = hash_password(&create_random_string(OPAQUE_TOKEN_LENGTH))?;
+    let user = sqlx::query_as::<_, User>(
+        "INSERT INTO users (id, name, email, password_hash, role, hospital_id) VALUES ($1, $2, $3, $4, $5, $6) RETURNING *",
+    )
+    .bind(Uuid::new_v4())
+    .bind(name)
+    .bind(email)
+    .bind(password_hash)
+    .bind(data.role)
+    .bind(hospital_id)
+    .fetch_one(&state.db_pool)
+    .await
+    .map_err(|e| match e.as_database_error() {
+        Some(db_error) if db_error.is_unique_violation() => {
+            AppError::Conflict(format!("A user with email {} already exists", email))
+        }
+        _ => AppError::DatabaseError(e.to_string()),
+    })?;
+    // They can still get in through the forgot-password flow
+    if let Err(e) = send_invitation(&state, &user, &hospital.name).await {
+        warn!("Failed to send invitation email to {}: {}", user.email, e);
+    }
+    Ok(user)
+}
+
+// Changes a user's role. Their current tokens carry the old role, so they are logged out.
+pub async fn update_user_role(
+    state: SharedState,
+    hospital_id: Uuid,
+    user_id: Uuid,
+    data: UpdateUserRole,
+    claim: ClaimsHeader,
+) -> Result<User, AppError> {
+    ensure_hospital_admin(&claim, hospital_id, "change user roles")?;
+    if user_id == claim.sub {
+        return Err(AppError::Conflict(
+            "You cannot change your own role".to_string(),
+        ));
+    }
+    ensure_role_assignable(&claim, data.role)?;
+    let mut tx = state
+        .db_pool
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    let user = get_hospital_user(&mut tx, hospital_id, user_id).await?;
+    ensure_role_assignable(&claim, user.role)?;
+    if user.role == data.role {
+        return Ok(user);
+    }
+    let user = sqlx::query_as::<_, User>("UPDATE users SET role = $1 WHERE id = $2 RETURNING *")
+        .bind(data.role)
+        .bind(user_id)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    revoke_user_sessions(&mut tx, user_id).await?;
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    Ok(user)
+}
+
+// Deactivating a user logs them out everywhere and stops them logging in until reactivated
+pub async fn set_user_active(
+    state: SharedState,
+    hospital_id: Uuid,
+    user_id: Uuid,
+    is_active: bool,
+    claim: ClaimsHeader,
+) -> Result<User, AppError> {
+    let action = if is_active {
+        "reactivate users"
+    } else {
+        "deactivate users"
+    };
+    ensure_hospital_admin(&claim, hospital_id, action)?;
+    if user_id == claim.sub && !is_active {
+        return Err(AppError::Conflict(
+            "You cannot deactivate your own account".to_string(),
+        ));
+    }
+    let mut tx = state
+        .db_pool
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    let user = get_hospital_user(&mut tx, hospital_id, user_id).await?;
+    ensure_role_assignable(&claim, user.role)?;
+    let user =
+        sqlx::query_as::<_, User>("UPDATE users SET is_active = $1 WHERE id = $2 RETURNING *")
+            .bind(is_active)
+            .bind(user_id)
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    if !is_active {
+        revoke_user_sessions(&mut tx, user_id).await?;
+    }
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    Ok(user)
+}
+
+// Makes a user the login account of a doctor or patient record in their hospital, replacing any
+// record they were linked to before. The user's role has to match the kind of record.
+pub async fn link_user_record(
+    state: SharedState,
+    hospital_id: Uuid,
+    user_id: Uuid,
+    data: LinkUserRecord,
+    claim: ClaimsHeader,
+) -> Result<User, AppError> {
+    ensure_hospital_admin(&claim, hospital_id, "link users to records")?;
+    let (table, kind, role, record_id) = match (data.doctor_id, data.patient_id) {
+        (Some(doctor_id), None) => ("doctors", "Doctor", UserRole::Doctor, doctor_id),
+        (None, Some(patient_id)) => ("patients", "Patient", UserRole::Patient, patient_id),
+        _ => {
+            return Err(AppError::UnProcessableEntity {
+                field: "doctor_id".to_string(),
+                message: "Give either a doctor_id or a patient_id".to_string(),
+            });
+        }
+    };
+    let mut tx = state
+        .db_pool
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    let user = get_hospital_user(&mut tx, hospital_id, user_id).await?;
+    if user.role != role {
+        return Err(AppError::UnProcessableEntity {
+            field: format!("{}_id", kind.to_lowercase()),
+            message: format!(
+                "Only users with the {:?} role can be linked to a {}",
+                role,
+                kind.to_lowercase()
+            ),
+        });
+    }
+    let linked = sqlx::query_scalar::<_, Option<Uuid>>(&format!(
+        "SELECT user_id FROM {table} WHERE id = $1 AND hospital_id = $2 FOR UPDATE"
+    ))
+    .bind(record_id)
+    .bind(hospital_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?
+    .ok_or_else(|| AppError::NotFound(format!("{} with id {} not found", kind, record_id)))?;
+    if linked.is_some_and(|linked| linked != user_id) {
+        return Err(AppError::Conflict(format!(
+            "{} {} is already linked to another user",
+            kind, record_id
+        )));
+    }
+
+    sqlx::query(&format!(
+        "UPDATE {table} SET user_id = NULL WHERE user_id = $1 AND id <> $2"
+    ))
+    .bind(user_id)
+    .bind(record_id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    sqlx::query(&format!("UPDATE {table} SET user_id = $1 WHERE id = $2"))
+        .bind(user_id)
+        .bind(record_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    Ok(user)
+}
+
+async fn get_hospital_user(
+    conn: &mut PgConnection,
+    hospital_id: Uuid,
+    user_id: Uuid,
+) -> Result<User, AppError> {
+    sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = $1 AND hospital_id = $2 FOR UPDATE")
+        .bind(user_id)
+        .bind(hospital_id)
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?
+        .ok_or_else(|| AppError::NotFound(format!("User with id {} not found", user_id)))
+}
+
+// Only super admins can create, change or deactivate super admin accounts
+fn ensure_role_assignable(claim: &ClaimsHeader, role: UserRole) -> Result<(), AppError> {
+    if role == UserRole::SuperAdmin && claim.role != UserRole::SuperAdmin {
+        return Err(AppError::Forbidden(
+            "Only super admins can manage super admin accounts".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app_state::AppState;
+    use crate::billing::gateway::FakeGateway;
+    use crate::mailer::LogMailer;
+    use crate::utils::SettingsCipher;
+    use sqlx::PgPool;
+    use std::sync::Arc;
+
+    async fn insert_doctor(pool: &PgPool, hospital_id: Option<Uuid>) -> Doctor {
+        let id = Uuid::new_v4();
+        sqlx::query("INSERT INTO doctors (id, name, specialization, available_days, hospital_id) VALUES ($1, 'Dr Okafor', 'Cardiology', ARRAY['Monday'], $2)")
+            .bind(id)
+            .bind(hospital_id)
+            .execute(pool)
+            .await
+            .unwrap();
+        sqlx::query_as::<_, Doctor>("SELECT * FROM doctors WHERE id = $1")
+            .bind(id)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    async fn insert_price(
+        pool: &PgPool,
+        hospital_id: Uuid,
+        doctor_id: Option<Uuid>,
+        visit_type: VisitType,
+        price: &str,
+    ) -> Uuid {
+        let id = Uuid::new_v4();
+        sqlx::query("INSERT INTO price_catalog (id, hospital_id, specialization, visit_type, doctor_id, price) VALUES ($1, $2, 'cardiology', $3, $4, $5::NUMERIC)")
+            .bind(id)
+            .bind(hospital_id)
+            .bind(visit_type)
+            .bind(doctor_id)
+            .bind(price)
+            .execute(pool)
+            .await
+            .unwrap();
+        id
+    }
+
+    #[sqlx::test]
+    async fn applies_catalog_prices(pool: PgPool) {
+        let state = Arc::new(AppState::new(
+            pool.clone(),
+            Arc::new(FakeGateway::default()),
+            Arc::new(LogMailer),
+            None,
+            SettingsCipher::default(),
+        ));
+        let hospital_id = Uuid::new_v4();
+        sqlx::query("INSERT INTO hospitals (id, name, address, phone) VALUES ($1, 'Lagos General', '1 Marina Road', '08000000000')")
+            .bind(hospital_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let doctor = insert_doctor(&pool, Some(hospital_id)).await;
+        let unassigned = insert_doctor(&pool, None).await;
+        let specialization =
+            insert_price(&pool, hospital_id, None, VisitType::Consultation, "15000").await;
+        let own = insert_price(
+            &pool,
+            hospital_id,
+            Some(doctor.id),
+            VisitType::Consultation,
+            "20000",
+        )
+        .await;
+
+        // The doctor's own entry wins over the specialization's
+        let price =
+            resolve_appointment_price(state.clone(), None, &doctor, VisitType::Consultation)
+                .await
+                .unwrap();
+        assert_eq!(price.amount, Money::from_hundredths(2_000_000));
+        assert_eq!(price.catalog_id, Some(own));
+
+        // A doctor without a hospital is priced from the patient's
+        let price = resolve_appointment_price(
+            state.clone(),
+            Some(hospital_id),
+            &unassigned,
+            VisitType::Consultation,
+        )
+        .await
+        .unwrap();
+        assert_eq!(price.amount, Money::from_hundredths(1_500_000));
+        assert_eq!(price.catalog_id, Some(specialization));
+
+        // Visits the hospital has not priced cost the default
+        let price = resolve_appointment_price(
+            state.clone(),
+            Some(hospital_id),
+            &doctor,
+            VisitType::Emergency,
+        )
+        .await
+        .unwrap();
+        assert_eq!(price.amount, DEFAULT_APPOINTMENT_PRICE);
+        assert_eq!(price.catalog_id, None);
+        let price = resolve_appointment_price(state, None, &unassigned, VisitType::Consultation)
+            .await
+            .unwrap();
+        assert_eq!(price.amount, DEFAULT_APPOINTMENT_PRICE);
+    }
+}
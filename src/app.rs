@@ -1,6 +1,10 @@
 use crate::app_state::{AppState, SharedState};
+use crate::billing::{gateway, service::run_reconciliation};
 use crate::config::AppConfig;
+use crate::idempotency::purge_expired_keys;
+use crate::mailer;
 use crate::router::create_router;
+use crate::utils::SettingsCipher;
 use axum::serve;
 use sqlx::PgPool;
 use std::error::Error;
@@ -13,7 +17,24 @@ pub async fn start_app() -> Result<(), Box<dyn Error>> {
         .await
         .expect("Failed to connect to the database");
 
-    let app_state = SharedState::new(AppState::new(db_pool));
+    let payment_gateway =
+        gateway::from_config(&app_config).expect("Failed to configure the payment gateway");
+    let mailer = mailer::from_config(&app_config).expect("Failed to configure the mailer");
+    let settings_cipher =
+        SettingsCipher::from_config(&app_config).expect("Failed to configure settings encryption");
+    let app_state = SharedState::new(AppState::new(
+        db_pool,
+        payment_gateway,
+        mailer,
+        app_config.app_url.clone(),
+        settings_cipher,
+    ));
+    tokio::spawn(run_reconciliation(
+        app_state.clone(),
+        app_config.reconcile_interval_seconds,
+        app_config.pending_bill_minutes,
+    ));
+    tokio::spawn(purge_expired_keys(app_state.clone()));
     let app = create_router(app_state);
     let local_server = format!("127.0.0.1:{}", &app_config.server_port);
     let listener = TcpListener::bind(&local_server).await.unwrap();
@@ -1,14 +1,33 @@
+use crate::billing::gateway::PaymentGateway;
+use crate::mailer::Mailer;
+use crate::utils::SettingsCipher;
 use sqlx::PgPool;
 use std::sync::Arc;
 
 #[derive(Debug, Clone)]
 pub struct AppState {
     pub db_pool: PgPool,
+    pub payment_gateway: Arc<dyn PaymentGateway>,
+    pub mailer: Arc<dyn Mailer>,
+    pub app_url: Option<String>, // Front end that emailed links point at
+    pub settings_cipher: SettingsCipher,
 }
 
 impl AppState {
-    pub fn new(db_pool: PgPool) -> Self {
-        Self { db_pool }
+    pub fn new(
+        db_pool: PgPool,
+        payment_gateway: Arc<dyn PaymentGateway>,
+        mailer: Arc<dyn Mailer>,
+        app_url: Option<String>,
+        settings_cipher: SettingsCipher,
+    ) -> Self {
+        Self {
+            db_pool,
+            payment_gateway,
+            mailer,
+            app_url,
+            settings_cipher,
+        }
     }
 }
 
@@ -2,14 +2,23 @@ use axum::extract::{Path, Query, State};
 use std::collections::HashMap;
 use uuid::Uuid;
 
-use crate::appointments::models::CreateAppointmentRequest;
+use crate::appointments::models::{
+    CreateAppointmentOrder, CreateAppointmentRequest, CreateAppointmentSeriesRequest,
+    JoinWaitlistRequest, RescheduleAppointmentRequest,
+};
 use crate::appointments::service;
+use crate::auth::headers::ClaimsHeader;
+use crate::auth::permissions::{
+    ensure_appointment_access, ensure_patient_access, ensure_series_access, ensure_waitlist_access,
+    patient_scope,
+};
 use crate::{app_state::SharedState, errors::AppError};
 use axum::{Json, http::StatusCode, response::IntoResponse};
 use serde_json::json;
 
 pub async fn get_appointments_handler(
     State(state): State<SharedState>,
+    claims: ClaimsHeader,
     Query(params): Query<HashMap<String, String>>,
 ) -> impl IntoResponse {
     let patient_id = match params.get("patient_id").map(|s| Uuid::parse_str(s)) {
@@ -23,6 +32,10 @@ pub async fn get_appointments_handler(
         }
         None => None,
     };
+    let patient_id = match patient_scope(&state, &claims, patient_id).await {
+        Ok(patient_id) => patient_id,
+        Err(e) => return e.into_response(),
+    };
 
     let doctor_id = match params.get("doctor_id").map(|s| Uuid::parse_str(s)) {
         Some(Ok(id)) => Some(id),
@@ -36,7 +49,7 @@ pub async fn get_appointments_handler(
         None => None,
     };
 
-    match service::get_appointments(state, patient_id, doctor_id).await {
+    match service::get_appointments(state, patient_id, doctor_id, claims.tenant()).await {
         Ok(appointments) => Json(appointments).into_response(),
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
@@ -48,8 +61,12 @@ pub async fn get_appointments_handler(
 
 pub async fn create_appointment_handler(
     State(state): State<SharedState>,
+    claims: ClaimsHeader,
     Json(payload): Json<CreateAppointmentRequest>,
 ) -> impl IntoResponse {
+    if let Err(e) = ensure_patient_access(&state, &claims, payload.patient_id).await {
+        return e.into_response();
+    }
     match service::create_appointment(state, payload).await {
         Ok(appointment) => (StatusCode::CREATED, Json(appointment)).into_response(),
         Err(e) => match e {
@@ -68,6 +85,12 @@ pub async fn create_appointment_handler(
                 Json(json!({"error": format!("{}: {}", field, message)})),
             )
                 .into_response(),
+            AppError::NotFound(e) => {
+                (StatusCode::NOT_FOUND, Json(json!({"error": e.to_string()}))).into_response()
+            }
+            AppError::Conflict(e) => {
+                (StatusCode::CONFLICT, Json(json!({"error": e.to_string()}))).into_response()
+            }
             _ => (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 Json(json!({"error": e.to_string()})),
@@ -79,9 +102,13 @@ pub async fn create_appointment_handler(
 
 pub async fn get_appointment_by_id_handler(
     State(state): State<SharedState>,
+    claims: ClaimsHeader,
     Path(appointment_id): Path<String>,
 ) -> impl IntoResponse {
-    match service::get_appointment_by_id(state, appointment_id).await {
+    if let Err(e) = ensure_appointment_access(&state, &claims, &appointment_id).await {
+        return e.into_response();
+    }
+    match service::get_appointment_by_id(state, appointment_id, claims.tenant()).await {
         Ok(appointment) => Json(appointment).into_response(),
         Err(e) => match e {
             AppError::DatabaseError(_) => (
@@ -105,6 +132,7 @@ pub async fn get_appointment_by_id_handler(
 
 pub async fn update_appointment_status_handler(
     State(state): State<SharedState>,
+    claims: ClaimsHeader,
     Path(appointment_id): Path<String>,
     Query(params): Query<HashMap<String, String>>,
 ) -> impl IntoResponse {
@@ -118,9 +146,353 @@ pub async fn update_appointment_status_handler(
                 .into_response();
         }
     };
+    let reason = params.get("reason").cloned();
+    if let Err(e) = ensure_appointment_access(&state, &claims, &appointment_id).await {
+        return e.into_response();
+    }
 
-    match service::update_appointment_status(state, appointment_id, status).await {
+    match service::update_appointment_status(state, appointment_id, status, reason, claims).await {
         Ok(appointment) => Json(appointment).into_response(),
+        Err(e) => match e {
+            AppError::DatabaseError(_) => (
+                StatusCode::NOT_FOUND,
+                Json(json!({"error": "Appointment not found"})),
+            )
+                .into_response(),
+            AppError::ParsingError(e) => (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response(),
+            AppError::UnProcessableEntity { field, message } => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(json!({"error": format!("{}: {}", field, message)})),
+            )
+                .into_response(),
+            AppError::Conflict(e) => {
+                (StatusCode::CONFLICT, Json(json!({"error": e.to_string()}))).into_response()
+            }
+            _ => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response(),
+        },
+    }
+}
+
+pub async fn reschedule_appointment_handler(
+    State(state): State<SharedState>,
+    claims: ClaimsHeader,
+    Path(appointment_id): Path<String>,
+    Json(payload): Json<RescheduleAppointmentRequest>,
+) -> impl IntoResponse {
+    if let Err(e) = ensure_appointment_access(&state, &claims, &appointment_id).await {
+        return e.into_response();
+    }
+    match service::reschedule_appointment(state, appointment_id, payload, claims).await {
+        Ok(appointment) => (StatusCode::CREATED, Json(appointment)).into_response(),
+        Err(e) => match e {
+            AppError::DatabaseError(_) => (
+                StatusCode::NOT_FOUND,
+                Json(json!({"error": "Appointment not found"})),
+            )
+                .into_response(),
+            AppError::ParsingError(e) => (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response(),
+            AppError::UnProcessableEntity { field, message } => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(json!({"error": format!("{}: {}", field, message)})),
+            )
+                .into_response(),
+            AppError::NotFound(e) => {
+                (StatusCode::NOT_FOUND, Json(json!({"error": e.to_string()}))).into_response()
+            }
+            AppError::Conflict(e) => {
+                (StatusCode::CONFLICT, Json(json!({"error": e.to_string()}))).into_response()
+            }
+            _ => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response(),
+        },
+    }
+}
+
+pub async fn get_appointment_events_handler(
+    State(state): State<SharedState>,
+    claims: ClaimsHeader,
+    Path(appointment_id): Path<String>,
+) -> impl IntoResponse {
+    if let Err(e) = ensure_appointment_access(&state, &claims, &appointment_id).await {
+        return e.into_response();
+    }
+    match service::get_appointment_events(state, appointment_id, claims.tenant()).await {
+        Ok(events) => Json(events).into_response(),
+        Err(e) => match e {
+            AppError::DatabaseError(_) => (
+                StatusCode::NOT_FOUND,
+                Json(json!({"error": "Appointment not found"})),
+            )
+                .into_response(),
+            AppError::ParsingError(e) => (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response(),
+            _ => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response(),
+        },
+    }
+}
+
+pub async fn join_waitlist_handler(
+    State(state): State<SharedState>,
+    claims: ClaimsHeader,
+    Json(payload): Json<JoinWaitlistRequest>,
+) -> impl IntoResponse {
+    if let Err(e) = ensure_patient_access(&state, &claims, payload.patient_id).await {
+        return e.into_response();
+    }
+    match service::join_waitlist(state, payload).await {
+        Ok(entry) => (StatusCode::CREATED, Json(entry)).into_response(),
+        Err(e) => match e {
+            AppError::UnProcessableEntity { field, message } => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(json!({"error": format!("{}: {}", field, message)})),
+            )
+                .into_response(),
+            _ => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response(),
+        },
+    }
+}
+
+pub async fn get_waitlist_handler(
+    State(state): State<SharedState>,
+    claims: ClaimsHeader,
+    Query(params): Query<HashMap<String, String>>,
+) -> impl IntoResponse {
+    let patient_id = match params.get("patient_id").map(|s| Uuid::parse_str(s)) {
+        Some(Ok(id)) => Some(id),
+        Some(Err(e)) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"Invalid patient_id": e.to_string()})),
+            )
+                .into_response();
+        }
+        None => None,
+    };
+    let patient_id = match patient_scope(&state, &claims, patient_id).await {
+        Ok(patient_id) => patient_id,
+        Err(e) => return e.into_response(),
+    };
+
+    match service::get_waitlist(state, patient_id, claims.tenant()).await {
+        Ok(entries) => Json(entries).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn leave_waitlist_handler(
+    State(state): State<SharedState>,
+    claims: ClaimsHeader,
+    Path(entry_id): Path<String>,
+) -> impl IntoResponse {
+    if let Err(e) = ensure_waitlist_access(&state, &claims, &entry_id).await {
+        return e.into_response();
+    }
+    match service::leave_waitlist(state, entry_id, claims.tenant()).await {
+        Ok(entry) => Json(entry).into_response(),
+        Err(e) => match e {
+            AppError::DatabaseError(_) => (
+                StatusCode::NOT_FOUND,
+                Json(json!({"error": "Waitlist entry not found"})),
+            )
+                .into_response(),
+            AppError::ParsingError(e) => (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response(),
+            AppError::Conflict(e) => {
+                (StatusCode::CONFLICT, Json(json!({"error": e.to_string()}))).into_response()
+            }
+            _ => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response(),
+        },
+    }
+}
+
+pub async fn create_appointment_series_handler(
+    State(state): State<SharedState>,
+    claims: ClaimsHeader,
+    Json(payload): Json<CreateAppointmentSeriesRequest>,
+) -> impl IntoResponse {
+    if let Err(e) = ensure_patient_access(&state, &claims, payload.patient_id).await {
+        return e.into_response();
+    }
+    match service::create_appointment_series(state, payload).await {
+        Ok(series) => (StatusCode::CREATED, Json(series)).into_response(),
+        Err(e) => match e {
+            AppError::ParsingError(e) => (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response(),
+            AppError::UnProcessableEntity { field, message } => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(json!({"error": format!("{}: {}", field, message)})),
+            )
+                .into_response(),
+            AppError::NotFound(e) => {
+                (StatusCode::NOT_FOUND, Json(json!({"error": e.to_string()}))).into_response()
+            }
+            AppError::Conflict(e) => {
+                (StatusCode::CONFLICT, Json(json!({"error": e.to_string()}))).into_response()
+            }
+            _ => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response(),
+        },
+    }
+}
+
+pub async fn get_appointment_series_handler(
+    State(state): State<SharedState>,
+    claims: ClaimsHeader,
+    Path(series_id): Path<String>,
+) -> impl IntoResponse {
+    if let Err(e) = ensure_series_access(&state, &claims, &series_id).await {
+        return e.into_response();
+    }
+    match service::get_appointment_series(state, series_id, claims.tenant()).await {
+        Ok(series) => Json(series).into_response(),
+        Err(e) => match e {
+            AppError::DatabaseError(_) => (
+                StatusCode::NOT_FOUND,
+                Json(json!({"error": "Appointment series not found"})),
+            )
+                .into_response(),
+            AppError::ParsingError(e) => (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response(),
+            _ => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response(),
+        },
+    }
+}
+
+pub async fn cancel_appointment_series_handler(
+    State(state): State<SharedState>,
+    claims: ClaimsHeader,
+    Path(series_id): Path<String>,
+    Query(params): Query<HashMap<String, String>>,
+) -> impl IntoResponse {
+    let reason = params.get("reason").cloned();
+    if let Err(e) = ensure_series_access(&state, &claims, &series_id).await {
+        return e.into_response();
+    }
+
+    match service::cancel_appointment_series(state, series_id, reason, claims).await {
+        Ok(series) => Json(series).into_response(),
+        Err(e) => match e {
+            AppError::DatabaseError(_) => (
+                StatusCode::NOT_FOUND,
+                Json(json!({"error": "Appointment series not found"})),
+            )
+                .into_response(),
+            AppError::ParsingError(e) => (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response(),
+            AppError::Conflict(e) => {
+                (StatusCode::CONFLICT, Json(json!({"error": e.to_string()}))).into_response()
+            }
+            _ => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response(),
+        },
+    }
+}
+
+pub async fn create_appointment_order_handler(
+    State(state): State<SharedState>,
+    claims: ClaimsHeader,
+    Path(appointment_id): Path<String>,
+    Json(payload): Json<CreateAppointmentOrder>,
+) -> impl IntoResponse {
+    if let Err(e) = ensure_appointment_access(&state, &claims, &appointment_id).await {
+        return e.into_response();
+    }
+    match service::create_appointment_order(state, appointment_id, payload, claims).await {
+        Ok(order) => (StatusCode::CREATED, Json(order)).into_response(),
+        Err(e) => match e {
+            AppError::DatabaseError(_) => (
+                StatusCode::NOT_FOUND,
+                Json(json!({"error": "Appointment not found"})),
+            )
+                .into_response(),
+            AppError::ParsingError(e) => (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response(),
+            AppError::UnProcessableEntity { field, message } => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(json!({"error": format!("{}: {}", field, message)})),
+            )
+                .into_response(),
+            AppError::Conflict(e) => {
+                (StatusCode::CONFLICT, Json(json!({"error": e.to_string()}))).into_response()
+            }
+            _ => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response(),
+        },
+    }
+}
+
+pub async fn get_appointment_orders_handler(
+    State(state): State<SharedState>,
+    claims: ClaimsHeader,
+    Path(appointment_id): Path<String>,
+) -> impl IntoResponse {
+    if let Err(e) = ensure_appointment_access(&state, &claims, &appointment_id).await {
+        return e.into_response();
+    }
+    match service::get_appointment_orders(state, appointment_id, claims.tenant()).await {
+        Ok(orders) => Json(orders).into_response(),
         Err(e) => match e {
             AppError::DatabaseError(_) => (
                 StatusCode::NOT_FOUND,
@@ -1,4 +1,8 @@
-use chrono::{DateTime, Utc};
+use crate::admin::models::AppliedPrice;
+use crate::billing::models::ItemKind;
+use crate::doctor::models::Doctor;
+use crate::money::Money;
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::{FromRow, Type};
 use uuid::Uuid;
@@ -9,26 +13,220 @@ pub struct Appointment {
     pub id: Uuid,
     pub patient_id: Uuid,
     pub doctor_id: Uuid,
+    pub hospital_id: Option<Uuid>,
     pub purpose: String,
     pub time: DateTime<Utc>,
     pub status: AppointmentStatus,
-    pub price: f64,
+    pub price: Money,
+    pub duration_minutes: i32,
+    pub rescheduled_from: Option<Uuid>, // The appointment this one replaced
+    pub series_id: Option<Uuid>,
+    pub visit_type: VisitType,
+    pub price_catalog_id: Option<Uuid>, // Catalog entry the price was taken from
 }
 
-#[derive(Serialize, Deserialize, Type)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, Type)]
+#[sqlx(type_name = "VARCHAR", rename_all = "PascalCase")]
+pub enum VisitType {
+    #[default]
+    Consultation,
+    FollowUp,
+    Emergency,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
 #[sqlx(type_name = "VARCHAR", rename_all = "PascalCase")]
 pub enum AppointmentStatus {
     Scheduled,
+    CheckedIn,
+    InProgress,
     Done,
     Cancelled,
+    NoShow,
+    Rescheduled,
+}
+
+impl AppointmentStatus {
+    // Statuses that keep the doctor's slot occupied
+    pub const SLOT_HOLDING: [&'static str; 3] = ["Scheduled", "CheckedIn", "InProgress"];
+
+    pub fn parse(status: &str) -> Option<Self> {
+        match status {
+            "Scheduled" => Some(Self::Scheduled),
+            "CheckedIn" => Some(Self::CheckedIn),
+            "InProgress" => Some(Self::InProgress),
+            "Done" => Some(Self::Done),
+            "Cancelled" => Some(Self::Cancelled),
+            "NoShow" => Some(Self::NoShow),
+            "Rescheduled" => Some(Self::Rescheduled),
+            _ => None,
+        }
+    }
+
+    // Scheduled -> CheckedIn -> InProgress -> Done, with Cancelled, NoShow and Rescheduled
+    // as the ways out before the visit starts. Done, Cancelled, NoShow and Rescheduled are final.
+    pub fn can_transition_to(self, next: Self) -> bool {
+        use AppointmentStatus::*;
+        matches!(
+            (self, next),
+            (Scheduled, CheckedIn | Cancelled | NoShow | Rescheduled)
+                | (CheckedIn, InProgress | Cancelled | NoShow)
+                | (InProgress, Done)
+        )
+    }
+
+    // Outcomes that can only be recorded once the appointment time has arrived
+    pub fn requires_started(self) -> bool {
+        matches!(self, Self::Done | Self::NoShow)
+    }
+}
+
+#[derive(Serialize, FromRow)]
+pub struct AppointmentEvent {
+    pub id: Uuid,
+    pub appointment_id: Uuid,
+    pub from_status: Option<AppointmentStatus>,
+    pub to_status: AppointmentStatus,
+    pub actor_id: Option<Uuid>,
+    pub reason: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+pub struct AppointmentEventList {
+    pub events: Vec<AppointmentEvent>,
 }
 
 #[derive(Deserialize)]
 pub struct CreateAppointmentRequest {
     pub patient_id: Uuid,
-    pub day: String,
-    pub time: String,
+    pub date: Option<String>, // ISO date or RFC3339 datetime
+    pub day: Option<String>,  // Convenience: the next occurrence of this weekday
+    pub time: Option<String>,
+    pub purpose: String,
+    pub doctor_id: Option<Uuid>,
+    pub specialization: Option<String>,
+    pub visit_type: Option<VisitType>, // Defaults to a consultation
+}
+
+#[derive(Deserialize)]
+pub struct RescheduleAppointmentRequest {
+    pub date: Option<String>,
+    pub day: Option<String>,
+    pub time: Option<String>,
+    pub doctor_id: Option<Uuid>, // Defaults to the current doctor
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[sqlx(type_name = "VARCHAR", rename_all = "PascalCase")]
+pub enum WaitlistStatus {
+    Waiting,
+    Booked,
+    Expired,
+    Cancelled,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct WaitlistEntry {
+    pub id: Uuid,
+    pub patient_id: Uuid,
+    pub specialization: Option<String>,
+    pub purpose: String,
+    pub window_start: DateTime<Utc>,
+    pub window_end: DateTime<Utc>,
+    pub status: WaitlistStatus,
+    pub appointment_id: Option<Uuid>, // Set once a freed slot has been booked for the patient
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct JoinWaitlistRequest {
+    pub patient_id: Uuid,
+    pub date: String,             // YYYY-MM-DD
+    pub timezone: Option<String>, // e.g. "WAT" or "+01:00", defaults to the hospital's
+    pub specialization: Option<String>,
     pub purpose: String,
+    pub expires_at: Option<DateTime<Utc>>, // Stop waiting earlier than the end of the day
+}
+
+#[derive(Serialize)]
+pub struct WaitlistEntryList {
+    pub entries: Vec<WaitlistEntry>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[sqlx(type_name = "VARCHAR", rename_all = "PascalCase")]
+pub enum SeriesStatus {
+    Active,
+    Cancelled,
+}
+
+// A recurring booking; each occurrence is an ordinary appointment carrying the series id
+#[derive(Serialize, FromRow)]
+pub struct AppointmentSeries {
+    pub id: Uuid,
+    pub patient_id: Uuid,
+    pub doctor_id: Uuid,
+    pub purpose: String,
+    pub interval_weeks: i32, // 1 = weekly, 2 = biweekly
+    pub occurrences: i32,
+    pub starts_at: DateTime<Utc>,
+    pub status: SeriesStatus,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateAppointmentSeriesRequest {
+    pub patient_id: Uuid,
+    pub date: Option<String>, // First occurrence, as for a single appointment
+    pub day: Option<String>,
+    pub time: Option<String>,
+    pub purpose: String,
+    pub doctor_id: Option<Uuid>,
+    pub specialization: Option<String>,
+    pub visit_type: Option<VisitType>,
+    pub interval_weeks: i32,
+    pub count: Option<i32>,       // Number of occurrences
+    pub until: Option<NaiveDate>, // Last possible date, inclusive
+}
+
+#[derive(Serialize)]
+pub struct AppointmentSeriesDetail {
+    pub series: AppointmentSeries,
+    pub appointments: Vec<Appointment>,
+}
+
+// Billable work ordered during a visit; the next bill for the appointment picks it up
+#[derive(Serialize, FromRow)]
+pub struct AppointmentOrder {
+    pub id: Uuid,
+    pub appointment_id: Uuid,
+    pub kind: ItemKind,
+    pub description: String,
+    pub quantity: i32,
+    pub unit_price: Money,
+    pub discount: Money, // Off the whole line
+    pub taxable: bool,
+    pub bill_id: Option<Uuid>, // Set once billed
+    pub ordered_by: Uuid,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateAppointmentOrder {
+    pub kind: ItemKind,
+    pub description: String,
+    pub quantity: Option<i32>, // Defaults to 1
+    pub unit_price: Money,
+    pub discount: Option<Money>,
+    pub taxable: Option<bool>, // Defaults to VAT-exempt
+}
+
+#[derive(Serialize)]
+pub struct AppointmentOrderList {
+    pub orders: Vec<AppointmentOrder>,
 }
 
 #[derive(Serialize)]
@@ -37,21 +235,75 @@ pub struct AppointmentList {
 }
 
 impl Appointment {
+    // Booked at the doctor's hospital, or the patient's (`hospital_id`) when the doctor has none
     pub fn new(
         patient_id: Uuid,
-        doctor_id: Uuid,
+        hospital_id: Option<Uuid>,
+        doctor: &Doctor,
         purpose: String,
         time: DateTime<Utc>,
-        price: f64,
+        visit_type: VisitType,
+        price: AppliedPrice,
     ) -> Self {
         Self {
             id: Uuid::new_v4(),
             patient_id,
-            doctor_id,
+            doctor_id: doctor.id,
+            hospital_id: doctor.hospital_id.or(hospital_id),
             purpose,
             time,
             status: AppointmentStatus::Scheduled,
-            price,
+            price: price.amount,
+            duration_minutes: doctor.slot_minutes,
+            rescheduled_from: None,
+            series_id: None,
+            visit_type,
+            price_catalog_id: price.catalog_id,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AppointmentStatus::{self, *};
+
+    const ALL: [AppointmentStatus; 7] = [
+        Scheduled,
+        CheckedIn,
+        InProgress,
+        Done,
+        Cancelled,
+        NoShow,
+        Rescheduled,
+    ];
+
+    #[test]
+    fn allows_only_the_lifecycle_moves() {
+        let allowed = [
+            (Scheduled, CheckedIn),
+            (Scheduled, Cancelled),
+            (Scheduled, NoShow),
+            (Scheduled, Rescheduled),
+            (CheckedIn, InProgress),
+            (CheckedIn, Cancelled),
+            (CheckedIn, NoShow),
+            (InProgress, Done),
+        ];
+        for from in ALL {
+            for to in ALL {
+                assert_eq!(
+                    from.can_transition_to(to),
+                    allowed.contains(&(from, to)),
+                    "{from:?} -> {to:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn only_outcomes_wait_for_the_visit() {
+        for status in ALL {
+            assert_eq!(status.requires_started(), matches!(status, Done | NoShow));
         }
     }
 }
@@ -2,24 +2,81 @@ use std::sync::Arc;
 
 use axum::{
     Router,
-    routing::{get, put},
+    routing::{delete, get, post, put},
 };
 
 use crate::{
     app_state::{AppState, SharedState},
     appointments::handlers::{
-        create_appointment_handler, get_appointment_by_id_handler, get_appointments_handler,
-        update_appointment_status_handler,
+        cancel_appointment_series_handler, create_appointment_handler,
+        create_appointment_order_handler, create_appointment_series_handler,
+        get_appointment_by_id_handler, get_appointment_events_handler,
+        get_appointment_orders_handler, get_appointment_series_handler, get_appointments_handler,
+        get_waitlist_handler, join_waitlist_handler, leave_waitlist_handler,
+        reschedule_appointment_handler, update_appointment_status_handler,
     },
+    auth::permissions::{Permission, require},
+    idempotency::idempotent,
 };
 
 pub fn appointments_router(state: SharedState) -> Router<Arc<AppState>> {
     Router::new()
-        .route(
-            "/",
-            get(get_appointments_handler).post(create_appointment_handler),
-        )
-        .route("/<id>", get(get_appointment_by_id_handler))
-        .route("/<id>/status", put(update_appointment_status_handler))
+        .merge(require(
+            &state,
+            Permission::ViewAppointments,
+            Router::new()
+                .route("/", get(get_appointments_handler))
+                .route("/series/{id}", get(get_appointment_series_handler))
+                .route("/{id}", get(get_appointment_by_id_handler))
+                .route("/{id}/events", get(get_appointment_events_handler)),
+        ))
+        .merge(require(
+            &state,
+            Permission::BookAppointments,
+            idempotent(
+                &state,
+                Router::new()
+                    .route("/", post(create_appointment_handler))
+                    .route("/series", post(create_appointment_series_handler)),
+            ),
+        ))
+        .merge(require(
+            &state,
+            Permission::UpdateAppointments,
+            Router::new()
+                .route(
+                    "/series/{id}/cancel",
+                    post(cancel_appointment_series_handler),
+                )
+                .route("/{id}/status", put(update_appointment_status_handler))
+                .merge(idempotent(
+                    &state,
+                    Router::new().route("/{id}/reschedule", post(reschedule_appointment_handler)),
+                )),
+        ))
+        .merge(require(
+            &state,
+            Permission::ManageWaitlist,
+            Router::new()
+                .route("/waitlist", get(get_waitlist_handler))
+                .route("/waitlist/{id}", delete(leave_waitlist_handler))
+                .merge(idempotent(
+                    &state,
+                    Router::new().route("/waitlist", post(join_waitlist_handler)),
+                )),
+        ))
+        .merge(require(
+            &state,
+            Permission::ViewOrders,
+            Router::new().route("/{id}/orders", get(get_appointment_orders_handler)),
+        ))
+        .merge(require(
+            &state,
+            Permission::ManageOrders,
+            idempotent(
+                &state,
+                Router::new().route("/{id}/orders", post(create_appointment_order_handler)),
+            ),
+        ))
         .with_state(state)
 }
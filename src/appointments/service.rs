@@ -1,38 +1,64 @@
-use rand::seq::IteratorRandom;
-use sqlx::QueryBuilder;
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveDate, NaiveTime, TimeZone, Utc};
+use sqlx::{PgConnection, QueryBuilder};
+use std::collections::HashMap;
 use uuid::Uuid;
 
+use crate::admin::models::AppliedPrice;
+use crate::admin::service::resolve_appointment_price;
 use crate::appointments::models::{
-    Appointment, AppointmentList, AppointmentStatus, CreateAppointmentRequest,
+    Appointment, AppointmentEvent, AppointmentEventList, AppointmentList, AppointmentOrder,
+    AppointmentOrderList, AppointmentSeries, AppointmentSeriesDetail, AppointmentStatus,
+    CreateAppointmentOrder, CreateAppointmentRequest, CreateAppointmentSeriesRequest,
+    JoinWaitlistRequest, RescheduleAppointmentRequest, SeriesStatus, VisitType, WaitlistEntry,
+    WaitlistEntryList, WaitlistStatus,
 };
-use crate::doctor::service::get_available_doctors;
-use crate::utils::combine_day_and_time;
-use crate::{app_state::SharedState, config::DEFAULT_APPOINTMENT_PRICE, errors::AppError};
+use crate::auth::headers::ClaimsHeader;
+use crate::billing::models::ItemKind;
+use crate::billing::service::{cancel_open_bills, refund_cancelled_appointment, transfer_bills};
+use crate::doctor::models::Doctor;
+use crate::doctor::service::{get_available_doctors, get_doctor_by_id};
+use crate::money::Money;
+use crate::notifications::service::notify_patient;
+use crate::utils::{
+    combine_date_and_time, hospital_offset, resolve_appointment_time, weekday_name,
+};
+use crate::{
+    app_state::SharedState,
+    config::{DEFAULT_TIMEZONE, MAX_SERIES_OCCURRENCES},
+    errors::AppError,
+};
+
+const APPOINTMENT_COLUMNS: &str = "id, patient_id, doctor_id, hospital_id, purpose, time, \
+    status, price, duration_minutes, rescheduled_from, series_id, visit_type, price_catalog_id";
 
-// Get all, optionally filter by eithor or both patient id or doctor id
+// Get all in the caller's hospital (every hospital when `hospital_id` is None), optionally
+// filtered by either or both patient id or doctor id
 pub async fn get_appointments(
     state: SharedState,
     patient_id: Option<Uuid>,
     doctor_id: Option<Uuid>,
+    hospital_id: Option<Uuid>,
 ) -> Result<AppointmentList, AppError> {
-    let mut builder = QueryBuilder::new(
-        "SELECT id, patient_id, doctor_id, purpose, time, status, price FROM appointments",
-    );
-
-    let mut where_added = false;
+    let mut builder = QueryBuilder::new(format!(
+        "SELECT {APPOINTMENT_COLUMNS} FROM appointments WHERE "
+    ));
+    match hospital_id {
+        Some(hid) => {
+            builder.push("hospital_id = ");
+            builder.push_bind(hid);
+        }
+        None => {
+            builder.push("TRUE");
+        }
+    }
 
     if let Some(pid) = patient_id {
-        builder.push(" WHERE patient_id = ");
+        builder.push(" AND patient_id = ");
         builder.push_bind(pid);
-        where_added = true;
     }
 
     if let Some(did) = doctor_id {
-        if where_added {
-            builder.push(" AND doctor_id = ");
-        } else {
-            builder.push(" WHERE doctor_id = ");
-        }
+        builder.push(" AND doctor_id = ");
         builder.push_bind(did);
     }
 
@@ -45,17 +71,19 @@ pub async fn get_appointments(
     Ok(AppointmentList { appointments })
 }
 
-// Get a specific appointment by id
+// Get a specific appointment by id, from `hospital_id` when given
 pub async fn get_appointment_by_id(
     state: SharedState,
     appointment_id: String,
+    hospital_id: Option<Uuid>,
 ) -> Result<Appointment, AppError> {
     let appointment_id =
         Uuid::parse_str(&appointment_id).map_err(|e| AppError::ParsingError(e.to_string()))?;
-    let appointment = sqlx::query_as::<_, Appointment>(
-        "SELECT id, patient_id, doctor_id, purpose, time, status, price FROM appointments WHERE id = $1",
-    )
+    let appointment = sqlx::query_as::<_, Appointment>(&format!(
+        "SELECT {APPOINTMENT_COLUMNS} FROM appointments WHERE id = $1 AND ($2::uuid IS NULL OR hospital_id = $2)"
+    ))
     .bind(appointment_id)
+    .bind(hospital_id)
     .fetch_one(&state.db_pool)
     .await
     .map_err(|e| AppError::DatabaseError(e.to_string()))?;
@@ -68,66 +96,1244 @@ pub async fn create_appointment(
     state: SharedState,
     payload: CreateAppointmentRequest,
 ) -> Result<Appointment, AppError> {
-    let available = get_available_doctors(state.clone(), payload.day.clone()).await?;
+    let time = resolve_appointment_time(
+        payload.date.as_deref(),
+        payload.day.as_deref(),
+        payload.time.as_deref(),
+    )?;
+    let hospital_id = patient_hospital(&state, payload.patient_id).await?;
+    let candidates = find_candidate_doctors(
+        state.clone(),
+        &time,
+        payload.doctor_id,
+        payload.specialization.clone(),
+        hospital_id,
+    )
+    .await?;
+
+    let visit_type = payload.visit_type.unwrap_or_default();
+
+    // Try each doctor in turn, skipping those whose slot was taken in the meantime
+    for doctor in candidates {
+        let price =
+            resolve_appointment_price(state.clone(), hospital_id, &doctor, visit_type).await?;
+        let appointment = Appointment::new(
+            payload.patient_id,
+            hospital_id,
+            &doctor,
+            payload.purpose.clone(),
+            time.with_timezone(&Utc),
+            visit_type,
+            price,
+        );
+        let mut tx = state
+            .db_pool
+            .begin()
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        if try_book_slot(&mut tx, &appointment).await? {
+            record_event(
+                &mut tx,
+                appointment.id,
+                None,
+                appointment.status,
+                None,
+                None,
+            )
+            .await?;
+            tx.commit()
+                .await
+                .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+            return Ok(appointment);
+        }
+    }
+
+    Err(AppError::Conflict(match payload.doctor_id {
+        Some(_) => "The requested slot is already booked with this doctor".to_string(),
+        None => "The requested slot is already booked with every available doctor".to_string(),
+    }))
+}
+
+// The hospital a patient is registered with, which their appointments are booked at
+async fn patient_hospital(state: &SharedState, patient_id: Uuid) -> Result<Option<Uuid>, AppError> {
+    sqlx::query_scalar::<_, Option<Uuid>>("SELECT hospital_id FROM patients WHERE id = $1")
+        .bind(patient_id)
+        .fetch_optional(&state.db_pool)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?
+        .ok_or_else(|| AppError::NotFound(format!("Patient with id {patient_id} not found")))
+}
+
+// Doctors who work a slot starting at `time`, in booking preference order, from `hospital_id`
+// when the patient belongs to one.
+// A requested doctor is the only candidate; otherwise the least booked doctor that day comes
+// first, with ties broken by id so assignment is deterministic.
+async fn find_candidate_doctors(
+    state: SharedState,
+    time: &DateTime<FixedOffset>,
+    doctor_id: Option<Uuid>,
+    specialization: Option<String>,
+    hospital_id: Option<Uuid>,
+) -> Result<Vec<Doctor>, AppError> {
+    if let Some(doctor_id) = doctor_id {
+        let doctor = get_doctor_by_id(state, doctor_id.to_string())
+            .await
+            .ok()
+            .filter(|doctor| hospital_id.is_none() || doctor.hospital_id == hospital_id)
+            .ok_or_else(|| AppError::NotFound(format!("Doctor with id {doctor_id} not found")))?;
+        if let Some(specialization) = specialization
+            && !doctor.specialization.eq_ignore_ascii_case(&specialization)
+        {
+            return Err(AppError::UnProcessableEntity {
+                field: "specialization".to_string(),
+                message: format!("{} is not a {} specialist", doctor.name, specialization),
+            });
+        }
+        if !doctor.has_slot_at(time) {
+            return Err(AppError::UnProcessableEntity {
+                field: "time".to_string(),
+                message: format!(
+                    "The requested time is outside {}'s working hours",
+                    doctor.name
+                ),
+            });
+        }
+        return Ok(vec![doctor]);
+    }
 
-    let doctor = available
+    let day = weekday_name(time.with_timezone(&hospital_offset()).weekday()).to_string();
+    let available = get_available_doctors(state.clone(), day, specialization, hospital_id).await?;
+    if available.doctors.is_empty() {
+        return Err(AppError::NotFound(
+            "No doctors available on the requested day".to_string(),
+        ));
+    }
+
+    let mut candidates: Vec<Doctor> = available
         .doctors
         .into_iter()
-        .choose(&mut rand::rng())
-        .ok_or_else(|| {
-            AppError::NotFound("No doctors available on the requested day".to_string())
-        })?;
+        .filter(|doctor| doctor.has_slot_at(time))
+        .collect();
+    if candidates.is_empty() {
+        return Err(AppError::UnProcessableEntity {
+            field: "time".to_string(),
+            message: "The requested time is outside every available doctor's working hours"
+                .to_string(),
+        });
+    }
 
-    let time = combine_day_and_time(&payload.day, &payload.time)?;
+    let doctor_ids: Vec<Uuid> = candidates.iter().map(|doctor| doctor.id).collect();
+    let bookings = count_bookings_on_day(state, &doctor_ids, time).await?;
+    candidates.sort_by_key(|doctor| (bookings.get(&doctor.id).copied().unwrap_or(0), doctor.id));
+    Ok(candidates)
+}
 
-    let appointment = Appointment::new(
-        payload.patient_id,
-        doctor.id,
-        payload.purpose,
-        time,
-        DEFAULT_APPOINTMENT_PRICE,
-    );
+// Active bookings per doctor on the hospital's calendar day of `time`
+async fn count_bookings_on_day(
+    state: SharedState,
+    doctor_ids: &[Uuid],
+    time: &DateTime<FixedOffset>,
+) -> Result<HashMap<Uuid, i64>, AppError> {
+    let time = time.with_timezone(&hospital_offset());
+    let day_start = time
+        .timezone()
+        .from_local_datetime(&time.date_naive().and_time(NaiveTime::MIN))
+        .single()
+        .ok_or_else(|| AppError::InternalServerError("Invalid local midnight".to_string()))?;
+    let day_end = day_start + Duration::days(1);
 
-    sqlx::query(
-        "INSERT INTO appointments (id, patient_id, doctor_id, purpose, time, status, price) VALUES ($1, $2, $3, $4, $5, $6, $7)",
+    let rows: Vec<(Uuid, i64)> = sqlx::query_as(
+        "SELECT doctor_id, COUNT(*) FROM appointments WHERE doctor_id = ANY($1) AND status = ANY($2) AND time >= $3 AND time < $4 GROUP BY doctor_id",
+    )
+    .bind(doctor_ids)
+    .bind(&AppointmentStatus::SLOT_HOLDING[..])
+    .bind(day_start)
+    .bind(day_end)
+    .fetch_all(&state.db_pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    Ok(rows.into_iter().collect())
+}
+
+// Inserts the appointment unless the doctor already has an active appointment overlapping it.
+// Locks the doctor's row until the surrounding transaction ends, so concurrent bookings for
+// the same doctor are serialised; the partial unique index on (doctor_id, time) is the backstop.
+async fn try_book_slot(
+    conn: &mut PgConnection,
+    appointment: &Appointment,
+) -> Result<bool, AppError> {
+    sqlx::query("SELECT id FROM doctors WHERE id = $1 FOR UPDATE")
+        .bind(appointment.doctor_id)
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    let end = appointment.time + Duration::minutes(appointment.duration_minutes as i64);
+    let taken: bool = sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM appointments WHERE doctor_id = $1 AND status = ANY($2) AND time < $3 AND time + make_interval(mins => duration_minutes) > $4)",
     )
+    .bind(appointment.doctor_id)
+    .bind(&AppointmentStatus::SLOT_HOLDING[..])
+    .bind(end)
+    .bind(appointment.time)
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    if taken {
+        return Ok(false);
+    }
+
+    let inserted = sqlx::query(&format!(
+        "INSERT INTO appointments ({APPOINTMENT_COLUMNS}) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)"
+    ))
     .bind(appointment.id)
     .bind(appointment.patient_id)
     .bind(appointment.doctor_id)
+    .bind(appointment.hospital_id)
     .bind(&appointment.purpose)
     .bind(appointment.time)
-    .bind(&appointment.status)
+    .bind(appointment.status)
     .bind(appointment.price)
-    .execute(&state.db_pool)
-    .await
-    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    .bind(appointment.duration_minutes)
+    .bind(appointment.rescheduled_from)
+    .bind(appointment.series_id)
+    .bind(appointment.visit_type)
+    .bind(appointment.price_catalog_id)
+    .execute(&mut *conn)
+    .await;
 
-    Ok(appointment)
+    match inserted {
+        Ok(_) => Ok(true),
+        Err(e)
+            if e.as_database_error()
+                .is_some_and(|db_error| db_error.is_unique_violation()) =>
+        {
+            Ok(false)
+        }
+        Err(e) => Err(AppError::DatabaseError(e.to_string())),
+    }
 }
 
-// Update appointment status
+// Update appointment status, enforcing the lifecycle and recording who made the change
 pub async fn update_appointment_status(
     state: SharedState,
     appointment_id: String,
     status: String,
+    reason: Option<String>,
+    claims: ClaimsHeader,
 ) -> Result<Appointment, AppError> {
-    let status = match status.as_str() {
-        "Scheduled" => AppointmentStatus::Scheduled,
-        "Done" => AppointmentStatus::Done,
-        "Cancelled" => AppointmentStatus::Cancelled,
-        _ => {
-            return Err(AppError::ParsingError(format!(
-                "Invalid status value: {status}",
-            )));
+    let next = AppointmentStatus::parse(&status)
+        .ok_or_else(|| AppError::ParsingError(format!("Invalid status value: {status}")))?;
+    if next == AppointmentStatus::Rescheduled {
+        return Err(AppError::UnProcessableEntity {
+            field: "status".to_string(),
+            message: "Use the reschedule endpoint to move an appointment".to_string(),
+        });
+    }
+    let id = Uuid::parse_str(&appointment_id).map_err(|e| AppError::ParsingError(e.to_string()))?;
+
+    let mut tx = state
+        .db_pool
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    let mut appointment = sqlx::query_as::<_, Appointment>(&format!(
+        "SELECT {APPOINTMENT_COLUMNS} FROM appointments WHERE id = $1 AND ($2::uuid IS NULL OR hospital_id = $2) FOR UPDATE"
+    ))
+    .bind(id)
+    .bind(claims.tenant())
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    transition(&mut tx, &mut appointment, next, Some(claims.sub), reason).await?;
+    if appointment.status == AppointmentStatus::Cancelled {
+        cancel_open_bills(&mut tx, appointment.id).await?;
+    }
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    if appointment.status == AppointmentStatus::Cancelled {
+        refund_within_policy(state.clone(), &appointment, claims.sub).await;
+        offer_freed_slot(state, &appointment).await;
+    }
+
+    Ok(appointment)
+}
+
+// Book a recurring series. Every occurrence goes to the same doctor and the whole series is
+// booked in one transaction, so it is either fully booked or not at all.
+pub async fn create_appointment_series(
+    state: SharedState,
+    payload: CreateAppointmentSeriesRequest,
+) -> Result<AppointmentSeriesDetail, AppError> {
+    let first = resolve_appointment_time(
+        payload.date.as_deref(),
+        payload.day.as_deref(),
+        payload.time.as_deref(),
+    )?;
+    let times = series_occurrences(first, payload.interval_weeks, payload.count, payload.until)?;
+    let hospital_id = patient_hospital(&state, payload.patient_id).await?;
+    let candidates = find_candidate_doctors(
+        state.clone(),
+        &first,
+        payload.doctor_id,
+        payload.specialization.clone(),
+        hospital_id,
+    )
+    .await?;
+
+    let visit_type = payload.visit_type.unwrap_or_default();
+
+    for doctor in candidates {
+        let price =
+            resolve_appointment_price(state.clone(), hospital_id, &doctor, visit_type).await?;
+        let mut tx = state
+            .db_pool
+            .begin()
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        let series = sqlx::query_as::<_, AppointmentSeries>(
+            "INSERT INTO appointment_series (id, patient_id, doctor_id, purpose, interval_weeks, occurrences, starts_at, status) VALUES ($1, $2, $3, $4, $5, $6, $7, $8) RETURNING *",
+        )
+        .bind(Uuid::new_v4())
+        .bind(payload.patient_id)
+        .bind(doctor.id)
+        .bind(&payload.purpose)
+        .bind(payload.interval_weeks)
+        .bind(times.len() as i32)
+        .bind(first.with_timezone(&Utc))
+        .bind(SeriesStatus::Active)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        let mut appointments = Vec::with_capacity(times.len());
+        for time in &times {
+            let mut appointment = Appointment::new(
+                payload.patient_id,
+                hospital_id,
+                &doctor,
+                payload.purpose.clone(),
+                time.with_timezone(&Utc),
+                visit_type,
+                price,
+            );
+            appointment.series_id = Some(series.id);
+            if !try_book_slot(&mut tx, &appointment).await? {
+                break;
+            }
+            record_event(
+                &mut tx,
+                appointment.id,
+                None,
+                appointment.status,
+                None,
+                None,
+            )
+            .await?;
+            appointments.push(appointment);
         }
-    };
+        if appointments.len() < times.len() {
+            continue;
+        }
+
+        tx.commit()
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        return Ok(AppointmentSeriesDetail {
+            series,
+            appointments,
+        });
+    }
+
+    Err(AppError::Conflict(
+        "No doctor is free for every occurrence of the series".to_string(),
+    ))
+}
+
+// Start times of a series, keeping the wall-clock time of the first occurrence
+fn series_occurrences(
+    first: DateTime<FixedOffset>,
+    interval_weeks: i32,
+    count: Option<i32>,
+    until: Option<NaiveDate>,
+) -> Result<Vec<DateTime<FixedOffset>>, AppError> {
+    if !(1..=12).contains(&interval_weeks) {
+        return Err(AppError::UnProcessableEntity {
+            field: "interval_weeks".to_string(),
+            message: "Repeat every 1 to 12 weeks".to_string(),
+        });
+    }
+    if count.is_none() && until.is_none() {
+        return Err(AppError::UnProcessableEntity {
+            field: "count".to_string(),
+            message: "Either count or until must be provided".to_string(),
+        });
+    }
+    if count.is_some_and(|count| !(1..=MAX_SERIES_OCCURRENCES).contains(&count)) {
+        return Err(AppError::UnProcessableEntity {
+            field: "count".to_string(),
+            message: format!("A series can have 1 to {MAX_SERIES_OCCURRENCES} occurrences"),
+        });
+    }
+
+    // One past the cap, so an `until` that runs too long is reported instead of truncated
+    let limit = count.unwrap_or(MAX_SERIES_OCCURRENCES + 1);
+    let times: Vec<DateTime<FixedOffset>> = (0..limit)
+        .map(|i| first + Duration::weeks((i * interval_weeks) as i64))
+        .take_while(|time| until.is_none_or(|until| time.date_naive() <= until))
+        .collect();
+    if times.is_empty() {
+        return Err(AppError::UnProcessableEntity {
+            field: "until".to_string(),
+            message: "The series ends before its first occurrence".to_string(),
+        });
+    }
+    if times.len() as i32 > MAX_SERIES_OCCURRENCES {
+        return Err(AppError::UnProcessableEntity {
+            field: "until".to_string(),
+            message: format!("A series can have at most {MAX_SERIES_OCCURRENCES} occurrences"),
+        });
+    }
+
+    Ok(times)
+}
+
+// Get a series with all of its occurrences, including rescheduled replacements, from
+// `hospital_id`'s patients when given
+pub async fn get_appointment_series(
+    state: SharedState,
+    series_id: String,
+    hospital_id: Option<Uuid>,
+) -> Result<AppointmentSeriesDetail, AppError> {
+    let id = Uuid::parse_str(&series_id).map_err(|e| AppError::ParsingError(e.to_string()))?;
+    let series = sqlx::query_as::<_, AppointmentSeries>(
+        "SELECT * FROM appointment_series WHERE id = $1 AND ($2::uuid IS NULL OR patient_id IN (SELECT id FROM patients WHERE hospital_id = $2))",
+    )
+    .bind(id)
+    .bind(hospital_id)
+    .fetch_one(&state.db_pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    let appointments = sqlx::query_as::<_, Appointment>(&format!(
+        "SELECT {APPOINTMENT_COLUMNS} FROM appointments WHERE series_id = $1 ORDER BY time"
+    ))
+    .bind(series.id)
+    .fetch_all(&state.db_pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    Ok(AppointmentSeriesDetail {
+        series,
+        appointments,
+    })
+}
+
+// Cancel a whole series: every occurrence that is still scheduled is cancelled.
+// Occurrences already attended, checked in or closed are left as they are.
+pub async fn cancel_appointment_series(
+    state: SharedState,
+    series_id: String,
+    reason: Option<String>,
+    claims: ClaimsHeader,
+) -> Result<AppointmentSeriesDetail, AppError> {
+    let id = Uuid::parse_str(&series_id).map_err(|e| AppError::ParsingError(e.to_string()))?;
+
+    let mut tx = state
+        .db_pool
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    let series = sqlx::query_as::<_, AppointmentSeries>(
+        "SELECT * FROM appointment_series WHERE id = $1 AND ($2::uuid IS NULL OR patient_id IN (SELECT id FROM patients WHERE hospital_id = $2)) FOR UPDATE",
+    )
+    .bind(id)
+    .bind(claims.tenant())
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    if series.status == SeriesStatus::Cancelled {
+        return Err(AppError::Conflict(
+            "The series has already been cancelled".to_string(),
+        ));
+    }
+
+    let mut appointments = sqlx::query_as::<_, Appointment>(&format!(
+        "SELECT {APPOINTMENT_COLUMNS} FROM appointments WHERE series_id = $1 AND status = $2 ORDER BY time FOR UPDATE"
+    ))
+    .bind(series.id)
+    .bind(AppointmentStatus::Scheduled)
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    for appointment in appointments.iter_mut() {
+        transition(
+            &mut tx,
+            appointment,
+            AppointmentStatus::Cancelled,
+            Some(claims.sub),
+            reason.clone(),
+        )
+        .await?;
+        cancel_open_bills(&mut tx, appointment.id).await?;
+    }
+    sqlx::query("UPDATE appointment_series SET status = $1 WHERE id = $2")
+        .bind(SeriesStatus::Cancelled)
+        .bind(series.id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    for appointment in &appointments {
+        refund_within_policy(state.clone(), appointment, claims.sub).await;
+        offer_freed_slot(state.clone(), appointment).await;
+    }
+
+    get_appointment_series(state, series_id, claims.tenant()).await
+}
+
+// Move an appointment to a new time and/or doctor. The original is closed as Rescheduled and a
+// new appointment linked to it takes over its price and bills, all in one transaction.
+pub async fn reschedule_appointment(
+    state: SharedState,
+    appointment_id: String,
+    payload: RescheduleAppointmentRequest,
+    claims: ClaimsHeader,
+) -> Result<Appointment, AppError> {
     let id = Uuid::parse_str(&appointment_id).map_err(|e| AppError::ParsingError(e.to_string()))?;
+    let time = resolve_appointment_time(
+        payload.date.as_deref(),
+        payload.day.as_deref(),
+        payload.time.as_deref(),
+    )?;
+
+    let mut tx = state
+        .db_pool
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    let mut original = sqlx::query_as::<_, Appointment>(&format!(
+        "SELECT {APPOINTMENT_COLUMNS} FROM appointments WHERE id = $1 AND ($2::uuid IS NULL OR hospital_id = $2) FOR UPDATE"
+    ))
+    .bind(id)
+    .bind(claims.tenant())
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    let doctor_id = payload.doctor_id.unwrap_or(original.doctor_id);
+    let doctor = find_candidate_doctors(
+        state.clone(),
+        &time,
+        Some(doctor_id),
+        None,
+        original.hospital_id,
+    )
+    .await?
+    .remove(0);
+
+    // Close the original first so its own slot does not block a move to an overlapping time
+    transition(
+        &mut tx,
+        &mut original,
+        AppointmentStatus::Rescheduled,
+        Some(claims.sub),
+        payload.reason.clone(),
+    )
+    .await?;
+
+    // The patient keeps the price they were quoted
+    let mut appointment = Appointment::new(
+        original.patient_id,
+        original.hospital_id,
+        &doctor,
+        original.purpose.clone(),
+        time.with_timezone(&Utc),
+        original.visit_type,
+        AppliedPrice {
+            amount: original.price,
+            catalog_id: original.price_catalog_id,
+        },
+    );
+    appointment.rescheduled_from = Some(original.id);
+    appointment.series_id = original.series_id;
+
+    if !try_book_slot(&mut tx, &appointment).await? {
+        return Err(AppError::Conflict(
+            "The requested slot is already booked with this doctor".to_string(),
+        ));
+    }
+    record_event(
+        &mut tx,
+        appointment.id,
+        None,
+        appointment.status,
+        Some(claims.sub),
+        payload.reason,
+    )
+    .await?;
+    transfer_bills(&mut tx, original.id, appointment.id).await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    offer_freed_slot(state, &original).await;
+
+    Ok(appointment)
+}
+
+// Moves a row-locked appointment to `next` and records the event
+async fn transition(
+    conn: &mut PgConnection,
+    appointment: &mut Appointment,
+    next: AppointmentStatus,
+    actor_id: Option<Uuid>,
+    reason: Option<String>,
+) -> Result<(), AppError> {
+    let current = appointment.status;
+    if !current.can_transition_to(next) {
+        return Err(AppError::Conflict(format!(
+            "Cannot move an appointment from {current:?} to {next:?}"
+        )));
+    }
+    if next.requires_started() && Utc::now() < appointment.time {
+        return Err(AppError::Conflict(format!(
+            "An appointment cannot be marked {next:?} before its scheduled time"
+        )));
+    }
+
     sqlx::query("UPDATE appointments SET status = $1 WHERE id = $2")
-        .bind(&status)
-        .bind(id)
-        .execute(&state.db_pool)
+        .bind(next)
+        .bind(appointment.id)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    record_event(conn, appointment.id, Some(current), next, actor_id, reason).await?;
+
+    appointment.status = next;
+    Ok(())
+}
+
+async fn record_event(
+    conn: &mut PgConnection,
+    appointment_id: Uuid,
+    from_status: Option<AppointmentStatus>,
+    to_status: AppointmentStatus,
+    actor_id: Option<Uuid>,
+    reason: Option<String>,
+) -> Result<(), AppError> {
+    sqlx::query(
+        "INSERT INTO appointment_events (id, appointment_id, from_status, to_status, actor_id, reason) VALUES ($1, $2, $3, $4, $5, $6)",
+    )
+    .bind(Uuid::new_v4())
+    .bind(appointment_id)
+    .bind(from_status)
+    .bind(to_status)
+    .bind(actor_id)
+    .bind(reason)
+    .execute(conn)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    Ok(())
+}
+
+// Get the status history of an appointment, oldest first
+pub async fn get_appointment_events(
+    state: SharedState,
+    appointment_id: String,
+    hospital_id: Option<Uuid>,
+) -> Result<AppointmentEventList, AppError> {
+    let appointment = get_appointment_by_id(state.clone(), appointment_id, hospital_id).await?;
+    let events = sqlx::query_as::<_, AppointmentEvent>(
+        "SELECT * FROM appointment_events WHERE appointment_id = $1 ORDER BY created_at",
+    )
+    .bind(appointment.id)
+    .fetch_all(&state.db_pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    Ok(AppointmentEventList { events })
+}
+
+// Attach billable work (a lab test, drugs, bed days) to an appointment for its next bill
+pub async fn create_appointment_order(
+    state: SharedState,
+    appointment_id: String,
+    payload: CreateAppointmentOrder,
+    claims: ClaimsHeader,
+) -> Result<AppointmentOrder, AppError> {
+    let appointment = get_appointment_by_id(state.clone(), appointment_id, claims.tenant()).await?;
+    if matches!(
+        appointment.status,
+        AppointmentStatus::Cancelled | AppointmentStatus::Rescheduled
+    ) {
+        return Err(AppError::Conflict(format!(
+            "Cannot add orders to an appointment that is {:?}",
+            appointment.status
+        )));
+    }
+
+    let quantity = payload.quantity.unwrap_or(1);
+    let discount = payload.discount.unwrap_or(Money::ZERO);
+    if payload.kind == ItemKind::Consultation {
+        return Err(AppError::UnProcessableEntity {
+            field: "kind".to_string(),
+            message: "The consultation is billed from the appointment itself".to_string(),
+        });
+    }
+    if payload.description.trim().is_empty() {
+        return Err(AppError::UnProcessableEntity {
+            field: "description".to_string(),
+            message: "Description is required".to_string(),
+        });
+    }
+    if quantity < 1 {
+        return Err(AppError::UnProcessableEntity {
+            field: "quantity".to_string(),
+            message: "Quantity must be at least 1".to_string(),
+        });
+    }
+    if !payload.unit_price.is_positive() {
+        return Err(AppError::UnProcessableEntity {
+            field: "unit_price".to_string(),
+            message: "Unit price must be greater than zero".to_string(),
+        });
+    }
+    if discount.is_negative() || discount > payload.unit_price.times(quantity) {
+        return Err(AppError::UnProcessableEntity {
+            field: "discount".to_string(),
+            message: "Discount must be between zero and the line's price".to_string(),
+        });
+    }
+
+    sqlx::query_as::<_, AppointmentOrder>(
+        "INSERT INTO appointment_orders (id, appointment_id, kind, description, quantity, unit_price, discount, taxable, ordered_by) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9) RETURNING *",
+    )
+    .bind(Uuid::new_v4())
+    .bind(appointment.id)
+    .bind(payload.kind)
+    .bind(payload.description.trim())
+    .bind(quantity)
+    .bind(payload.unit_price)
+    .bind(discount)
+    .bind(payload.taxable.unwrap_or(false))
+    .bind(claims.sub)
+    .fetch_one(&state.db_pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))
+}
+
+pub async fn get_appointment_orders(
+    state: SharedState,
+    appointment_id: String,
+    hospital_id: Option<Uuid>,
+) -> Result<AppointmentOrderList, AppError> {
+    let appointment = get_appointment_by_id(state.clone(), appointment_id, hospital_id).await?;
+    let orders = sqlx::query_as::<_, AppointmentOrder>(
+        "SELECT * FROM appointment_orders WHERE appointment_id = $1 ORDER BY created_at",
+    )
+    .bind(appointment.id)
+    .fetch_all(&state.db_pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    Ok(AppointmentOrderList { orders })
+}
+
+// Join the waitlist for a day, optionally for a specific specialization
+pub async fn join_waitlist(
+    state: SharedState,
+    payload: JoinWaitlistRequest,
+) -> Result<WaitlistEntry, AppError> {
+    let date = NaiveDate::parse_from_str(payload.date.trim(), "%Y-%m-%d").map_err(|_| {
+        AppError::UnProcessableEntity {
+            field: "date".to_string(),
+            message: format!("Expected YYYY-MM-DD, got {}", payload.date),
+        }
+    })?;
+    let timezone = payload.timezone.as_deref().unwrap_or(DEFAULT_TIMEZONE);
+    let window_start =
+        combine_date_and_time(date, &format!("00:00:00{timezone}"))?.with_timezone(&Utc);
+    let window_end = window_start + Duration::days(1);
+    let expires_at = payload
+        .expires_at
+        .map_or(window_end, |expires_at| expires_at.min(window_end));
+    if expires_at <= Utc::now() {
+        return Err(AppError::UnProcessableEntity {
+            field: "date".to_string(),
+            message: "The waitlist can only be joined for upcoming days".to_string(),
+        });
+    }
+
+    let entry = sqlx::query_as::<_, WaitlistEntry>(
+        "INSERT INTO waitlist_entries (id, patient_id, specialization, purpose, window_start, window_end, status, expires_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8) RETURNING *",
+    )
+    .bind(Uuid::new_v4())
+    .bind(payload.patient_id)
+    .bind(payload.specialization)
+    .bind(payload.purpose)
+    .bind(window_start)
+    .bind(window_end)
+    .bind(WaitlistStatus::Waiting)
+    .bind(expires_at)
+    .fetch_one(&state.db_pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    Ok(entry)
+}
+
+// Get waitlist entries of the hospital's patients, oldest first, optionally for a single patient
+pub async fn get_waitlist(
+    state: SharedState,
+    patient_id: Option<Uuid>,
+    hospital_id: Option<Uuid>,
+) -> Result<WaitlistEntryList, AppError> {
+    expire_stale_waitlist_entries(&state).await?;
+
+    let mut builder = QueryBuilder::new("SELECT * FROM waitlist_entries WHERE ");
+    match hospital_id {
+        Some(hid) => {
+            builder.push("patient_id IN (SELECT id FROM patients WHERE hospital_id = ");
+            builder.push_bind(hid);
+            builder.push(")");
+        }
+        None => {
+            builder.push("TRUE");
+        }
+    }
+    if let Some(pid) = patient_id {
+        builder.push(" AND patient_id = ");
+        builder.push_bind(pid);
+    }
+    builder.push(" ORDER BY created_at");
+
+    let entries = builder
+        .build_query_as::<WaitlistEntry>()
+        .fetch_all(&state.db_pool)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    Ok(WaitlistEntryList { entries })
+}
+
+// Leave the waitlist; only entries still waiting can be withdrawn. Only `hospital_id`'s
+// patients' entries are found when it is given.
+pub async fn leave_waitlist(
+    state: SharedState,
+    entry_id: String,
+    hospital_id: Option<Uuid>,
+) -> Result<WaitlistEntry, AppError> {
+    let id = Uuid::parse_str(&entry_id).map_err(|e| AppError::ParsingError(e.to_string()))?;
+    let entry = sqlx::query_as::<_, WaitlistEntry>(
+        "SELECT * FROM waitlist_entries WHERE id = $1 AND ($2::uuid IS NULL OR patient_id IN (SELECT id FROM patients WHERE hospital_id = $2))",
+    )
+    .bind(id)
+    .bind(hospital_id)
+    .fetch_one(&state.db_pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    sqlx::query_as::<_, WaitlistEntry>(
+        "UPDATE waitlist_entries SET status = $1 WHERE id = $2 AND status = $3 RETURNING *",
+    )
+    .bind(WaitlistStatus::Cancelled)
+    .bind(entry.id)
+    .bind(WaitlistStatus::Waiting)
+    .fetch_optional(&state.db_pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?
+    .ok_or_else(|| {
+        AppError::Conflict(format!(
+            "Waitlist entry is {:?} and can no longer be withdrawn",
+            entry.status
+        ))
+    })
+}
+
+async fn expire_stale_waitlist_entries(state: &SharedState) -> Result<(), AppError> {
+    sqlx::query(
+        "UPDATE waitlist_entries SET status = $1 WHERE status = $2 AND expires_at <= NOW()",
+    )
+    .bind(WaitlistStatus::Expired)
+    .bind(WaitlistStatus::Waiting)
+    .execute(&state.db_pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    Ok(())
+}
+
+// Refunds a cancelled appointment's paid bills when the refund policy allows it. Like
+// offer_freed_slot, failures are logged because the cancellation has already been committed.
+async fn refund_within_policy(state: SharedState, cancelled: &Appointment, cancelled_by: Uuid) {
+    if let Err(e) = refund_cancelled_appointment(state, cancelled, Some(cancelled_by)).await {
+        tracing::warn!(appointment = %cancelled.id, error = %e, "failed to refund cancelled appointment");
+    }
+}
+
+// Offers a freed slot to the waitlist. Failures are only logged, since the change that freed
+// the slot has already been committed.
+async fn offer_freed_slot(state: SharedState, freed: &Appointment) {
+    match fill_from_waitlist(state, freed).await {
+        Ok(Some(appointment)) => tracing::info!(
+            freed = %freed.id,
+            booked = %appointment.id,
+            "freed slot booked from the waitlist"
+        ),
+        Ok(None) => {}
+        Err(e) => tracing::warn!(freed = %freed.id, error = %e, "failed to offer slot to waitlist"),
+    }
+}
+
+// Books the longest-waiting matching patient into the freed slot and notifies them
+async fn fill_from_waitlist(
+    state: SharedState,
+    freed: &Appointment,
+) -> Result<Option<Appointment>, AppError> {
+    expire_stale_waitlist_entries(&state).await?;
+    if freed.time <= Utc::now() {
+        return Ok(None);
+    }
+    let doctor = get_doctor_by_id(state.clone(), freed.doctor_id.to_string()).await?;
+
+    let mut tx = state
+        .db_pool
+        .begin()
         .await
         .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    let entry = sqlx::query_as::<_, WaitlistEntry>(
+        "SELECT * FROM waitlist_entries WHERE status = $1 AND window_start <= $2 AND window_end > $2 AND expires_at > NOW() AND (specialization IS NULL OR LOWER(specialization) = LOWER($3)) \
+         AND patient_id IN (SELECT id FROM patients WHERE hospital_id IS NOT DISTINCT FROM $4) ORDER BY created_at LIMIT 1 FOR UPDATE SKIP LOCKED",
+    )
+    .bind(WaitlistStatus::Waiting)
+    .bind(freed.time)
+    .bind(&doctor.specialization)
+    .bind(freed.hospital_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    let Some(entry) = entry else {
+        return Ok(None);
+    };
 
-    get_appointment_by_id(state, appointment_id).await
+    let price = resolve_appointment_price(
+        state.clone(),
+        freed.hospital_id,
+        &doctor,
+        VisitType::default(),
+    )
+    .await?;
+    let appointment = Appointment::new(
+        entry.patient_id,
+        freed.hospital_id,
+        &doctor,
+        entry.purpose.clone(),
+        freed.time,
+        VisitType::default(),
+        price,
+    );
+    if !try_book_slot(&mut tx, &appointment).await? {
+        return Ok(None);
+    }
+    record_event(
+        &mut tx,
+        appointment.id,
+        None,
+        appointment.status,
+        None,
+        Some("Booked from waitlist".to_string()),
+    )
+    .await?;
+    sqlx::query("UPDATE waitlist_entries SET status = $1, appointment_id = $2 WHERE id = $3")
+        .bind(WaitlistStatus::Booked)
+        .bind(appointment.id)
+        .bind(entry.id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    notify_patient(
+        &mut tx,
+        entry.patient_id,
+        format!(
+            "A slot opened up: you are booked with {} at {}",
+            doctor.name,
+            appointment.time.to_rfc3339()
+        ),
+    )
+    .await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    Ok(Some(appointment))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::admin::models::UserRole;
+    use crate::test_support::TestApp;
+    use reqwest::StatusCode;
+    use serde_json::Value;
+    use sqlx::PgPool;
+
+    async fn set_status(app: &TestApp, token: &str, id: Uuid, status: &str) -> StatusCode {
+        app.put(token, &format!("/appointments/{id}/status?status={status}"))
+            .await
+            .status()
+    }
+
+    #[sqlx::test]
+    async fn refuses_moves_outside_the_lifecycle(pool: PgPool) {
+        let app = TestApp::spawn(pool).await;
+        let hospital = app.create_hospital("Lagos General").await;
+        let token = app.login_as(hospital.id, UserRole::Admin).await;
+        let id = hospital.appointment_id;
+
+        // A week out, so the visit cannot be over or missed yet
+        for status in ["Done", "NoShow", "InProgress"] {
+            assert_eq!(
+                set_status(&app, &token, id, status).await,
+                StatusCode::CONFLICT,
+                "{status}"
+            );
+        }
+        assert_eq!(
+            set_status(&app, &token, id, "Rescheduled").await,
+            StatusCode::UNPROCESSABLE_ENTITY
+        );
+        assert_eq!(app.status_of("appointments", id).await, "Scheduled");
+
+        assert_eq!(
+            set_status(&app, &token, id, "Cancelled").await,
+            StatusCode::OK
+        );
+        for status in ["Scheduled", "CheckedIn", "Cancelled"] {
+            assert_eq!(
+                set_status(&app, &token, id, status).await,
+                StatusCode::CONFLICT,
+                "{status}"
+            );
+        }
+        assert_eq!(app.status_of("appointments", id).await, "Cancelled");
+    }
+
+    #[sqlx::test]
+    async fn records_each_status_change(pool: PgPool) {
+        let app = TestApp::spawn(pool).await;
+        let hospital = app.create_hospital("Lagos General").await;
+        let token = app.login_as(hospital.id, UserRole::Admin).await;
+        let id = hospital.appointment_id;
+        sqlx::query("UPDATE appointments SET time = NOW() - INTERVAL '1 hour' WHERE id = $1")
+            .bind(id)
+            .execute(&app.pool)
+            .await
+            .unwrap();
+
+        for status in ["CheckedIn", "InProgress", "Done"] {
+            assert_eq!(
+                set_status(&app, &token, id, status).await,
+                StatusCode::OK,
+                "{status}"
+            );
+        }
+        // A refused move leaves no trace
+        assert_eq!(
+            set_status(&app, &token, id, "Cancelled").await,
+            StatusCode::CONFLICT
+        );
+
+        let response = app.get(&token, &format!("/appointments/{id}/events")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: Value = response.json().await.unwrap();
+        let moves: Vec<(&str, &str)> = body["events"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|event| {
+                assert!(event["actor_id"].is_string());
+                (
+                    event["from_status"].as_str().unwrap(),
+                    event["to_status"].as_str().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            moves,
+            [
+                ("Scheduled", "CheckedIn"),
+                ("CheckedIn", "InProgress"),
+                ("InProgress", "Done"),
+            ]
+        );
+    }
+
+    #[sqlx::test]
+    async fn cancelling_in_time_refunds_the_visit(pool: PgPool) {
+        let app = TestApp::spawn(pool).await;
+        let hospital = app.create_hospital("Lagos General").await;
+        let token = app.login_as(hospital.id, UserRole::Admin).await;
+        let response = app
+            .post_json(
+                &token,
+                &format!("/billing/{}/payments", hospital.bill_id),
+                serde_json::json!({"method": "Cash", "receipt_number": "R-001"}),
+            )
+            .await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(app.status_of("bills", hospital.bill_id).await, "Paid");
+
+        // The visit is a week out, well before the refund cutoff
+        assert_eq!(
+            set_status(&app, &token, hospital.appointment_id, "Cancelled").await,
+            StatusCode::OK
+        );
+        assert_eq!(app.status_of("bills", hospital.bill_id).await, "Refunded");
+        let body: Value = app
+            .get(&token, &format!("/billing/{}/refunds", hospital.bill_id))
+            .await
+            .json()
+            .await
+            .unwrap();
+        let refunds = body["refunds"].as_array().unwrap();
+        assert_eq!(refunds.len(), 1);
+        // Cash goes back over the desk rather than through the gateway
+        assert_eq!(refunds[0]["status"], "manual");
+        assert_eq!(refunds[0]["reason"], "Appointment cancelled");
+    }
+
+    // 10:00 hospital time on the next Monday
+    fn next_monday() -> DateTime<FixedOffset> {
+        let today = Utc::now().with_timezone(&hospital_offset()).date_naive();
+        let date = today + Duration::days(7 - today.weekday().num_days_from_monday() as i64);
+        hospital_offset()
+            .from_local_datetime(&date.and_hms_opt(10, 0, 0).unwrap())
+            .unwrap()
+    }
+
+    #[test]
+    fn spaces_occurrences_by_the_interval() {
+        let first = next_monday();
+        let times = series_occurrences(first, 2, Some(3), None).unwrap();
+        assert_eq!(
+            times,
+            [
+                first,
+                first + Duration::weeks(2),
+                first + Duration::weeks(4)
+            ]
+        );
+
+        // `until` is inclusive and wins over a longer count
+        let until = (first + Duration::weeks(3)).date_naive();
+        let times = series_occurrences(first, 1, Some(10), Some(until)).unwrap();
+        assert_eq!(times.len(), 4);
+        assert_eq!(times.last(), Some(&(first + Duration::weeks(3))));
+    }
+
+    #[test]
+    fn refuses_series_outside_the_limits() {
+        let first = next_monday();
+        let far = (first + Duration::weeks(MAX_SERIES_OCCURRENCES as i64)).date_naive();
+        let before = (first - Duration::days(1)).date_naive();
+        for (interval, count, until, field) in [
+            (0, Some(3), None, "interval_weeks"),
+            (13, Some(3), None, "interval_weeks"),
+            (1, None, None, "count"),
+            (1, Some(0), None, "count"),
+            (1, Some(MAX_SERIES_OCCURRENCES + 1), None, "count"),
+            (1, None, Some(far), "until"),
+            (1, None, Some(before), "until"),
+        ] {
+            match series_occurrences(first, interval, count, until) {
+                Err(AppError::UnProcessableEntity { field: got, .. }) => assert_eq!(got, field),
+                other => panic!(
+                    "{interval} {count:?} {until:?}: {:?}",
+                    other.map(|t| t.len())
+                ),
+            }
+        }
+    }
+
+    #[sqlx::test]
+    async fn books_every_occurrence_or_none(pool: PgPool) {
+        let app = TestApp::spawn(pool).await;
+        let hospital = app.create_hospital("Lagos General").await;
+        let token = app.login_as(hospital.id, UserRole::Receptionist).await;
+        sqlx::query("UPDATE doctors SET working_hours = '[{\"day\": \"Monday\", \"start\": \"09:00:00\", \"end\": \"12:00:00\"}]' WHERE id = $1")
+            .bind(hospital.doctor_id)
+            .execute(&app.pool)
+            .await
+            .unwrap();
+        let first = next_monday();
+        let series = |start: DateTime<FixedOffset>| {
+            serde_json::json!({
+                "patient_id": hospital.patient_id,
+                "doctor_id": hospital.doctor_id,
+                "date": start.to_rfc3339(),
+                "purpose": "Physiotherapy",
+                "interval_weeks": 1,
+                "count": 3,
+            })
+        };
+
+        let response = app
+            .post_json(&token, "/appointments/series", series(first))
+            .await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let body: Value = response.json().await.unwrap();
+        let times: Vec<DateTime<Utc>> = body["appointments"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|appointment| {
+                assert_eq!(appointment["series_id"], body["series"]["id"]);
+                assert_eq!(appointment["hospital_id"], serde_json::json!(hospital.id));
+                appointment["time"].as_str().unwrap().parse().unwrap()
+            })
+            .collect();
+        assert_eq!(
+            times,
+            (0..3)
+                .map(|week| (first + Duration::weeks(week)).with_timezone(&Utc))
+                .collect::<Vec<_>>()
+        );
+
+        // Weeks four and five are free but week six is taken, so none of them are booked
+        sqlx::query("INSERT INTO appointments (id, patient_id, doctor_id, time, purpose, status, price, hospital_id) VALUES ($1, $2, $3, $4, 'Checkup', 'Scheduled', 10000, $5)")
+            .bind(Uuid::new_v4())
+            .bind(hospital.patient_id)
+            .bind(hospital.doctor_id)
+            .bind(first + Duration::weeks(5))
+            .bind(hospital.id)
+            .execute(&app.pool)
+            .await
+            .unwrap();
+        let response = app
+            .post_json(
+                &token,
+                "/appointments/series",
+                series(first + Duration::weeks(3)),
+            )
+            .await;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        let (booked, series_count): (i64, i64) = sqlx::query_as(
+            "SELECT (SELECT COUNT(*) FROM appointments WHERE series_id IS NOT NULL), \
+             (SELECT COUNT(*) FROM appointment_series)",
+        )
+        .fetch_one(&app.pool)
+        .await
+        .unwrap();
+        assert_eq!(booked, 3);
+        assert_eq!(series_count, 1);
+    }
 }
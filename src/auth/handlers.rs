@@ -1,5 +1,11 @@
 use crate::{
-    app_state::SharedState, auth::headers::ClaimsHeader, auth::models::LoginRequest, auth::service,
+    app_state::SharedState,
+    auth::headers::ClaimsHeader,
+    auth::models::{
+        ForgotPasswordRequest, LoginRequest, RefreshRequest, ResetPasswordRequest,
+        VerifyEmailRequest,
+    },
+    auth::service,
     errors::AppError,
 };
 use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
@@ -20,6 +26,118 @@ pub async fn login_handler(
     }
 }
 
+pub async fn refresh_handler(
+    State(state): State<SharedState>,
+    Json(data): Json<RefreshRequest>,
+) -> impl IntoResponse {
+    match service::refresh_tokens(state, data).await {
+        Ok(response) => Json(response).into_response(),
+        Err(e) => match e {
+            AppError::Unauthorized(e) => {
+                (StatusCode::UNAUTHORIZED, Json(json!({"error": e}))).into_response()
+            }
+            _ => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response(),
+        },
+    }
+}
+
+pub async fn logout_handler(
+    State(state): State<SharedState>,
+    claims: ClaimsHeader,
+) -> impl IntoResponse {
+    match service::logout(state, claims).await {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn forgot_password_handler(
+    State(state): State<SharedState>,
+    Json(data): Json<ForgotPasswordRequest>,
+) -> impl IntoResponse {
+    match service::forgot_password(state, data).await {
+        Ok(()) => (
+            StatusCode::ACCEPTED,
+            Json(json!({"message": "If the address has an account, a reset token is on its way"})),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn reset_password_handler(
+    State(state): State<SharedState>,
+    Json(data): Json<ResetPasswordRequest>,
+) -> impl IntoResponse {
+    match service::reset_password(state, data).await {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => match e {
+            AppError::UnProcessableEntity { field, message } => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(json!({"error": format!("{}: {}", field, message)})),
+            )
+                .into_response(),
+            _ => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response(),
+        },
+    }
+}
+
+pub async fn verify_email_handler(
+    State(state): State<SharedState>,
+    Json(data): Json<VerifyEmailRequest>,
+) -> impl IntoResponse {
+    match service::verify_email(state, data).await {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => match e {
+            AppError::UnProcessableEntity { field, message } => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(json!({"error": format!("{}: {}", field, message)})),
+            )
+                .into_response(),
+            _ => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response(),
+        },
+    }
+}
+
+pub async fn resend_email_verification_handler(
+    State(state): State<SharedState>,
+    claims: ClaimsHeader,
+) -> impl IntoResponse {
+    match service::resend_email_verification(state, claims).await {
+        Ok(()) => StatusCode::ACCEPTED.into_response(),
+        Err(e) => match e {
+            AppError::Conflict(e) => {
+                (StatusCode::CONFLICT, Json(json!({"error": e}))).into_response()
+            }
+            _ => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response(),
+        },
+    }
+}
+
 pub async fn get_user_info_handler(
     State(state): State<SharedState>,
     claims: ClaimsHeader,
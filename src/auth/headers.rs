@@ -1,7 +1,8 @@
+use crate::app_state::SharedState;
 use crate::auth::models::Claims;
 use crate::errors::AppError;
 use crate::utils::get_secret_key;
-use axum::extract::FromRequestParts;
+use axum::extract::{FromRef, FromRequestParts};
 use axum::http::request::Parts;
 use axum_extra::TypedHeader;
 use axum_extra::headers::{Authorization, authorization::Bearer};
@@ -10,10 +11,16 @@ use jsonwebtoken::{DecodingKey, Validation, decode};
 impl<S> FromRequestParts<S> for Claims
 where
     S: Send + Sync,
+    SharedState: FromRef<S>,
 {
     type Rejection = AppError;
 
     async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, AppError> {
+        // Already checked by an earlier extractor or middleware for this request
+        if let Some(claims) = parts.extensions.get::<Claims>() {
+            return Ok(claims.clone());
+        }
+
         // Extract the Authorization header
         let TypedHeader(Authorization(bearer)) =
             TypedHeader::<Authorization<Bearer>>::from_request_parts(parts, state)
@@ -32,6 +39,20 @@ where
         )
         .map_err(|_| AppError::Unauthorized("Invalid or expired token".to_string()))?;
 
+        // Tokens revoked by logging out or by refresh-token reuse
+        let state = SharedState::from_ref(state);
+        let revoked = sqlx::query_scalar::<_, bool>(
+            "SELECT EXISTS (SELECT 1 FROM revoked_tokens WHERE jti = $1)",
+        )
+        .bind(token_data.claims.jti)
+        .fetch_one(&state.db_pool)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        if revoked {
+            return Err(AppError::Unauthorized("Token has been revoked".to_string()));
+        }
+
+        parts.extensions.insert(token_data.claims.clone());
         Ok(token_data.claims)
     }
 }
@@ -1,5 +1,6 @@
 pub mod handlers;
 pub mod headers;
 pub mod models;
+pub mod permissions;
 pub mod router;
 pub mod service;
@@ -1,7 +1,10 @@
 use crate::admin::models::{User, UserRole};
+use crate::config::ACCESS_TOKEN_MINUTES;
 use crate::utils::get_secret_key;
+use chrono::{DateTime, Utc};
 use jsonwebtoken::{Algorithm, EncodingKey, Header, encode};
 use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, Type};
 use uuid::Uuid;
 
 #[derive(Deserialize)]
@@ -13,13 +16,62 @@ pub struct LoginRequest {
 #[derive(Serialize)]
 pub struct LoginResponse {
     pub token: String,
+    pub refresh_token: String,
+    pub expires_in: i64, // Seconds until `token` expires
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Deserialize)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+#[derive(Deserialize)]
+pub struct ForgotPasswordRequest {
+    pub email: String,
+}
+
+#[derive(Deserialize)]
+pub struct ResetPasswordRequest {
+    pub token: String,
+    pub password: String,
+}
+
+#[derive(Deserialize)]
+pub struct VerifyEmailRequest {
+    pub token: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Type)]
+#[sqlx(type_name = "VARCHAR", rename_all = "PascalCase")]
+pub enum TokenPurpose {
+    PasswordReset,
+    EmailVerification,
+}
+
+#[derive(FromRow)]
+pub struct UserToken {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub expires_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+}
+
+#[derive(FromRow)]
+pub struct RefreshToken {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub family_id: Uuid,
+    pub expires_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: Uuid, // user id
     pub hospital_id: Uuid,
     pub role: UserRole,
+    pub jti: Uuid,  // token id, for revocation
     pub exp: usize, // expiry
 }
 
@@ -29,7 +81,17 @@ impl Claims {
             sub: user.id,
             hospital_id: user.hospital_id,
             role: user.role,
-            exp: (chrono::Utc::now() + chrono::Duration::hours(24)).timestamp() as usize,
+            jti: Uuid::new_v4(),
+            exp: (Utc::now() + chrono::Duration::minutes(ACCESS_TOKEN_MINUTES)).timestamp()
+                as usize,
+        }
+    }
+
+    // The hospital the caller's queries are limited to; None for super admins, who see them all
+    pub fn tenant(&self) -> Option<Uuid> {
+        match self.role {
+            UserRole::SuperAdmin => None,
+            _ => Some(self.hospital_id),
         }
     }
 
@@ -38,13 +100,11 @@ impl Claims {
 
         let secret_key = get_secret_key().expect("Failed to get secret key from config");
 
-        let token = encode(
+        encode(
             &header,
             self,
-            &EncodingKey::from_secret(&secret_key.as_bytes()),
+            &EncodingKey::from_secret(secret_key.as_bytes()),
         )
-        .expect("Failed to encode JWT");
-
-        token
+        .expect("Failed to encode JWT")
     }
 }
@@ -0,0 +1,292 @@
+// Route-level authorization. Routers wrap each group of routes in `require` with the permission
+// it needs, and the matrix in `UserRole::can` decides which roles hold it.
+use crate::admin::models::UserRole;
+use crate::app_state::SharedState;
+use crate::auth::models::Claims;
+use crate::errors::AppError;
+use axum::{
+    Router,
+    extract::{FromRequestParts, Request, State},
+    middleware::{self, Next},
+    response::Response,
+};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    ViewHospital,
+    ManageHospital, // Hospital details, prices and payment settings
+    ManageUsers,    // Staff and patient login accounts
+    ViewPatients,
+    ManagePatients,
+    ViewDoctors,
+    ManageDoctors,
+    ViewAppointments,
+    BookAppointments,
+    UpdateAppointments, // Check-in, status changes and rescheduling
+    ManageWaitlist,
+    ViewOrders,
+    ManageOrders, // Lab tests, drugs and other clinical orders
+    ViewBills,
+    IssueBills,
+    PayBills,        // Online payments through the gateway
+    CollectPayments, // Cash, POS and bank transfers at the desk
+    RefundBills,
+    ViewInsurance,
+    ManagePolicies,
+    ManageClaims, // HMO providers, claim statuses and exports
+    ViewNotifications,
+}
+
+impl UserRole {
+    pub fn can(self, permission: Permission) -> bool {
+        use Permission::*;
+        match self {
+            UserRole::SuperAdmin | UserRole::Admin => true,
+            _ if permission == ViewHospital => true,
+            UserRole::Doctor => matches!(
+                permission,
+                ViewPatients
+                    | ViewDoctors
+                    | ViewAppointments
+                    | BookAppointments
+                    | UpdateAppointments
+                    | ViewOrders
+                    | ManageOrders
+                    | ViewBills
+                    | ViewInsurance
+                    | ViewNotifications
+            ),
+            UserRole::Nurse => matches!(
+                permission,
+                ViewPatients
+                    | ViewDoctors
+                    | ViewAppointments
+                    | UpdateAppointments
+                    | ViewOrders
+                    | ManageOrders
+                    | ViewNotifications
+            ),
+            UserRole::Receptionist => matches!(
+                permission,
+                ViewPatients
+                    | ManagePatients
+                    | ViewDoctors
+                    | ViewAppointments
+                    | BookAppointments
+                    | UpdateAppointments
+                    | ManageWaitlist
+                    | ViewBills
+                    | ViewInsurance
+                    | ManagePolicies
+                    | ViewNotifications
+            ),
+            UserRole::Cashier => matches!(
+                permission,
+                ViewPatients
+                    | ViewDoctors
+                    | ViewAppointments
+                    | ViewOrders
+                    | ViewBills
+                    | IssueBills
+                    | PayBills
+                    | CollectPayments
+                    | ViewInsurance
+                    | ManagePolicies
+                    | ManageClaims
+                    | ViewNotifications
+            ),
+            // Patients only ever see their own records; see `patient_scope`
+            UserRole::Patient => matches!(
+                permission,
+                ViewDoctors
+                    | ViewAppointments
+                    | BookAppointments
+                    | ManageWaitlist
+                    | ViewBills
+                    | PayBills
+                    | ViewNotifications
+            ),
+        }
+    }
+}
+
+// Puts every route in `routes` behind `permission`
+pub fn require<S>(state: &SharedState, permission: Permission, routes: Router<S>) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    routes.route_layer(middleware::from_fn_with_state(
+        (state.clone(), permission),
+        authorize,
+    ))
+}
+
+// Rejects requests without a valid, unrevoked token (401) or whose role lacks the permission (403)
+pub async fn authorize(
+    State((state, permission)): State<(SharedState, Permission)>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let (mut parts, body) = request.into_parts();
+    let claims = Claims::from_request_parts(&mut parts, &state).await?;
+    if !claims.role.can(permission) {
+        return Err(AppError::Forbidden(format!(
+            "{:?} users cannot do this ({:?})",
+            claims.role, permission
+        )));
+    }
+    Ok(next.run(Request::from_parts(parts, body)).await)
+}
+
+// Narrows a patient filter to the caller's own record when they are a patient. Staff get the
+// filter they asked for; a patient asking for someone else is refused.
+pub async fn patient_scope(
+    state: &SharedState,
+    claims: &Claims,
+    requested: Option<Uuid>,
+) -> Result<Option<Uuid>, AppError> {
+    if claims.role != UserRole::Patient {
+        return Ok(requested);
+    }
+    let own = sqlx::query_scalar::<_, Uuid>("SELECT id FROM patients WHERE user_id = $1")
+        .bind(claims.sub)
+        .fetch_optional(&state.db_pool)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?
+        .ok_or_else(|| {
+            AppError::Forbidden("Your account is not linked to a patient record".to_string())
+        })?;
+    match requested {
+        Some(patient_id) if patient_id != own => Err(AppError::Forbidden(
+            "You can only access your own records".to_string(),
+        )),
+        _ => Ok(Some(own)),
+    }
+}
+
+// Checks a record against the caller: staff of another hospital get a 404, as if it did not
+// exist, and a patient gets a 403 unless the record is theirs. `query` selects the record's
+// hospital_id and patient_id by id. Unknown or malformed ids are let through for the handler to
+// report.
+async fn ensure_record_access(
+    state: &SharedState,
+    claims: &Claims,
+    what: &str,
+    query: &str,
+    id: &str,
+) -> Result<(), AppError> {
+    if claims.tenant().is_none() {
+        return Ok(());
+    }
+    let Ok(id) = Uuid::parse_str(id) else {
+        return Ok(());
+    };
+    let record = sqlx::query_as::<_, (Option<Uuid>, Uuid)>(query)
+        .bind(id)
+        .fetch_optional(&state.db_pool)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    let Some((hospital_id, patient_id)) = record else {
+        return Ok(());
+    };
+    if hospital_id != claims.tenant() {
+        return Err(AppError::NotFound(format!("{} not found", what)));
+    }
+    patient_scope(state, claims, Some(patient_id))
+        .await
+        .map(|_| ())
+}
+
+pub async fn ensure_patient_access(
+    state: &SharedState,
+    claims: &Claims,
+    patient_id: Uuid,
+) -> Result<(), AppError> {
+    ensure_record_access(
+        state,
+        claims,
+        "Patient",
+        "SELECT hospital_id, id FROM patients WHERE id = $1",
+        &patient_id.to_string(),
+    )
+    .await
+}
+
+pub async fn ensure_appointment_access(
+    state: &SharedState,
+    claims: &Claims,
+    appointment_id: &str,
+) -> Result<(), AppError> {
+    ensure_record_access(
+        state,
+        claims,
+        "Appointment",
+        "SELECT hospital_id, patient_id FROM appointments WHERE id = $1",
+        appointment_id,
+    )
+    .await
+}
+
+pub async fn ensure_series_access(
+    state: &SharedState,
+    claims: &Claims,
+    series_id: &str,
+) -> Result<(), AppError> {
+    ensure_record_access(
+        state,
+        claims,
+        "Series",
+        "SELECT p.hospital_id, s.patient_id FROM appointment_series s JOIN patients p ON p.id = s.patient_id WHERE s.id = $1",
+        series_id,
+    )
+    .await
+}
+
+pub async fn ensure_waitlist_access(
+    state: &SharedState,
+    claims: &Claims,
+    entry_id: &str,
+) -> Result<(), AppError> {
+    ensure_record_access(
+        state,
+        claims,
+        "Waitlist entry",
+        "SELECT p.hospital_id, w.patient_id FROM waitlist_entries w JOIN patients p ON p.id = w.patient_id WHERE w.id = $1",
+        entry_id,
+    )
+    .await
+}
+
+pub async fn ensure_bill_access(
+    state: &SharedState,
+    claims: &Claims,
+    bill_id: &str,
+) -> Result<(), AppError> {
+    ensure_record_access(
+        state,
+        claims,
+        "Bill",
+        "SELECT b.hospital_id, a.patient_id FROM bills b JOIN appointments a ON a.id = b.appointment_id WHERE b.id = $1",
+        bill_id,
+    )
+    .await
+}
+
+// As `ensure_bill_access`, for the bill a gateway payment reference belongs to
+pub async fn ensure_payment_access(
+    state: &SharedState,
+    claims: &Claims,
+    reference: &str,
+) -> Result<(), AppError> {
+    let bill_id =
+        sqlx::query_scalar::<_, Uuid>("SELECT bill_id FROM payments WHERE reference = $1")
+            .bind(reference)
+            .fetch_optional(&state.db_pool)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    match bill_id {
+        Some(bill_id) => ensure_bill_access(state, claims, &bill_id.to_string()).await,
+        None => Ok(()),
+    }
+}
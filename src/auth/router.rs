@@ -1,5 +1,8 @@
 use crate::app_state::{AppState, SharedState};
-use crate::auth::handlers::{get_user_info_handler, login_handler};
+use crate::auth::handlers::{
+    forgot_password_handler, get_user_info_handler, login_handler, logout_handler, refresh_handler,
+    resend_email_verification_handler, reset_password_handler, verify_email_handler,
+};
 use axum::{
     Router,
     routing::{get, post},
@@ -9,6 +12,15 @@ use std::sync::Arc;
 pub fn auth_router(state: SharedState) -> Router<Arc<AppState>> {
     Router::new()
         .route("/login", post(login_handler))
+        .route("/refresh", post(refresh_handler))
+        .route("/logout", post(logout_handler))
+        .route("/forgot-password", post(forgot_password_handler))
+        .route("/reset-password", post(reset_password_handler))
+        .route("/verify-email", post(verify_email_handler))
+        .route(
+            "/verify-email/resend",
+            post(resend_email_verification_handler),
+        )
         .route("/me", get(get_user_info_handler))
         .with_state(state)
 }
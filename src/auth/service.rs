@@ -1,10 +1,25 @@
 use crate::{
-    admin::service::{get_user_by_email, get_user_by_id},
+    admin::{
+        models::User,
+        service::{get_user_by_email, get_user_by_id},
+    },
     app_state::SharedState,
-    auth::models::{Claims, LoginRequest, LoginResponse},
+    auth::models::{
+        Claims, ForgotPasswordRequest, LoginRequest, LoginResponse, RefreshRequest, RefreshToken,
+        ResetPasswordRequest, TokenPurpose, UserToken, VerifyEmailRequest,
+    },
+    config::{
+        ACCESS_TOKEN_MINUTES, EMAIL_VERIFICATION_TOKEN_HOURS, INVITATION_TOKEN_HOURS,
+        MIN_PASSWORD_LENGTH, OPAQUE_TOKEN_LENGTH, PASSWORD_RESET_TOKEN_MINUTES, REFRESH_TOKEN_DAYS,
+    },
     errors::AppError,
-    utils::verify_password,
+    mailer::Email,
+    utils::{create_random_string, hash_password, hash_token, verify_password},
 };
+use chrono::{DateTime, Duration, Utc};
+use sqlx::PgConnection;
+use tracing::warn;
+use uuid::Uuid;
 
 pub async fn verify_login(
     state: SharedState,
@@ -14,9 +29,21 @@ pub async fn verify_login(
         .await
         .map_err(|_| AppError::Unauthorized("Invalid email or password".to_string()))?;
     if verify_password(&login_request.password, &user_data.password_hash)? {
-        let user_claims = Claims::new(user_data);
-        let token = user_claims.generate_token();
-        Ok(LoginResponse { token })
+        if !user_data.is_active {
+            return Err(AppError::Unauthorized(
+                "This account has been deactivated".to_string(),
+            ));
+        }
+        let mut tx = state
+            .db_pool
+            .begin()
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        let response = issue_tokens(&mut tx, user_data, Uuid::new_v4()).await?;
+        tx.commit()
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        Ok(response)
     } else {
         Err(AppError::Unauthorized(
             "Invalid email or password".to_string(),
@@ -24,6 +51,406 @@ pub async fn verify_login(
     }
 }
 
+// Mints an access token and a refresh token in `family_id`, storing only the refresh token's hash
+async fn issue_tokens(
+    conn: &mut PgConnection,
+    user: User,
+    family_id: Uuid,
+) -> Result<LoginResponse, AppError> {
+    let user_id = user.id;
+    let claims = Claims::new(user);
+    let refresh_token = create_random_string(OPAQUE_TOKEN_LENGTH);
+    sqlx::query("INSERT INTO refresh_tokens (id, user_id, family_id, token_hash, access_jti, expires_at) VALUES ($1, $2, $3, $4, $5, $6)")
+        .bind(Uuid::new_v4())
+        .bind(user_id)
+        .bind(family_id)
+        .bind(hash_token(&refresh_token))
+        .bind(claims.jti)
+        .bind(Utc::now() + Duration::days(REFRESH_TOKEN_DAYS))
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    Ok(LoginResponse {
+        token: claims.generate_token(),
+        refresh_token,
+        expires_in: ACCESS_TOKEN_MINUTES * 60,
+    })
+}
+
+// Exchanges a refresh token for a new pair. Each refresh token works once: presenting one that
+// was already exchanged means it was stolen or leaked, so the whole family is revoked and the
+// user has to log in again.
+pub async fn refresh_tokens(
+    state: SharedState,
+    request: RefreshRequest,
+) -> Result<LoginResponse, AppError> {
+    let mut tx = state
+        .db_pool
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    let token = sqlx::query_as::<_, RefreshToken>(
+        "SELECT * FROM refresh_tokens WHERE token_hash = $1 FOR UPDATE",
+    )
+    .bind(hash_token(request.refresh_token.trim()))
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?
+    .ok_or_else(|| AppError::Unauthorized("Invalid refresh token".to_string()))?;
+
+    if token.revoked_at.is_some() {
+        return Err(AppError::Unauthorized(
+            "Refresh token has been revoked".to_string(),
+        ));
+    }
+    if token.used_at.is_some() {
+        warn!(
+            "Refresh token {} reused; revoking family {}",
+            token.id, token.family_id
+        );
+        revoke_family(&mut tx, token.family_id).await?;
+        tx.commit()
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        return Err(AppError::Unauthorized(
+            "Refresh token has already been used; please log in again".to_string(),
+        ));
+    }
+    if token.expires_at <= Utc::now() {
+        return Err(AppError::Unauthorized(
+            "Refresh token has expired".to_string(),
+        ));
+    }
+
+    sqlx::query("UPDATE refresh_tokens SET used_at = NOW() WHERE id = $1")
+        .bind(token.id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    let user = get_user_by_id(state.clone(), token.user_id)
+        .await
+        .ok()
+        .filter(|user| user.is_active)
+        .ok_or_else(|| AppError::Unauthorized("Invalid refresh token".to_string()))?;
+    let response = issue_tokens(&mut tx, user, token.family_id).await?;
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    Ok(response)
+}
+
+// Revokes the caller's access token and the refresh tokens of the login it came from
+pub async fn logout(state: SharedState, claims: Claims) -> Result<(), AppError> {
+    let mut tx = state
+        .db_pool
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    let family_id =
+        sqlx::query_scalar::<_, Uuid>("SELECT family_id FROM refresh_tokens WHERE access_jti = $1")
+            .bind(claims.jti)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    if let Some(family_id) = family_id {
+        revoke_family(&mut tx, family_id).await?;
+    }
+    let expires_at = DateTime::from_timestamp(claims.exp as i64, 0).unwrap_or_else(Utc::now);
+    deny_access_token(&mut tx, claims.jti, expires_at).await?;
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    Ok(())
+}
+
+// Revokes every refresh token in a family, and the access tokens issued with them that have not
+// expired yet
+async fn revoke_family(conn: &mut PgConnection, family_id: Uuid) -> Result<(), AppError> {
+    let access_tokens = sqlx::query_as::<_, (Uuid, DateTime<Utc>)>(
+        "UPDATE refresh_tokens SET revoked_at = COALESCE(revoked_at, NOW()) WHERE family_id = $1 \
+         RETURNING access_jti, created_at + make_interval(mins => $2)",
+    )
+    .bind(family_id)
+    .bind(ACCESS_TOKEN_MINUTES as i32)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    for (jti, expires_at) in access_tokens {
+        if expires_at > Utc::now() {
+            deny_access_token(conn, jti, expires_at).await?;
+        }
+    }
+    Ok(())
+}
+
+async fn deny_access_token(
+    conn: &mut PgConnection,
+    jti: Uuid,
+    expires_at: DateTime<Utc>,
+) -> Result<(), AppError> {
+    // Entries are only needed until the token would have expired anyway
+    sqlx::query("DELETE FROM revoked_tokens WHERE expires_at < NOW()")
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    sqlx::query(
+        "INSERT INTO revoked_tokens (jti, expires_at) VALUES ($1, $2) ON CONFLICT (jti) DO NOTHING",
+    )
+    .bind(jti)
+    .bind(expires_at)
+    .execute(&mut *conn)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    Ok(())
+}
+
+// Revokes every login of a user, e.g. once their password or role changes
+pub async fn revoke_user_sessions(conn: &mut PgConnection, user_id: Uuid) -> Result<(), AppError> {
+    let families = sqlx::query_scalar::<_, Uuid>(
+        "SELECT DISTINCT family_id FROM refresh_tokens WHERE user_id = $1 AND revoked_at IS NULL",
+    )
+    .bind(user_id)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    for family_id in families {
+        revoke_family(conn, family_id).await?;
+    }
+    Ok(())
+}
+
+// Emails a password reset token. Unknown addresses get the same answer as known ones, so the
+// endpoint cannot be used to find out who has an account.
+pub async fn forgot_password(
+    state: SharedState,
+    request: ForgotPasswordRequest,
+) -> Result<(), AppError> {
+    let Ok(user) = get_user_by_email(state.clone(), request.email.trim().to_string()).await else {
+        return Ok(());
+    };
+    if !user.is_active {
+        return Ok(());
+    }
+    let token = create_user_token(
+        &state,
+        user.id,
+        TokenPurpose::PasswordReset,
+        chrono::Duration::minutes(PASSWORD_RESET_TOKEN_MINUTES),
+    )
+    .await?;
+    state
+        .mailer
+        .send(Email {
+            to: user.email,
+            subject: "Reset your password".to_string(),
+            body: format!(
+                "Use this token to choose a new password: {}\n{}\nIt expires in {} minutes. If you did not ask to reset your password, you can ignore this email.",
+                token,
+                email_link(&state, "reset-password", &token),
+                PASSWORD_RESET_TOKEN_MINUTES
+            ),
+        })
+        .await
+}
+
+// Sets a new password with a reset token and logs the user out everywhere. Receiving the token
+// also proves they own the address.
+pub async fn reset_password(
+    state: SharedState,
+    request: ResetPasswordRequest,
+) -> Result<(), AppError> {
+    if request.password.chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(AppError::UnProcessableEntity {
+            field: "password".to_string(),
+            message: format!("Must be at least {} characters", MIN_PASSWORD_LENGTH),
+        });
+    }
+    let password_hash = hash_password(&request.password)?;
+    let mut tx = state
+        .db_pool
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    let user_id = consume_user_token(&mut tx, &request.token, TokenPurpose::PasswordReset).await?;
+    sqlx::query("UPDATE users SET password_hash = $1, email_verified_at = COALESCE(email_verified_at, NOW()) WHERE id = $2")
+        .bind(password_hash)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    revoke_user_sessions(&mut tx, user_id).await?;
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    Ok(())
+}
+
+// Emails a token confirming that the user owns their address
+pub async fn send_email_verification(state: &SharedState, user: &User) -> Result<(), AppError> {
+    let token = create_user_token(
+        state,
+        user.id,
+        TokenPurpose::EmailVerification,
+        chrono::Duration::hours(EMAIL_VERIFICATION_TOKEN_HOURS),
+    )
+    .await?;
+    state
+        .mailer
+        .send(Email {
+            to: user.email.clone(),
+            subject: "Verify your email address".to_string(),
+            body: format!(
+                "Hello {},\n\nUse this token to verify your email address: {}\n{}\nIt expires in {} hours.",
+                user.name,
+                token,
+                email_link(state, "verify-email", &token),
+                EMAIL_VERIFICATION_TOKEN_HOURS
+            ),
+        })
+        .await
+}
+
+// Emails a new user a token to choose their first password with. It is a password reset token
+// with a longer lifetime, and using it also verifies their address.
+pub async fn send_invitation(
+    state: &SharedState,
+    user: &User,
+    hospital_name: &str,
+) -> Result<(), AppError> {
+    let token = create_user_token(
+        state,
+        user.id,
+        TokenPurpose::PasswordReset,
+        chrono::Duration::hours(INVITATION_TOKEN_HOURS),
+    )
+    .await?;
+    state
+        .mailer
+        .send(Email {
+            to: user.email.clone(),
+            subject: format!("You have been invited to {}", hospital_name),
+            body: format!(
+                "Hello {},\n\nAn account has been created for you at {}. Use this token to choose your password: {}\n{}\nIt expires in {} hours.",
+                user.name,
+                hospital_name,
+                token,
+                email_link(state, "reset-password", &token),
+                INVITATION_TOKEN_HOURS
+            ),
+        })
+        .await
+}
+
+pub async fn resend_email_verification(state: SharedState, claims: Claims) -> Result<(), AppError> {
+    let user = get_user_by_id(state.clone(), claims.sub).await?;
+    if user.email_verified_at.is_some() {
+        return Err(AppError::Conflict(
+            "Your email address is already verified".to_string(),
+        ));
+    }
+    send_email_verification(&state, &user).await
+}
+
+pub async fn verify_email(state: SharedState, request: VerifyEmailRequest) -> Result<(), AppError> {
+    let mut tx = state
+        .db_pool
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    let user_id =
+        consume_user_token(&mut tx, &request.token, TokenPurpose::EmailVerification).await?;
+    sqlx::query(
+        "UPDATE users SET email_verified_at = COALESCE(email_verified_at, NOW()) WHERE id = $1",
+    )
+    .bind(user_id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    Ok(())
+}
+
+// Stores a new emailed token and returns it. Earlier unused tokens for the same purpose stop
+// working, so only the latest email counts.
+async fn create_user_token(
+    state: &SharedState,
+    user_id: Uuid,
+    purpose: TokenPurpose,
+    lifetime: chrono::Duration,
+) -> Result<String, AppError> {
+    let token = create_random_string(OPAQUE_TOKEN_LENGTH);
+    let mut tx = state
+        .db_pool
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    sqlx::query("UPDATE user_tokens SET used_at = NOW() WHERE user_id = $1 AND purpose = $2 AND used_at IS NULL")
+        .bind(user_id)
+        .bind(purpose)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    sqlx::query("INSERT INTO user_tokens (id, user_id, purpose, token_hash, expires_at) VALUES ($1, $2, $3, $4, $5)")
+        .bind(Uuid::new_v4())
+        .bind(user_id)
+        .bind(purpose)
+        .bind(hash_token(&token))
+        .bind(Utc::now() + lifetime)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    Ok(token)
+}
+
+// Marks an emailed token used and returns whose it is
+async fn consume_user_token(
+    conn: &mut PgConnection,
+    token: &str,
+    purpose: TokenPurpose,
+) -> Result<Uuid, AppError> {
+    let invalid = || AppError::UnProcessableEntity {
+        field: "token".to_string(),
+        message: "Invalid or expired token".to_string(),
+    };
+    let user_token = sqlx::query_as::<_, UserToken>(
+        "SELECT * FROM user_tokens WHERE token_hash = $1 AND purpose = $2 FOR UPDATE",
+    )
+    .bind(hash_token(token.trim()))
+    .bind(purpose)
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?
+    .ok_or_else(invalid)?;
+    if user_token.used_at.is_some() || user_token.expires_at <= Utc::now() {
+        return Err(invalid());
+    }
+    sqlx::query("UPDATE user_tokens SET used_at = NOW() WHERE id = $1")
+        .bind(user_token.id)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    Ok(user_token.user_id)
+}
+
+// A link into the front end for an emailed token, when APP_URL is set
+fn email_link(state: &SharedState, page: &str, token: &str) -> String {
+    match &state.app_url {
+        Some(app_url) => format!(
+            "Or open {}/{}?token={}\n",
+            app_url.trim_end_matches('/'),
+            page,
+            token
+        ),
+        None => String::new(),
+    }
+}
+
 pub async fn get_user_from_claims(
     state: SharedState,
     claims: Claims,
@@ -31,3 +458,72 @@ pub async fn get_user_from_claims(
     let user = get_user_by_id(state, claims.sub).await?;
     Ok(user)
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::TestApp;
+    use crate::utils::hash_password;
+    use reqwest::StatusCode;
+    use serde_json::{Value, json};
+    use sqlx::PgPool;
+    use uuid::Uuid;
+
+    async fn post(app: &TestApp, path: &str, body: Value) -> (StatusCode, Value) {
+        let response = app
+            .client
+            .post(format!("{}{}", app.base_url, path))
+            .json(&body)
+            .send()
+            .await
+            .unwrap();
+        let status = response.status();
+        (status, response.json().await.unwrap_or(Value::Null))
+    }
+
+    async fn refresh(app: &TestApp, refresh_token: &Value) -> (StatusCode, Value) {
+        post(
+            app,
+            "/auth/refresh",
+            json!({"refresh_token": refresh_token}),
+        )
+        .await
+    }
+
+    #[sqlx::test]
+    async fn reusing_a_refresh_token_revokes_the_login(pool: PgPool) {
+        let app = TestApp::spawn(pool).await;
+        let hospital = app.create_hospital("Lagos General").await;
+        sqlx::query("INSERT INTO users (id, name, email, password_hash, role, hospital_id) VALUES ($1, 'Ada Obi', 'ada@example.com', $2, 'Receptionist', $3)")
+            .bind(Uuid::new_v4())
+            .bind(hash_password("correct horse battery").unwrap())
+            .bind(hospital.id)
+            .execute(&app.pool)
+            .await
+            .unwrap();
+        let credentials = json!({"email": "ada@example.com", "password": "correct horse battery"});
+
+        let (status, first) = post(&app, "/auth/login", credentials.clone()).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, second) = refresh(&app, &first["refresh_token"]).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_ne!(second["refresh_token"], first["refresh_token"]);
+        let token = second["token"].as_str().unwrap();
+        assert_eq!(app.get(token, "/auth/me").await.status(), StatusCode::OK);
+
+        // A separate login is a separate family and survives the revocation
+        let (_, other) = post(&app, "/auth/login", credentials).await;
+
+        // Replaying the exchanged token revokes every token issued since
+        let (status, _) = refresh(&app, &first["refresh_token"]).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = refresh(&app, &second["refresh_token"]).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            app.get(token, "/auth/me").await.status(),
+            StatusCode::UNAUTHORIZED
+        );
+
+        let (status, _) = refresh(&app, &other["refresh_token"]).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+}
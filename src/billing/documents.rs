@@ -0,0 +1,376 @@
+// Printable invoices and receipts. Both formats are rendered in-process: HTML by hand, and PDF as
+// plain text in the built-in Courier font, so no templates, fonts or external services are needed.
+use crate::billing::models::{BillDocument, DocumentKind, Payment, PaymentStatus};
+use crate::money::Money;
+use chrono::{DateTime, Utc};
+use std::fmt::Write;
+
+// A4 in points, with the text block inside the margins
+const PAGE_WIDTH: u32 = 595;
+const PAGE_HEIGHT: u32 = 842;
+const MARGIN: u32 = 50;
+const FONT_SIZE: u32 = 9;
+const LINE_HEIGHT: u32 = 12;
+const LINES_PER_PAGE: usize = ((PAGE_HEIGHT - 2 * MARGIN) / LINE_HEIGHT) as usize;
+const LINE_WIDTH: usize = 88; // Courier characters are 0.6 of the font size wide
+
+fn title(kind: DocumentKind) -> &'static str {
+    match kind {
+        DocumentKind::Invoice => "Invoice",
+        DocumentKind::Receipt => "Receipt",
+    }
+}
+
+fn format_time(time: DateTime<Utc>) -> String {
+    time.format("%Y-%m-%d %H:%M UTC").to_string()
+}
+
+// Only money that was actually received is printed
+fn received_payments(document: &BillDocument) -> impl Iterator<Item = &Payment> {
+    document
+        .invoice
+        .payments
+        .iter()
+        .filter(|payment| payment.status == PaymentStatus::Succeeded)
+}
+
+fn payment_method(payment: &Payment) -> String {
+    match &payment.receipt_number {
+        Some(receipt_number) => format!("{:?} #{}", payment.method, receipt_number),
+        None => format!("{:?}", payment.method),
+    }
+}
+
+// Label and amount for each line of the totals block
+fn totals(document: &BillDocument, kind: DocumentKind) -> Vec<(&'static str, String)> {
+    let bill = &document.invoice.bill;
+    let refunded: Money = received_payments(document)
+        .map(|payment| payment.refunded)
+        .sum();
+    let mut totals = vec![
+        ("Subtotal", bill.subtotal.to_string()),
+        ("Discount", bill.discount.to_string()),
+        ("Tax", bill.tax.to_string()),
+        ("Total", format!("{} {}", bill.currency, bill.amount)),
+    ];
+    if bill.insurer_amount.is_positive() {
+        totals.push(("Insurance", bill.insurer_amount.to_string()));
+    }
+    totals.push(("Paid", bill.amount_paid.to_string()));
+    if refunded.is_positive() {
+        totals.push(("Refunded", refunded.to_string()));
+    }
+    if kind == DocumentKind::Invoice || document.invoice.outstanding.is_positive() {
+        totals.push(("Outstanding", document.invoice.outstanding.to_string()));
+    }
+    totals
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+pub fn render_html(document: &BillDocument, kind: DocumentKind) -> String {
+    let bill = &document.invoice.bill;
+    let appointment = &document.appointment;
+    let title = title(kind);
+    let mut html = String::new();
+
+    // Writing to a String cannot fail
+    let _ = write!(
+        html,
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>{title} {reference}</title>\n<style>\n\
+         body {{ font-family: Helvetica, Arial, sans-serif; font-size: 14px; margin: 40px; color: #222; }}\n\
+         h1 {{ margin: 0 0 4px; }}\n\
+         table {{ border-collapse: collapse; width: 100%; margin: 16px 0; }}\n\
+         th, td {{ padding: 6px 8px; border-bottom: 1px solid #ddd; text-align: left; }}\n\
+         .amount {{ text-align: right; }}\n\
+         .totals {{ width: auto; margin-left: auto; }}\n\
+         .muted {{ color: #666; }}\n\
+         </style>\n</head>\n<body>\n",
+        reference = escape_html(&bill.reference),
+    );
+    match &document.hospital {
+        Some(hospital) => {
+            let _ = write!(
+                html,
+                "<header>\n<h1>{}</h1>\n<div class=\"muted\">{}</div>\n<div class=\"muted\">{}</div>\n</header>\n",
+                escape_html(&hospital.name),
+                escape_html(&hospital.address),
+                escape_html(&hospital.phone),
+            );
+        }
+        None => html.push_str("<header>\n<h1>Hospital Portal</h1>\n</header>\n"),
+    }
+
+    let _ = write!(
+        html,
+        "<h2>{title}</h2>\n<table>\n\
+         <tr><th>Reference</th><td>{reference}</td><th>Status</th><td>{status:?}</td></tr>\n\
+         <tr><th>Issued</th><td>{issued}</td><th>Paid</th><td>{paid}</td></tr>\n\
+         <tr><th>Patient</th><td>{patient} (card {card})</td><th>Doctor</th><td>{doctor}</td></tr>\n\
+         <tr><th>Appointment</th><td>{time}</td><th>Visit</th><td>{visit:?}: {purpose}</td></tr>\n\
+         </table>\n",
+        reference = escape_html(&bill.reference),
+        status = bill.status,
+        issued = format_time(bill.created_at),
+        paid = bill
+            .paid_at
+            .map(format_time)
+            .unwrap_or_else(|| "-".to_string()),
+        patient = escape_html(&document.patient.name),
+        card = escape_html(&document.patient.card_id),
+        doctor = escape_html(&document.doctor_name),
+        time = format_time(appointment.time),
+        visit = appointment.visit_type,
+        purpose = escape_html(&appointment.purpose),
+    );
+
+    html.push_str(
+        "<table>\n<tr><th>Description</th><th class=\"amount\">Qty</th><th class=\"amount\">Unit price</th>\
+         <th class=\"amount\">Discount</th><th class=\"amount\">Tax</th><th class=\"amount\">Total</th></tr>\n",
+    );
+    for item in &document.invoice.items {
+        let _ = writeln!(
+            html,
+            "<tr><td>{}</td><td class=\"amount\">{}</td><td class=\"amount\">{}</td>\
+             <td class=\"amount\">{}</td><td class=\"amount\">{}</td><td class=\"amount\">{}</td></tr>",
+            escape_html(&item.description),
+            item.quantity,
+            item.unit_price,
+            item.discount,
+            item.tax,
+            item.total,
+        );
+    }
+    html.push_str("</table>\n<table class=\"totals\">\n");
+    for (label, amount) in totals(document, kind) {
+        let _ = writeln!(
+            html,
+            "<tr><th>{label}</th><td class=\"amount\">{}</td></tr>",
+            escape_html(&amount)
+        );
+    }
+    html.push_str("</table>\n");
+
+    let payments: Vec<&Payment> = received_payments(document).collect();
+    if !payments.is_empty() {
+        html.push_str(
+            "<h3>Payments</h3>\n<table>\n<tr><th>Date</th><th>Reference</th><th>Method</th>\
+             <th class=\"amount\">Amount</th></tr>\n",
+        );
+        for payment in payments {
+            let _ = writeln!(
+                html,
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td class=\"amount\">{}</td></tr>",
+                format_time(payment.paid_at.unwrap_or(payment.created_at)),
+                escape_html(&payment.reference),
+                escape_html(&payment_method(payment)),
+                payment.amount,
+            );
+        }
+        html.push_str("</table>\n");
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+// Fits text into a column, cutting it short when it is too long
+fn column(text: &str, width: usize) -> String {
+    if text.chars().count() > width {
+        let cut: String = text.chars().take(width - 1).collect();
+        format!("{cut}~")
+    } else {
+        text.to_string()
+    }
+}
+
+// The document as lines of fixed-width text, laid out for the PDF page
+fn text_lines(document: &BillDocument, kind: DocumentKind) -> Vec<String> {
+    let bill = &document.invoice.bill;
+    let appointment = &document.appointment;
+    let rule = "-".repeat(LINE_WIDTH);
+    let mut lines = Vec::new();
+
+    match &document.hospital {
+        Some(hospital) => {
+            lines.push(hospital.name.to_uppercase());
+            lines.push(hospital.address.clone());
+            lines.push(hospital.phone.clone());
+        }
+        None => lines.push("HOSPITAL PORTAL".to_string()),
+    }
+    lines.push(String::new());
+    let heading = title(kind).to_uppercase();
+    let reference = format!("Reference: {}", bill.reference);
+    lines.push(format!(
+        "{heading}{reference:>width$}",
+        width = LINE_WIDTH - heading.len()
+    ));
+    lines.push(format!(
+        "Issued: {}   Status: {:?}",
+        format_time(bill.created_at),
+        bill.status
+    ));
+    if let Some(paid_at) = bill.paid_at {
+        lines.push(format!("Paid: {}", format_time(paid_at)));
+    }
+    lines.push(String::new());
+    lines.push(format!(
+        "Patient: {} (card {})",
+        document.patient.name, document.patient.card_id
+    ));
+    lines.push(format!("Doctor: {}", document.doctor_name));
+    lines.push(column(
+        &format!(
+            "Appointment: {}, {:?}: {}",
+            format_time(appointment.time),
+            appointment.visit_type,
+            appointment.purpose
+        ),
+        LINE_WIDTH,
+    ));
+    lines.push(String::new());
+
+    lines.push(format!(
+        "{:<34}{:>6}{:>12}{:>12}{:>11}{:>13}",
+        "Description", "Qty", "Unit price", "Discount", "Tax", "Total"
+    ));
+    lines.push(rule.clone());
+    for item in &document.invoice.items {
+        lines.push(format!(
+            "{:<34}{:>6}{:>12}{:>12}{:>11}{:>13}",
+            column(&item.description, 33),
+            item.quantity,
+            item.unit_price.to_string(),
+            item.discount.to_string(),
+            item.tax.to_string(),
+            item.total.to_string(),
+        ));
+    }
+    lines.push(rule.clone());
+    for (label, amount) in totals(document, kind) {
+        lines.push(format!("{:>70}{:>18}", format!("{label}:"), amount));
+    }
+
+    let payments: Vec<&Payment> = received_payments(document).collect();
+    if !payments.is_empty() {
+        lines.push(String::new());
+        lines.push("PAYMENTS".to_string());
+        lines.push(format!(
+            "{:<22}{:<16}{:<32}{:>18}",
+            "Date", "Reference", "Method", "Amount"
+        ));
+        lines.push(rule);
+        for payment in payments {
+            lines.push(format!(
+                "{:<22}{:<16}{:<32}{:>18}",
+                format_time(payment.paid_at.unwrap_or(payment.created_at)),
+                column(&payment.reference, 15),
+                column(&payment_method(payment), 31),
+                payment.amount.to_string(),
+            ));
+        }
+    }
+    lines
+}
+
+// A PDF string literal. Characters outside Latin-1 cannot be shown in the standard fonts.
+fn pdf_string(text: &str) -> String {
+    let mut literal = String::from("(");
+    for c in text.chars() {
+        match c {
+            '(' | ')' | '\\' => {
+                literal.push('\\');
+                literal.push(c);
+            }
+            ' '..='~' => literal.push(c),
+            '\u{a0}'..='\u{ff}' => {
+                let _ = write!(literal, "\\{:03o}", c as u32);
+            }
+            _ => literal.push('?'),
+        }
+    }
+    literal.push(')');
+    literal
+}
+
+// Writes the lines onto as many A4 pages as they need
+fn pdf_from_lines(lines: &[String]) -> Vec<u8> {
+    let pages: Vec<&[String]> = if lines.is_empty() {
+        vec![&[]]
+    } else {
+        lines.chunks(LINES_PER_PAGE).collect()
+    };
+
+    // Objects 1-3 are the catalog, page tree and font, followed by each page and its content
+    let page_ids: Vec<usize> = (0..pages.len()).map(|page| 4 + 2 * page).collect();
+    let mut objects = vec![
+        "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+        format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            page_ids
+                .iter()
+                .map(|id| format!("{id} 0 R"))
+                .collect::<Vec<_>>()
+                .join(" "),
+            pages.len()
+        ),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Courier /Encoding /WinAnsiEncoding >>"
+            .to_string(),
+    ];
+    for (page, page_id) in pages.iter().zip(&page_ids) {
+        let mut content = format!(
+            "BT\n/F1 {FONT_SIZE} Tf\n{LINE_HEIGHT} TL\n{MARGIN} {} Td\n",
+            PAGE_HEIGHT - MARGIN - FONT_SIZE
+        );
+        for line in page.iter() {
+            content.push_str(&pdf_string(line));
+            content.push_str(" Tj T*\n");
+        }
+        content.push_str("ET");
+        objects.push(format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {PAGE_WIDTH} {PAGE_HEIGHT}] \
+             /Resources << /Font << /F1 3 0 R >> >> /Contents {} 0 R >>",
+            page_id + 1
+        ));
+        objects.push(format!(
+            "<< /Length {} >>\nstream\n{content}\nendstream",
+            content.len()
+        ));
+    }
+
+    let mut pdf = String::from("%PDF-1.4\n");
+    let mut offsets = Vec::with_capacity(objects.len());
+    for (index, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        let _ = write!(pdf, "{} 0 obj\n{object}\nendobj\n", index + 1);
+    }
+    let xref = pdf.len();
+    let _ = write!(pdf, "xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+    for offset in offsets {
+        let _ = writeln!(pdf, "{offset:010} 00000 n ");
+    }
+    let _ = write!(
+        pdf,
+        "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{xref}\n%%EOF\n",
+        objects.len() + 1
+    );
+    pdf.into_bytes()
+}
+
+pub fn render_pdf(document: &BillDocument, kind: DocumentKind) -> Vec<u8> {
+    pdf_from_lines(&text_lines(document, kind))
+}
@@ -0,0 +1,479 @@
+use crate::billing::models::{
+    ChargeOutcome, GatewayRefund, InitializePayment, PayStackRequest, PaystackChargeData,
+    PaystackEvent, PaystackRefundRequest, PaystackVerifyResponse, WebhookEvent,
+};
+use crate::config::AppConfig;
+use crate::errors::AppError;
+use crate::utils::create_random_string;
+use async_trait::async_trait;
+use axum::http::HeaderMap;
+use hmac::{Hmac, Mac};
+use reqwest::{Client, StatusCode};
+use secrecy::{ExposeSecret, SecretString};
+use serde_json::Value;
+use sha2::Sha512;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+
+// A payment provider. Billing only talks to providers through this trait, so adding one
+// (Flutterwave, Stripe) means implementing it and naming it in `from_config`.
+#[async_trait]
+pub trait PaymentGateway: Debug + Send + Sync {
+    // Starts a charge and returns the URL the patient pays at
+    async fn initialize(&self, payment: InitializePayment) -> Result<String, AppError>;
+
+    async fn verify(&self, reference: &str) -> Result<ChargeOutcome, AppError>;
+
+    // Refunds `amount` minor units of a successful charge, or all of it when `amount` is None
+    async fn refund(&self, reference: &str, amount: Option<i64>)
+    -> Result<GatewayRefund, AppError>;
+
+    // Authenticates and parses a webhook delivery. Fails with Unauthorized when the request
+    // did not come from the provider, and returns None for events billing does not act on.
+    fn parse_webhook(
+        &self,
+        headers: &HeaderMap,
+        body: &[u8],
+    ) -> Result<Option<WebhookEvent>, AppError>;
+
+    // The same provider acting for a hospital with its own account
+    fn with_secret_key(&self, secret_key: SecretString) -> Arc<dyn PaymentGateway>;
+}
+
+// Builds the gateway named by PAYMENT_GATEWAY
+pub fn from_config(config: &AppConfig) -> Result<Arc<dyn PaymentGateway>, AppError> {
+    match config.payment_gateway.as_str() {
+        "paystack" => {
+            let secret_key = config.paystack_secret_key.clone().ok_or_else(|| {
+                AppError::MissingEnvironmentVarible("PAYSTACK_SECRET_KEY".to_string())
+            })?;
+            Ok(Arc::new(PaystackGateway::new(
+                config.paystack_url.clone(),
+                secret_key,
+            )))
+        }
+        "fake" => Ok(Arc::new(FakeGateway::default())),
+        other => Err(AppError::ParsingError(format!(
+            "Unknown PAYMENT_GATEWAY '{}', expected 'paystack' or 'fake'",
+            other
+        ))),
+    }
+}
+
+#[derive(Debug)]
+pub struct PaystackGateway {
+    client: Client,
+    base_url: String,
+    secret_key: SecretString,
+}
+
+impl PaystackGateway {
+    pub fn new(base_url: String, secret_key: String) -> Self {
+        Self {
+            client: Client::new(),
+            base_url,
+            secret_key: SecretString::from(secret_key),
+        }
+    }
+
+    fn bearer(&self) -> String {
+        format!("Bearer {}", self.secret_key.expose_secret())
+    }
+
+    // Paystack signs the raw request body with HMAC-SHA512 keyed by the secret key, hex encoded
+    pub fn verify_signature(&self, body: &[u8], signature: &str) -> bool {
+        let Ok(signature) = hex::decode(signature.trim()) else {
+            return false;
+        };
+        let Ok(mut mac) =
+            Hmac::<Sha512>::new_from_slice(self.secret_key.expose_secret().as_bytes())
+        else {
+            return false;
+        };
+        mac.update(body);
+        mac.verify_slice(&signature).is_ok()
+    }
+}
+
+async fn paystack_error(response: reqwest::Response) -> AppError {
+    let error_message = response
+        .text()
+        .await
+        .unwrap_or_else(|_| "Unknown error".to_string());
+    AppError::InternalServerError(format!("Paystack API returned an error: {}", error_message))
+}
+
+fn send_error(e: reqwest::Error) -> AppError {
+    AppError::InternalServerError(format!("Failed to send request to Paystack: {}", e))
+}
+
+#[async_trait]
+impl PaymentGateway for PaystackGateway {
+    async fn initialize(&self, payment: InitializePayment) -> Result<String, AppError> {
+        let paystack_request = PayStackRequest {
+            email: payment.email,
+            amount: payment.amount,
+            currency: payment.currency,
+            reference: payment.reference,
+            callback_url: payment.callback_url,
+            subaccount: payment.subaccount,
+        };
+        let response = self
+            .client
+            .post(format!("{}/transaction/initialize", &self.base_url))
+            .header("Authorization", self.bearer())
+            .json(&paystack_request)
+            .send()
+            .await
+            .map_err(send_error)?;
+        if !response.status().is_success() {
+            return Err(paystack_error(response).await);
+        }
+
+        let response_json: Value = response.json().await.map_err(|e| {
+            AppError::ParsingError(format!("Failed to parse Paystack response: {}", e))
+        })?;
+        let authorization_url = response_json["data"]["authorization_url"]
+            .as_str()
+            .ok_or_else(|| {
+                AppError::ParsingError(
+                    "Missing 'authorization_url' in Paystack response".to_string(),
+                )
+            })?
+            .to_string();
+        Ok(authorization_url)
+    }
+
+    async fn verify(&self, reference: &str) -> Result<ChargeOutcome, AppError> {
+        let response = self
+            .client
+            .get(format!(
+                "{}/transaction/verify/{}",
+                &self.base_url, reference
+            ))
+            .header("Authorization", self.bearer())
+            .send()
+            .await
+            .map_err(send_error)?;
+        if matches!(
+            response.status(),
+            StatusCode::NOT_FOUND | StatusCode::BAD_REQUEST
+        ) {
+            // Paystack answers this way for references it has never seen
+            return Ok(ChargeOutcome::Missing);
+        }
+        if !response.status().is_success() {
+            return Err(paystack_error(response).await);
+        }
+
+        let transaction = response
+            .json::<PaystackVerifyResponse>()
+            .await
+            .map_err(|e| {
+                AppError::ParsingError(format!("Failed to parse Paystack response: {}", e))
+            })?
+            .data;
+        Ok(match transaction.status.as_str() {
+            "success" => ChargeOutcome::Succeeded {
+                amount: transaction.amount,
+                currency: transaction.currency,
+            },
+            "failed" | "reversed" => ChargeOutcome::Failed(transaction.gateway_response),
+            "abandoned" => ChargeOutcome::Abandoned,
+            _ => ChargeOutcome::Pending,
+        })
+    }
+
+    async fn refund(
+        &self,
+        reference: &str,
+        amount: Option<i64>,
+    ) -> Result<GatewayRefund, AppError> {
+        let response = self
+            .client
+            .post(format!("{}/refund", &self.base_url))
+            .header("Authorization", self.bearer())
+            .json(&PaystackRefundRequest {
+                transaction: reference.to_string(),
+                amount,
+            })
+            .send()
+            .await
+            .map_err(send_error)?;
+        if !response.status().is_success() {
+            return Err(paystack_error(response).await);
+        }
+
+        let response_json: Value = response.json().await.map_err(|e| {
+            AppError::ParsingError(format!("Failed to parse Paystack response: {}", e))
+        })?;
+        let data = &response_json["data"];
+        Ok(GatewayRefund {
+            id: match &data["id"] {
+                Value::String(id) => id.clone(),
+                id => id.to_string(),
+            },
+            status: data["status"].as_str().unwrap_or("pending").to_string(),
+        })
+    }
+
+    fn parse_webhook(
+        &self,
+        headers: &HeaderMap,
+        body: &[u8],
+    ) -> Result<Option<WebhookEvent>, AppError> {
+        let signature = headers
+            .get("x-paystack-signature")
+            .and_then(|value| value.to_str().ok())
+            .ok_or_else(|| {
+                AppError::Unauthorized("Missing x-paystack-signature header".to_string())
+            })?;
+        if !self.verify_signature(body, signature) {
+            return Err(AppError::Unauthorized(
+                "Invalid Paystack signature".to_string(),
+            ));
+        }
+        parse_paystack_event(body)
+    }
+
+    fn with_secret_key(&self, secret_key: SecretString) -> Arc<dyn PaymentGateway> {
+        Arc::new(Self {
+            client: self.client.clone(),
+            base_url: self.base_url.clone(),
+            secret_key,
+        })
+    }
+}
+
+// Only charge results are acted on. Every other event (transfers, subscriptions, disputes) is
+// acknowledged without reading its data, so Paystack stops redelivering it.
+fn parse_paystack_event(body: &[u8]) -> Result<Option<WebhookEvent>, AppError> {
+    let payload: Value =
+        serde_json::from_slice(body).map_err(|e| AppError::ParsingError(e.to_string()))?;
+    let event = serde_json::from_value::<PaystackEvent>(payload.clone())
+        .map_err(|e| AppError::ParsingError(e.to_string()))?;
+    if !matches!(event.event.as_str(), "charge.success" | "charge.failed") {
+        return Ok(None);
+    }
+    let data = serde_json::from_value::<PaystackChargeData>(event.data)
+        .map_err(|e| AppError::ParsingError(e.to_string()))?;
+    let outcome = match event.event.as_str() {
+        "charge.success" => ChargeOutcome::Succeeded {
+            amount: data.amount,
+            currency: data.currency,
+        },
+        _ => ChargeOutcome::Failed(data.gateway_response),
+    };
+    Ok(Some(WebhookEvent {
+        event: event.event,
+        reference: data.reference,
+        outcome,
+        payload,
+    }))
+}
+
+// In-process stand-in for staging and integration tests; nothing leaves the process. Every
+// initialized charge succeeds, except for payer emails starting with "fail", which are declined.
+#[derive(Debug, Default, Clone)]
+pub struct FakeGateway {
+    charges: Arc<Mutex<HashMap<String, FakeCharge>>>, // Shared by every hospital's copy
+}
+
+#[derive(Debug)]
+struct FakeCharge {
+    amount: i64,
+    currency: String,
+    declined: bool,
+    refunded: i64,
+}
+
+#[async_trait]
+impl PaymentGateway for FakeGateway {
+    async fn initialize(&self, payment: InitializePayment) -> Result<String, AppError> {
+        let url = format!(
+            "{}?reference={}",
+            payment
+                .callback_url
+                .as_deref()
+                .unwrap_or("https://checkout.fake.gateway"),
+            payment.reference
+        );
+        self.charges.lock().unwrap().insert(
+            payment.reference,
+            FakeCharge {
+                amount: payment.amount,
+                currency: payment.currency,
+                declined: payment.email.starts_with("fail"),
+                refunded: 0,
+            },
+        );
+        Ok(url)
+    }
+
+    async fn verify(&self, reference: &str) -> Result<ChargeOutcome, AppError> {
+        Ok(match self.charges.lock().unwrap().get(reference) {
+            None => ChargeOutcome::Missing,
+            Some(charge) if charge.declined => {
+                ChargeOutcome::Failed(Some("Declined by fake gateway".to_string()))
+            }
+            Some(charge) => ChargeOutcome::Succeeded {
+                amount: charge.amount,
+                currency: charge.currency.clone(),
+            },
+        })
+    }
+
+    async fn refund(
+        &self,
+        reference: &str,
+        amount: Option<i64>,
+    ) -> Result<GatewayRefund, AppError> {
+        let mut charges = self.charges.lock().unwrap();
+        let charge = charges
+            .get_mut(reference)
+            .filter(|charge| !charge.declined)
+            .ok_or_else(|| {
+                AppError::NotFound(format!("No successful charge with reference {}", reference))
+            })?;
+        let amount = amount.unwrap_or(charge.amount - charge.refunded);
+        if amount <= 0 || charge.refunded + amount > charge.amount {
+            return Err(AppError::UnProcessableEntity {
+                field: "amount".to_string(),
+                message: "Refund exceeds the amount charged".to_string(),
+            });
+        }
+        charge.refunded += amount;
+        Ok(GatewayRefund {
+            id: format!("fake_{}", create_random_string(12)),
+            status: "processed".to_string(),
+        })
+    }
+
+    // Accepts Paystack-shaped payloads without a signature
+    fn parse_webhook(
+        &self,
+        _headers: &HeaderMap,
+        body: &[u8],
+    ) -> Result<Option<WebhookEvent>, AppError> {
+        parse_paystack_event(body)
+    }
+
+    fn with_secret_key(&self, _secret_key: SecretString) -> Arc<dyn PaymentGateway> {
+        Arc::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET_KEY: &str = "sk_test_webhook";
+
+    // Recorded from Paystack's test mode, trimmed to the fields that matter
+    const CHARGE_SUCCESS: &str = r#"{
+        "event": "charge.success",
+        "data": {
+            "id": 302961,
+            "domain": "test",
+            "status": "success",
+            "reference": "qTPrJoy9Bx",
+            "amount": 1500000,
+            "message": null,
+            "gateway_response": "Approved by Financial Institution",
+            "paid_at": "2026-05-05T13:24:45.000Z",
+            "channel": "card",
+            "currency": "NGN",
+            "customer": { "id": 68324, "email": "patient@example.com" }
+        }
+    }"#;
+
+    const TRANSFER_SUCCESS: &str = r#"{
+        "event": "transfer.success",
+        "data": {
+            "amount": 30000,
+            "currency": "NGN",
+            "domain": "test",
+            "integration": { "id": 463433, "business_name": "General Hospital" },
+            "reason": "Payout",
+            "recipient": { "recipient_code": "RCP_2x5j67tnnw1t98k" },
+            "status": "success",
+            "transfer_code": "TRF_wpl1dem4967avzm"
+        }
+    }"#;
+
+    fn sign(body: &[u8]) -> String {
+        let mut mac = Hmac::<Sha512>::new_from_slice(SECRET_KEY.as_bytes()).unwrap();
+        mac.update(body);
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    fn gateway() -> PaystackGateway {
+        PaystackGateway::new(
+            "https://api.paystack.co".to_string(),
+            SECRET_KEY.to_string(),
+        )
+    }
+
+    fn signed_headers(signature: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-paystack-signature", signature.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn accepts_a_valid_signature() {
+        let body = CHARGE_SUCCESS.as_bytes();
+        assert!(gateway().verify_signature(body, &sign(body)));
+    }
+
+    #[test]
+    fn rejects_a_bad_signature() {
+        let body = CHARGE_SUCCESS.as_bytes();
+        let tampered = sign(b"{\"event\":\"charge.success\"}");
+        assert!(!gateway().verify_signature(body, &tampered));
+        assert!(!gateway().verify_signature(body, "not-hex"));
+        assert!(matches!(
+            gateway().parse_webhook(&signed_headers(&tampered), body),
+            Err(AppError::Unauthorized(_))
+        ));
+        assert!(matches!(
+            gateway().parse_webhook(&HeaderMap::new(), body),
+            Err(AppError::Unauthorized(_))
+        ));
+    }
+
+    #[test]
+    fn parses_a_charge_success_event() {
+        let body = CHARGE_SUCCESS.as_bytes();
+        let event = gateway()
+            .parse_webhook(&signed_headers(&sign(body)), body)
+            .unwrap()
+            .expect("charge events are acted on");
+        assert_eq!(event.event, "charge.success");
+        assert_eq!(event.reference, "qTPrJoy9Bx");
+        assert!(matches!(
+            event.outcome,
+            ChargeOutcome::Succeeded { amount: 1500000, ref currency } if currency == "NGN"
+        ));
+        assert_eq!(event.payload["data"]["channel"], "card");
+    }
+
+    #[test]
+    fn acknowledges_events_other_than_charges() {
+        let body = TRANSFER_SUCCESS.as_bytes();
+        let event = gateway()
+            .parse_webhook(&signed_headers(&sign(body)), body)
+            .unwrap();
+        assert!(event.is_none());
+    }
+
+    #[test]
+    fn rejects_a_charge_event_without_a_reference() {
+        let body = br#"{"event":"charge.success","data":{"amount":100,"currency":"NGN"}}"#;
+        assert!(matches!(
+            parse_paystack_event(body),
+            Err(AppError::ParsingError(_))
+        ));
+    }
+}
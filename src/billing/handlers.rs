@@ -1,15 +1,39 @@
 use crate::app_state::SharedState;
-use crate::billing::models::{CreateBillRequest, PayBillRequest};
-use crate::billing::service::{issue_bill, pay_bill};
+use crate::auth::headers::ClaimsHeader;
+use crate::auth::permissions::{
+    ensure_appointment_access, ensure_bill_access, ensure_patient_access, ensure_payment_access,
+};
+use crate::billing::documents::{render_html, render_pdf};
+use crate::billing::models::{
+    CreateBillRequest, CreateRefundRequest, DocumentFormat, DocumentKind, PayBillRequest,
+    RecordPaymentRequest,
+};
+use crate::billing::service::{
+    cancel_bill, get_account_statement, get_bill_document, get_invoice, get_refunds,
+    handle_payment_webhook, issue_bill, pay_bill, record_offline_payment, refund_bill,
+    verify_payment,
+};
 use crate::errors::AppError;
-use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
+use axum::{
+    Json,
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
+};
 use serde_json::json;
+use std::collections::HashMap;
+use uuid::Uuid;
 
 pub async fn issue_bill_handler(
     State(state): State<SharedState>,
+    claims: ClaimsHeader,
     Json(payload): Json<CreateBillRequest>,
 ) -> impl IntoResponse {
-    match issue_bill(state, payload).await {
+    if let Err(e) = ensure_appointment_access(&state, &claims, &payload.appointment_id).await {
+        return e.into_response();
+    }
+    match issue_bill(state, payload, claims.tenant()).await {
         Ok(bill) => (StatusCode::CREATED, Json(bill)).into_response(),
         Err(e) => match e {
             AppError::DatabaseError(_) => (
@@ -38,9 +62,13 @@ pub async fn issue_bill_handler(
 
 pub async fn pay_bill_handler(
     State(state): State<SharedState>,
+    claims: ClaimsHeader,
     Json(payload): Json<PayBillRequest>,
 ) -> impl IntoResponse {
-    match pay_bill(state, payload).await {
+    if let Err(e) = ensure_bill_access(&state, &claims, &payload.bill_id).await {
+        return e.into_response();
+    }
+    match pay_bill(state, payload, claims.tenant()).await {
         Ok(bill) => (StatusCode::OK, Json(bill)).into_response(),
         Err(e) => match e {
             AppError::DatabaseError(_) => (
@@ -58,6 +86,160 @@ pub async fn pay_bill_handler(
                 Json(json!({"error": format!("{}: {}", field, message)})),
             )
                 .into_response(),
+            AppError::Conflict(e) => {
+                (StatusCode::CONFLICT, Json(json!({"error": e}))).into_response()
+            }
+            _ => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response(),
+        },
+    }
+}
+
+// Cash, POS and bank-transfer collections, recorded against the cashier who took them
+pub async fn record_payment_handler(
+    State(state): State<SharedState>,
+    claims: ClaimsHeader,
+    Path(bill_id): Path<String>,
+    Json(payload): Json<RecordPaymentRequest>,
+) -> impl IntoResponse {
+    if let Err(e) = ensure_bill_access(&state, &claims, &bill_id).await {
+        return e.into_response();
+    }
+    match record_offline_payment(state, bill_id, payload, claims.sub, claims.tenant()).await {
+        Ok(payment) => (StatusCode::CREATED, Json(payment)).into_response(),
+        Err(e) => match e {
+            AppError::DatabaseError(_) => (
+                StatusCode::NOT_FOUND,
+                Json(json!({"error": "Bill not found"})),
+            )
+                .into_response(),
+            AppError::ParsingError(e) => (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response(),
+            AppError::UnProcessableEntity { field, message } => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(json!({"error": format!("{}: {}", field, message)})),
+            )
+                .into_response(),
+            AppError::Conflict(e) => {
+                (StatusCode::CONFLICT, Json(json!({"error": e}))).into_response()
+            }
+            _ => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response(),
+        },
+    }
+}
+
+// Paystack retries anything that is not a 2xx, so only signature and payload problems are
+// reported as client errors
+pub async fn paystack_webhook_handler(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> impl IntoResponse {
+    webhook_response(handle_payment_webhook(state, None, &headers, &body).await)
+}
+
+// Deliveries for a hospital that takes payments on its own Paystack account
+pub async fn hospital_paystack_webhook_handler(
+    State(state): State<SharedState>,
+    Path(hospital_id): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> impl IntoResponse {
+    let hospital_id = match Uuid::parse_str(&hospital_id) {
+        Ok(id) => id,
+        Err(_) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "Invalid hospital ID format"})),
+            )
+                .into_response();
+        }
+    };
+    webhook_response(handle_payment_webhook(state, Some(hospital_id), &headers, &body).await)
+}
+
+fn webhook_response(result: Result<(), AppError>) -> Response {
+    match result {
+        Ok(()) => (StatusCode::OK, Json(json!({"status": "ok"}))).into_response(),
+        Err(e) => match e {
+            AppError::Unauthorized(e) => {
+                (StatusCode::UNAUTHORIZED, Json(json!({"error": e}))).into_response()
+            }
+            AppError::ParsingError(e) => {
+                (StatusCode::BAD_REQUEST, Json(json!({"error": e}))).into_response()
+            }
+            _ => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response(),
+        },
+    }
+}
+
+// Settles a bill from Paystack's own record of the transaction, e.g. after the payment redirect
+pub async fn verify_payment_handler(
+    State(state): State<SharedState>,
+    claims: ClaimsHeader,
+    Path(reference): Path<String>,
+) -> impl IntoResponse {
+    if let Err(e) = ensure_payment_access(&state, &claims, &reference).await {
+        return e.into_response();
+    }
+    match verify_payment(state, reference, claims.tenant()).await {
+        Ok(bill) => (StatusCode::OK, Json(bill)).into_response(),
+        Err(e) => match e {
+            AppError::NotFound(e) => {
+                (StatusCode::NOT_FOUND, Json(json!({"error": e}))).into_response()
+            }
+            AppError::UnProcessableEntity { field, message } => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(json!({"error": format!("{}: {}", field, message)})),
+            )
+                .into_response(),
+            _ => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response(),
+        },
+    }
+}
+
+pub async fn cancel_bill_handler(
+    State(state): State<SharedState>,
+    claims: ClaimsHeader,
+    Path(bill_id): Path<String>,
+) -> impl IntoResponse {
+    if let Err(e) = ensure_bill_access(&state, &claims, &bill_id).await {
+        return e.into_response();
+    }
+    match cancel_bill(state, bill_id, claims.tenant()).await {
+        Ok(bill) => Json(bill).into_response(),
+        Err(e) => match e {
+            AppError::DatabaseError(_) => (
+                StatusCode::NOT_FOUND,
+                Json(json!({"error": "Bill not found"})),
+            )
+                .into_response(),
+            AppError::ParsingError(e) => (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response(),
+            AppError::Conflict(e) => {
+                (StatusCode::CONFLICT, Json(json!({"error": e}))).into_response()
+            }
             _ => (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 Json(json!({"error": e.to_string()})),
@@ -66,3 +248,260 @@ pub async fn pay_bill_handler(
         },
     }
 }
+
+pub async fn refund_bill_handler(
+    State(state): State<SharedState>,
+    claims: ClaimsHeader,
+    Path(bill_id): Path<String>,
+    Json(payload): Json<CreateRefundRequest>,
+) -> impl IntoResponse {
+    if let Err(e) = ensure_bill_access(&state, &claims, &bill_id).await {
+        return e.into_response();
+    }
+    let bill_id = match Uuid::parse_str(&bill_id) {
+        Ok(id) => id,
+        Err(_) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "Invalid bill ID format"})),
+            )
+                .into_response();
+        }
+    };
+    match refund_bill(
+        state,
+        bill_id,
+        payload.amount,
+        payload.reason,
+        Some(claims.sub),
+        claims.tenant(),
+    )
+    .await
+    {
+        Ok(refund) => (StatusCode::CREATED, Json(refund)).into_response(),
+        Err(e) => match e {
+            AppError::DatabaseError(_) => (
+                StatusCode::NOT_FOUND,
+                Json(json!({"error": "Bill not found"})),
+            )
+                .into_response(),
+            AppError::UnProcessableEntity { field, message } => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(json!({"error": format!("{}: {}", field, message)})),
+            )
+                .into_response(),
+            AppError::Conflict(e) => {
+                (StatusCode::CONFLICT, Json(json!({"error": e}))).into_response()
+            }
+            _ => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response(),
+        },
+    }
+}
+
+pub async fn get_refunds_handler(
+    State(state): State<SharedState>,
+    claims: ClaimsHeader,
+    Path(bill_id): Path<String>,
+) -> impl IntoResponse {
+    if let Err(e) = ensure_bill_access(&state, &claims, &bill_id).await {
+        return e.into_response();
+    }
+    match get_refunds(state, bill_id, claims.tenant()).await {
+        Ok(refunds) => Json(refunds).into_response(),
+        Err(e) => match e {
+            AppError::DatabaseError(_) => (
+                StatusCode::NOT_FOUND,
+                Json(json!({"error": "Bill not found"})),
+            )
+                .into_response(),
+            AppError::ParsingError(e) => (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response(),
+            _ => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response(),
+        },
+    }
+}
+
+pub async fn get_invoice_handler(
+    State(state): State<SharedState>,
+    claims: ClaimsHeader,
+    Path(bill_id): Path<String>,
+) -> impl IntoResponse {
+    if let Err(e) = ensure_bill_access(&state, &claims, &bill_id).await {
+        return e.into_response();
+    }
+    match get_invoice(state, bill_id, claims.tenant()).await {
+        Ok(invoice) => Json(invoice).into_response(),
+        Err(e) => match e {
+            AppError::DatabaseError(_) => (
+                StatusCode::NOT_FOUND,
+                Json(json!({"error": "Bill not found"})),
+            )
+                .into_response(),
+            AppError::ParsingError(e) => (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response(),
+            _ => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response(),
+        },
+    }
+}
+
+pub async fn get_invoice_document_handler(
+    State(state): State<SharedState>,
+    claims: ClaimsHeader,
+    Path(bill_id): Path<String>,
+    Query(params): Query<HashMap<String, String>>,
+) -> impl IntoResponse {
+    if let Err(e) = ensure_bill_access(&state, &claims, &bill_id).await {
+        return e.into_response();
+    }
+    bill_document_response(
+        state,
+        bill_id,
+        DocumentKind::Invoice,
+        claims.tenant(),
+        &params,
+    )
+    .await
+}
+
+pub async fn get_receipt_document_handler(
+    State(state): State<SharedState>,
+    claims: ClaimsHeader,
+    Path(bill_id): Path<String>,
+    Query(params): Query<HashMap<String, String>>,
+) -> impl IntoResponse {
+    if let Err(e) = ensure_bill_access(&state, &claims, &bill_id).await {
+        return e.into_response();
+    }
+    bill_document_response(
+        state,
+        bill_id,
+        DocumentKind::Receipt,
+        claims.tenant(),
+        &params,
+    )
+    .await
+}
+
+// Renders a bill as `?format=html` (the default) or `?format=pdf`
+async fn bill_document_response(
+    state: SharedState,
+    bill_id: String,
+    kind: DocumentKind,
+    hospital_id: Option<Uuid>,
+    params: &HashMap<String, String>,
+) -> Response {
+    let format = match params.get("format").map(String::as_str) {
+        None | Some("html") => DocumentFormat::Html,
+        Some("pdf") => DocumentFormat::Pdf,
+        Some(other) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": format!("Unsupported format: {}", other)})),
+            )
+                .into_response();
+        }
+    };
+
+    match get_bill_document(state, bill_id, kind, hospital_id).await {
+        Ok(document) => {
+            let name = match kind {
+                DocumentKind::Invoice => "invoice",
+                DocumentKind::Receipt => "receipt",
+            };
+            let reference = &document.invoice.bill.reference;
+            match format {
+                DocumentFormat::Html => (
+                    [(header::CONTENT_TYPE, "text/html; charset=utf-8")],
+                    render_html(&document, kind),
+                )
+                    .into_response(),
+                DocumentFormat::Pdf => (
+                    [
+                        (header::CONTENT_TYPE, "application/pdf".to_string()),
+                        (
+                            header::CONTENT_DISPOSITION,
+                            format!("inline; filename=\"{}-{}.pdf\"", name, reference),
+                        ),
+                    ],
+                    render_pdf(&document, kind),
+                )
+                    .into_response(),
+            }
+        }
+        Err(e) => match e {
+            AppError::DatabaseError(_) => (
+                StatusCode::NOT_FOUND,
+                Json(json!({"error": "Bill not found"})),
+            )
+                .into_response(),
+            AppError::ParsingError(e) => (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response(),
+            AppError::Conflict(e) => {
+                (StatusCode::CONFLICT, Json(json!({"error": e}))).into_response()
+            }
+            _ => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response(),
+        },
+    }
+}
+
+pub async fn get_account_statement_handler(
+    State(state): State<SharedState>,
+    claims: ClaimsHeader,
+    Query(params): Query<HashMap<String, String>>,
+) -> impl IntoResponse {
+    let patient_id = match params.get("patient_id").map(|s| Uuid::parse_str(s)) {
+        Some(Ok(id)) => id,
+        Some(Err(e)) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": format!("Invalid patient_id: {}", e)})),
+            )
+                .into_response();
+        }
+        None => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "Missing 'patient_id' query parameter"})),
+            )
+                .into_response();
+        }
+    };
+    if let Err(e) = ensure_patient_access(&state, &claims, patient_id).await {
+        return e.into_response();
+    }
+    let currency = params.get("currency").cloned();
+
+    match get_account_statement(state, patient_id, currency, claims.tenant()).await {
+        Ok(statement) => Json(statement).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
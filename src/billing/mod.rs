@@ -1,3 +1,5 @@
+pub mod documents;
+pub mod gateway;
 pub mod handlers;
 pub mod models;
 pub mod router;
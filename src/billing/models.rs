@@ -1,7 +1,14 @@
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use sqlx::{FromRow, Type};
 use uuid::Uuid;
 
+use crate::admin::models::Hospital;
+use crate::appointments::models::Appointment;
+use crate::money::Money;
+use crate::patient::models::Patient;
 use crate::utils::create_random_string;
 
 #[derive(Serialize, FromRow)]
@@ -9,55 +16,347 @@ pub struct Bill {
     pub id: Uuid,
     pub reference: String, // Unique reference for the bill, can be generated using a utility function
     pub appointment_id: Uuid,
-    pub amount: f64,
+    pub hospital_id: Option<Uuid>, // The appointment's hospital
+    pub amount: Money,             // What is charged: subtotal - discount + tax
+    pub subtotal: Money,           // Line items before discounts and tax
+    pub discount: Money,
+    pub tax: Money,
+    pub insurer_amount: Money, // The HMO's share; the patient pays the rest
+    pub policy_id: Option<Uuid>,
+    pub amount_paid: Money, // Sum of successful payments
     pub currency: String,   // e.g., "USD", "NGN"
     pub status: BillStatus, // e.g., "pending", "paid", "cancelled"
+    pub paid_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
 }
 
-#[derive(Serialize, Deserialize, Type)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
 #[sqlx(type_name = "VARCHAR", rename_all = "PascalCase")]
 pub enum BillStatus {
     Pending,
+    PartiallyPaid,
     Paid,
     Cancelled,
+    PartiallyRefunded,
+    Refunded,
+}
+
+impl BillStatus {
+    // Statuses in which the bill still accepts payments
+    pub fn is_payable(self) -> bool {
+        matches!(self, Self::Pending | Self::PartiallyPaid)
+    }
+}
+
+#[derive(Serialize, FromRow)]
+pub struct Payment {
+    pub id: Uuid,
+    pub bill_id: Uuid,
+    pub reference: String, // Unique per payment; online payments send it to the gateway
+    pub amount: Money,
+    pub currency: String,
+    pub method: PaymentMethod,
+    pub status: PaymentStatus,
+    pub refunded: Money,
+    pub receipt_number: Option<String>, // Offline payments only
+    pub collected_by: Option<Uuid>,     // The cashier who recorded an offline payment
+    pub paid_at: Option<DateTime<Utc>>,
+    pub last_verified_at: Option<DateTime<Utc>>, // Last time the gateway was asked about it
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[sqlx(type_name = "VARCHAR", rename_all = "PascalCase")]
+pub enum PaymentMethod {
+    Online, // Through the payment gateway
+    Cash,
+    Pos,
+    BankTransfer,
+}
+
+#[derive(Deserialize)]
+pub struct RecordPaymentRequest {
+    pub method: PaymentMethod, // Cash, Pos or BankTransfer
+    pub amount: Option<Money>, // Defaults to the outstanding balance
+    pub receipt_number: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[sqlx(type_name = "VARCHAR", rename_all = "PascalCase")]
+pub enum PaymentStatus {
+    Pending,
+    Succeeded,
+    Failed,
+    Expired, // Not completed at the gateway in time; still settled if the charge goes through
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Type)]
+#[sqlx(type_name = "VARCHAR", rename_all = "PascalCase")]
+pub enum ItemKind {
+    Consultation, // The appointment itself, priced from the catalog
+    LabTest,
+    Drug,
+    BedDay,
+    Procedure,
+    Other,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct BillItem {
+    pub id: Uuid,
+    pub bill_id: Uuid,
+    pub order_id: Option<Uuid>, // The appointment order this line bills, if any
+    pub kind: ItemKind,
+    pub description: String,
+    pub quantity: i32,
+    pub unit_price: Money,
+    pub discount: Money,
+    pub tax: Money,
+    pub total: Money,
+}
+
+// A bill with its line items and payments
+#[derive(Serialize)]
+pub struct Invoice {
+    #[serde(flatten)]
+    pub bill: Bill,
+    pub outstanding: Money,
+    pub items: Vec<BillItem>,
+    pub payments: Vec<Payment>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocumentKind {
+    Invoice,
+    Receipt, // Only once something has been paid
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocumentFormat {
+    Html,
+    Pdf,
+}
+
+// Everything printed on an invoice or receipt
+pub struct BillDocument {
+    pub hospital: Option<Hospital>, // The doctor's hospital, else the patient's
+    pub patient: Patient,
+    pub doctor_name: String,
+    pub appointment: Appointment,
+    pub invoice: Invoice,
+}
+
+// Refund statuses of our own. Once the gateway takes a refund its status is whatever it reports
+pub const REFUND_PENDING: &str = "pending"; // Recorded, not yet sent to the gateway
+pub const REFUND_FAILED: &str = "failed"; // The gateway refused it; nothing was paid back
+pub const REFUND_MANUAL: &str = "manual"; // Paid back at the desk
+
+#[derive(Serialize, FromRow)]
+pub struct Refund {
+    pub id: Uuid,
+    pub bill_id: Uuid,
+    pub payment_id: Option<Uuid>, // The payment the money went back through
+    pub amount: Money,
+    pub reason: Option<String>,
+    pub provider_refund_id: Option<String>, // None for offline payments, returned at the desk
+    pub status: String,
+    pub requested_by: Option<Uuid>, // None for automatic refunds
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateRefundRequest {
+    pub amount: Option<Money>, // Defaults to everything not yet refunded
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct RefundList {
+    pub refunds: Vec<Refund>,
 }
 
 #[derive(Deserialize)]
 pub struct CreateBillRequest {
     pub appointment_id: String,
-    pub amount: Option<f64>, // Optional, can be calculated based on appointment details
+    pub discount_percent: Option<Decimal>, // Taken off every line, e.g. 10 for 10%
     pub currency: Option<String>, // Optional, default to a specific currency if not provided
+    pub policy_id: Option<Uuid>,  // Defaults to the patient's active insurance policy, if any
+    pub self_pay: Option<bool>,   // Bill the patient in full, ignoring insurance
 }
 
 #[derive(Deserialize)]
 pub struct PayBillRequest {
     pub bill_id: String,
     pub email: String,
+    pub amount: Option<Money>, // An instalment; defaults to the outstanding balance
 }
 
 #[derive(Serialize)]
 pub struct PayStackRequest {
     pub email: String,
-    pub amount: u64,
+    pub amount: i64, // In the currency's minor unit, e.g. kobo
+    pub currency: String,
+    pub reference: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub callback_url: Option<String>, // The hospital's; Paystack falls back to the dashboard's
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subaccount: Option<String>, // The hospital's Paystack subaccount to settle into
+}
+
+#[derive(Serialize)]
+pub struct PaystackRefundRequest {
+    pub transaction: String, // Reference of the charge being refunded
+    pub amount: Option<i64>, // Partial refund in minor units; the whole charge when absent
+}
+
+// Body of a Paystack webhook. `data` differs per event (transfers, subscriptions), so it is
+// only read into `PaystackChargeData` for charge events; the raw payload is stored.
+#[derive(Deserialize)]
+pub struct PaystackEvent {
+    pub event: String, // e.g. "charge.success", "transfer.success"
+    pub data: Value,
+}
+
+#[derive(Deserialize)]
+pub struct PaystackChargeData {
+    pub reference: String,
+    pub amount: i64, // Minor units
+    pub currency: String,
+    pub gateway_response: Option<String>,
+}
+
+// A charge for the gateway to start; the patient is sent to the returned authorization URL
+pub struct InitializePayment {
+    pub email: String,
+    pub amount: i64, // Minor units
+    pub currency: String,
+    pub reference: String,
+    pub callback_url: Option<String>, // The hospital's; the gateway's default when unset
+    pub subaccount: Option<String>,   // Settles into the hospital's Paystack subaccount
+}
+
+// A verified webhook, reduced to what billing acts on
+pub struct WebhookEvent {
+    pub event: String,
     pub reference: String,
-    pub callback_url: String,
+    pub outcome: ChargeOutcome,
+    pub payload: Value, // Stored as received
+}
+
+pub struct GatewayRefund {
+    pub id: String,     // The provider's refund id
+    pub status: String, // As reported by the provider, e.g. "pending", "processed"
+}
+
+// What the payment gateway reports for a transaction reference
+pub enum ChargeOutcome {
+    Succeeded { amount: i64, currency: String },
+    Failed(Option<String>),
+    Abandoned, // Started but never completed
+    Missing,   // Never initialized with Paystack
+    Pending,
+}
+
+// Response from Paystack's /transaction/verify/{reference}
+#[derive(Deserialize)]
+pub struct PaystackVerifyResponse {
+    pub data: PaystackTransaction,
+}
+
+#[derive(Deserialize)]
+pub struct PaystackTransaction {
+    pub status: String, // "success", "failed", "abandoned", "ongoing", ...
+    pub amount: i64,
+    pub currency: String,
+    pub gateway_response: Option<String>,
 }
 
 #[derive(Serialize)]
 pub struct AuthorizationResponse {
     pub authorization_url: String,
+    pub reference: String, // The payment's reference, for verification
+    pub payment_id: Uuid,
+    pub amount: Money,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum StatementEntryKind {
+    Bill,
+    Payment,
+    Refund,
+    WriteOff, // Unpaid balance dropped when a bill was closed by a refund
+}
+
+// One line of a patient's statement. Bills raise the balance and payments and write-offs lower
+// it. A refund returns money and reduces the charge by the same amount, so it leaves the
+// balance unchanged.
+#[derive(Serialize)]
+pub struct StatementEntry {
+    pub date: DateTime<Utc>,
+    pub kind: StatementEntryKind,
+    pub bill_id: Uuid,
     pub reference: String,
+    pub amount: Money,
+    pub balance: Money, // Running balance owed after this entry
+}
+
+#[derive(Serialize)]
+pub struct AccountStatement {
+    pub patient_id: Uuid,
+    pub currency: String,
+    pub total_billed: Money,
+    pub total_paid: Money,
+    pub total_refunded: Money,
+    pub balance: Money,
+    pub entries: Vec<StatementEntry>,
 }
 
 impl Bill {
-    pub fn new(appointment_id: Uuid, amount: f64, currency: Option<String>) -> Self {
+    pub fn new(appointment: &Appointment, currency: String) -> Self {
         Self {
             id: Uuid::new_v4(),
             reference: create_random_string(10), // Generate a unique reference
-            appointment_id,
-            amount,
-            currency: currency.unwrap_or_else(|| "NGN".to_string()), // Default to "NGN" if not provided
+            appointment_id: appointment.id,
+            hospital_id: appointment.hospital_id,
+            amount: Money::ZERO,
+            subtotal: Money::ZERO,
+            discount: Money::ZERO,
+            tax: Money::ZERO,
+            insurer_amount: Money::ZERO,
+            policy_id: None,
+            amount_paid: Money::ZERO,
+            currency,
             status: BillStatus::Pending,
+            paid_at: None,
+            created_at: Utc::now(),
+        }
+    }
+
+    // The part of the bill the patient pays
+    pub fn patient_amount(&self) -> Money {
+        self.amount - self.insurer_amount
+    }
+
+    // What the patient still owes; nothing once the bill is paid, cancelled or refunded
+    pub fn outstanding(&self) -> Money {
+        if self.status.is_payable() {
+            self.patient_amount() - self.amount_paid
+        } else {
+            Money::ZERO
         }
     }
+
+    // Totals the bill from its line items
+    pub fn set_totals(&mut self, items: &[BillItem]) {
+        self.subtotal = items.iter().map(BillItem::gross).sum();
+        self.discount = items.iter().map(|item| item.discount).sum();
+        self.tax = items.iter().map(|item| item.tax).sum();
+        self.amount = items.iter().map(|item| item.total).sum();
+    }
+}
+
+impl BillItem {
+    pub fn gross(&self) -> Money {
+        self.unit_price.times(self.quantity)
+    }
 }
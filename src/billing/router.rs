@@ -1,12 +1,69 @@
 use crate::app_state::{AppState, SharedState};
-use crate::billing::handlers::{issue_bill_handler, pay_bill_handler};
+use crate::auth::permissions::{Permission, require};
+use crate::billing::handlers::{
+    cancel_bill_handler, get_account_statement_handler, get_invoice_document_handler,
+    get_invoice_handler, get_receipt_document_handler, get_refunds_handler,
+    hospital_paystack_webhook_handler, issue_bill_handler, pay_bill_handler,
+    paystack_webhook_handler, record_payment_handler, refund_bill_handler, verify_payment_handler,
+};
+use crate::idempotency::idempotent;
 use axum::Router;
-use axum::routing::post;
+use axum::routing::{get, post};
 use std::sync::Arc;
 
 pub fn billing_router(state: SharedState) -> Router<Arc<AppState>> {
     Router::new()
-        .route("/issue", post(issue_bill_handler))
-        .route("/pay", post(pay_bill_handler))
+        // Called by Paystack, which signs its deliveries instead of sending a token
+        .route("/webhook/paystack", post(paystack_webhook_handler))
+        .route(
+            "/webhook/paystack/{hospital_id}",
+            post(hospital_paystack_webhook_handler),
+        )
+        .merge(require(
+            &state,
+            Permission::ViewBills,
+            Router::new()
+                .route("/statement", get(get_account_statement_handler))
+                .route("/{id}", get(get_invoice_handler))
+                .route("/{id}/invoice", get(get_invoice_document_handler))
+                .route("/{id}/receipt", get(get_receipt_document_handler))
+                .route("/{id}/refunds", get(get_refunds_handler)),
+        ))
+        .merge(require(
+            &state,
+            Permission::IssueBills,
+            Router::new()
+                .route("/{id}/cancel", post(cancel_bill_handler))
+                .merge(idempotent(
+                    &state,
+                    Router::new().route("/issue", post(issue_bill_handler)),
+                )),
+        ))
+        .merge(require(
+            &state,
+            Permission::PayBills,
+            Router::new()
+                .route("/verify/{reference}", get(verify_payment_handler))
+                .merge(idempotent(
+                    &state,
+                    Router::new().route("/pay", post(pay_bill_handler)),
+                )),
+        ))
+        .merge(require(
+            &state,
+            Permission::CollectPayments,
+            idempotent(
+                &state,
+                Router::new().route("/{id}/payments", post(record_payment_handler)),
+            ),
+        ))
+        .merge(require(
+            &state,
+            Permission::RefundBills,
+            idempotent(
+                &state,
+                Router::new().route("/{id}/refunds", post(refund_bill_handler)),
+            ),
+        ))
         .with_state(state)
 }
@@ -1,109 +1,1982 @@
+use crate::admin::models::{Hospital, PaymentSettings};
+use crate::admin::service::{payment_settings_for_appointment, payment_settings_for_hospital};
 use crate::app_state::SharedState;
+use crate::appointments::models::{Appointment, AppointmentOrder, VisitType};
 use crate::appointments::service::get_appointment_by_id;
+use crate::billing::gateway::PaymentGateway;
 use crate::billing::models::{
-    AuthorizationResponse, Bill, BillStatus, CreateBillRequest, PayBillRequest, PayStackRequest,
+    AccountStatement, AuthorizationResponse, Bill, BillDocument, BillItem, BillStatus,
+    ChargeOutcome, CreateBillRequest, DocumentKind, InitializePayment, Invoice, ItemKind,
+    PayBillRequest, Payment, PaymentMethod, PaymentStatus, REFUND_FAILED, REFUND_MANUAL,
+    REFUND_PENDING, RecordPaymentRequest, Refund, RefundList, StatementEntry, StatementEntryKind,
+    WebhookEvent,
 };
-use crate::{errors::AppError, utils::get_paystack_config};
-use reqwest::Client;
-use serde_json::Value;
+use crate::config::{DEFAULT_CURRENCY, REFUND_CUTOFF_HOURS, VAT_PERCENT};
+use crate::errors::AppError;
+use crate::insurance::models::ClaimStatus;
+use crate::insurance::service::{cover_bill, open_claim};
+use crate::money::Money;
+use crate::patient::models::Patient;
+use crate::utils::create_random_string;
+use axum::http::HeaderMap;
+use chrono::{Duration as ChronoDuration, Utc};
+use rust_decimal::Decimal;
+use secrecy::SecretString;
+use sqlx::PgConnection;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
 use uuid::Uuid;
 
-pub async fn issue_bill(state: SharedState, payload: CreateBillRequest) -> Result<Bill, AppError> {
+// Builds an itemised bill for an appointment: the visit itself, unless an earlier bill already
+// charges for it, plus every order not billed yet. The orders are claimed in the same
+// transaction, so no two bills charge for the same order.
+pub async fn issue_bill(
+    state: SharedState,
+    payload: CreateBillRequest,
+    hospital_id: Option<Uuid>,
+) -> Result<Invoice, AppError> {
     let app_id = Uuid::parse_str(&payload.appointment_id)
         .map_err(|e| AppError::ParsingError(e.to_string()))?;
-    match get_appointment_by_id(state.clone(), payload.appointment_id.clone()).await {
-        Ok(appointment) => {
-            let amount = payload.amount.unwrap_or(appointment.price);
-            let bill = Bill::new(app_id, amount, payload.currency);
-            sqlx::query("INSERT INTO bills (id, appointment_id, amount, currency, status) VALUES ($1, $2, $3, $4, $5)")
-                    .bind(&bill.id)
-                    .bind(&bill.appointment_id)
-                    .bind(&bill.amount)
-                    .bind(&bill.currency)
-                    .bind(&bill.status)
-                    .execute(&state.db_pool)
-                    .await
-                    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
-            Ok(bill)
-        }
-        Err(e) => {
-            return Err(AppError::DatabaseError(format!(
-                "Appointment not found: {}",
-                e.to_string()
-            )));
-        }
+    let appointment =
+        get_appointment_by_id(state.clone(), payload.appointment_id.clone(), hospital_id)
+            .await
+            .map_err(|e| AppError::DatabaseError(format!("Appointment not found: {}", e)))?;
+    let discount_percent = payload.discount_percent.unwrap_or(Decimal::ZERO);
+    if discount_percent < Decimal::ZERO || discount_percent > Decimal::ONE_HUNDRED {
+        return Err(AppError::UnProcessableEntity {
+            field: "discount_percent".to_string(),
+            message: "Discount must be between 0 and 100 percent".to_string(),
+        });
+    }
+    let currency = match payload.currency {
+        Some(currency) => currency,
+        None => payment_settings_for_appointment(&state, app_id)
+            .await?
+            .map(|settings| settings.default_currency)
+            .unwrap_or_else(|| DEFAULT_CURRENCY.to_string()),
+    };
+    let mut bill = Bill::new(&appointment, currency);
+
+    let mut tx = state
+        .db_pool
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    // Serializes billing per appointment, so concurrent bills see each other's items
+    sqlx::query("SELECT id FROM appointments WHERE id = $1 FOR UPDATE")
+        .bind(appointment.id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    let visit_billed = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS (SELECT 1 FROM bill_items i JOIN bills b ON b.id = i.bill_id WHERE b.appointment_id = $1 AND b.status <> $2 AND i.kind = $3)",
+    )
+    .bind(appointment.id)
+    .bind(BillStatus::Cancelled)
+    .bind(ItemKind::Consultation)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    let orders = sqlx::query_as::<_, AppointmentOrder>(
+        "SELECT * FROM appointment_orders WHERE appointment_id = $1 AND bill_id IS NULL ORDER BY created_at FOR UPDATE",
+    )
+    .bind(appointment.id)
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    let mut items = Vec::with_capacity(orders.len() + 1);
+    if !visit_billed {
+        items.push(price_line(
+            &bill.currency,
+            discount_percent,
+            false,
+            BillItem {
+                id: Uuid::new_v4(),
+                bill_id: bill.id,
+                order_id: None,
+                kind: ItemKind::Consultation,
+                description: visit_description(appointment.visit_type).to_string(),
+                quantity: 1,
+                unit_price: appointment.price,
+                discount: Money::ZERO,
+                tax: Money::ZERO,
+                total: Money::ZERO,
+            },
+        )?);
+    }
+    for order in &orders {
+        items.push(price_line(
+            &bill.currency,
+            discount_percent,
+            order.taxable,
+            BillItem {
+                id: Uuid::new_v4(),
+                bill_id: bill.id,
+                order_id: Some(order.id),
+                kind: order.kind,
+                description: order.description.clone(),
+                quantity: order.quantity,
+                unit_price: order.unit_price,
+                discount: order.discount,
+                tax: Money::ZERO,
+                total: Money::ZERO,
+            },
+        )?);
+    }
+    if items.is_empty() {
+        return Err(AppError::UnProcessableEntity {
+            field: "appointment_id".to_string(),
+            message: "Everything on this appointment has already been billed".to_string(),
+        });
+    }
+    bill.set_totals(&items);
+    validate_bill_amount(&bill)?;
+    let policy = if payload.self_pay.unwrap_or(false) {
+        None
+    } else {
+        cover_bill(
+            &mut tx,
+            &mut bill,
+            &items,
+            appointment.patient_id,
+            payload.policy_id,
+            appointment.time.date_naive(),
+        )
+        .await?
+    };
+
+    sqlx::query("INSERT INTO bills (id, reference, appointment_id, hospital_id, amount, subtotal, discount, tax, insurer_amount, policy_id, currency, status, paid_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)")
+        .bind(bill.id)
+        .bind(&bill.reference)
+        .bind(bill.appointment_id)
+        .bind(bill.hospital_id)
+        .bind(bill.amount)
+        .bind(bill.subtotal)
+        .bind(bill.discount)
+        .bind(bill.tax)
+        .bind(bill.insurer_amount)
+        .bind(bill.policy_id)
+        .bind(&bill.currency)
+        .bind(bill.status)
+        .bind(bill.paid_at)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    if let Some(policy) = &policy {
+        open_claim(&mut tx, &bill, policy).await?;
     }
+    for item in &items {
+        sqlx::query("INSERT INTO bill_items (id, bill_id, order_id, kind, description, quantity, unit_price, discount, tax, total) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)")
+            .bind(item.id)
+            .bind(item.bill_id)
+            .bind(item.order_id)
+            .bind(item.kind)
+            .bind(&item.description)
+            .bind(item.quantity)
+            .bind(item.unit_price)
+            .bind(item.discount)
+            .bind(item.tax)
+            .bind(item.total)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    }
+    let order_ids: Vec<Uuid> = orders.iter().map(|order| order.id).collect();
+    sqlx::query("UPDATE appointment_orders SET bill_id = $1 WHERE id = ANY($2)")
+        .bind(bill.id)
+        .bind(&order_ids)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    Ok(Invoice {
+        outstanding: bill.outstanding(),
+        bill,
+        items,
+        payments: Vec::new(),
+    })
 }
 
-async fn get_bill_by_id(state: SharedState, bill_id: String) -> Result<Bill, AppError> {
-    let id = Uuid::parse_str(&bill_id).map_err(|e| AppError::ParsingError(e.to_string()))?;
-    let bill = sqlx::query_as::<_, Bill>("SELECT * FROM bills WHERE id = $1")
-        .bind(id)
+fn visit_description(visit_type: VisitType) -> &'static str {
+    match visit_type {
+        VisitType::Consultation => "Consultation",
+        VisitType::FollowUp => "Follow-up visit",
+        VisitType::Emergency => "Emergency visit",
+    }
+}
+
+// Completes a line: the bill-wide discount is added to the line's own, VAT is charged on what
+// is left of taxable lines, and each is rounded to the currency's minor unit
+fn price_line(
+    currency: &str,
+    discount_percent: Decimal,
+    taxable: bool,
+    mut item: BillItem,
+) -> Result<BillItem, AppError> {
+    let gross = item.gross();
+    item.discount = (item.discount + gross.percent(discount_percent)).round_for(currency)?;
+    if item.discount > gross {
+        return Err(AppError::UnProcessableEntity {
+            field: "discount".to_string(),
+            message: format!("The discount on '{}' exceeds its price", item.description),
+        });
+    }
+    let net = gross - item.discount;
+    item.tax = if taxable {
+        net.percent(VAT_PERCENT).round_for(currency)?
+    } else {
+        Money::ZERO
+    };
+    item.total = net + item.tax;
+    Ok(item)
+}
+
+pub async fn get_invoice(
+    state: SharedState,
+    bill_id: String,
+    hospital_id: Option<Uuid>,
+) -> Result<Invoice, AppError> {
+    let bill = get_bill_by_id(state.clone(), bill_id, hospital_id).await?;
+    let items = sqlx::query_as::<_, BillItem>(
+        "SELECT * FROM bill_items WHERE bill_id = $1 ORDER BY kind <> 'Consultation', description",
+    )
+    .bind(bill.id)
+    .fetch_all(&state.db_pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    let payments = sqlx::query_as::<_, Payment>(
+        "SELECT * FROM payments WHERE bill_id = $1 ORDER BY created_at",
+    )
+    .bind(bill.id)
+    .fetch_all(&state.db_pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    Ok(Invoice {
+        outstanding: bill.outstanding(),
+        bill,
+        items,
+        payments,
+    })
+}
+
+// Gathers what is printed on a bill's invoice or receipt
+pub async fn get_bill_document(
+    state: SharedState,
+    bill_id: String,
+    kind: DocumentKind,
+    hospital_id: Option<Uuid>,
+) -> Result<BillDocument, AppError> {
+    let invoice = get_invoice(state.clone(), bill_id, hospital_id).await?;
+    if kind == DocumentKind::Receipt && !invoice.bill.amount_paid.is_positive() {
+        return Err(AppError::Conflict(
+            "Nothing has been paid on this bill yet".to_string(),
+        ));
+    }
+    let appointment =
+        get_appointment_by_id(state.clone(), invoice.bill.appointment_id.to_string(), None).await?;
+    let patient = sqlx::query_as::<_, Patient>("SELECT * FROM patients WHERE id = $1")
+        .bind(appointment.patient_id)
         .fetch_one(&state.db_pool)
         .await
         .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    let (doctor_name, doctor_hospital_id) = sqlx::query_as::<_, (String, Option<Uuid>)>(
+        "SELECT name, hospital_id FROM doctors WHERE id = $1",
+    )
+    .bind(appointment.doctor_id)
+    .fetch_one(&state.db_pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    let hospital = sqlx::query_as::<_, Hospital>("SELECT * FROM hospitals WHERE id = $1")
+        .bind(doctor_hospital_id.or(patient.hospital_id))
+        .fetch_optional(&state.db_pool)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    Ok(BillDocument {
+        hospital,
+        patient,
+        doctor_name,
+        appointment,
+        invoice,
+    })
+}
+
+// A bill must be positive and expressible in whole minor units of its currency, so what Paystack
+// charges is exactly what the bill says
+fn validate_bill_amount(bill: &Bill) -> Result<(), AppError> {
+    if !bill.amount.is_positive() {
+        return Err(AppError::UnProcessableEntity {
+            field: "amount".to_string(),
+            message: "Amount must be greater than zero".to_string(),
+        });
+    }
+    bill.amount.to_minor_units(&bill.currency)?;
+    Ok(())
+}
+
+// Points the bills of `from`, and its unbilled orders, at the appointment that replaced it.
+// Paid and refunded bills move too: the visit they paid for is the new appointment, so it is
+// not billed twice and cancelling it refunds them. Only cancelled bills stay behind.
+pub async fn transfer_bills(conn: &mut PgConnection, from: Uuid, to: Uuid) -> Result<(), AppError> {
+    sqlx::query("UPDATE bills SET appointment_id = $1 WHERE appointment_id = $2 AND status <> $3")
+        .bind(to)
+        .bind(from)
+        .bind(BillStatus::Cancelled)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    // Work ordered but not billed yet follows the visit too
+    sqlx::query(
+        "UPDATE appointment_orders SET appointment_id = $1 WHERE appointment_id = $2 AND bill_id IS NULL",
+    )
+    .bind(to)
+    .bind(from)
+    .execute(conn)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    Ok(())
+}
+
+// A bill by id, from `hospital_id` when given
+async fn get_bill_by_id(
+    state: SharedState,
+    bill_id: String,
+    hospital_id: Option<Uuid>,
+) -> Result<Bill, AppError> {
+    let id = Uuid::parse_str(&bill_id).map_err(|e| AppError::ParsingError(e.to_string()))?;
+    let bill = sqlx::query_as::<_, Bill>(
+        "SELECT * FROM bills WHERE id = $1 AND ($2::uuid IS NULL OR hospital_id = $2)",
+    )
+    .bind(id)
+    .bind(hospital_id)
+    .fetch_one(&state.db_pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
     Ok(bill)
 }
 
+// Starts an online payment of the outstanding balance or an instalment of it. Each attempt is
+// its own payment with its own reference, as gateways reject a reused reference.
 pub async fn pay_bill(
     state: SharedState,
     payload: PayBillRequest,
+    hospital_id: Option<Uuid>,
 ) -> Result<AuthorizationResponse, AppError> {
-    let bill = get_bill_by_id(state.clone(), payload.bill_id.clone()).await?;
+    let bill_id =
+        Uuid::parse_str(&payload.bill_id).map_err(|e| AppError::ParsingError(e.to_string()))?;
+
+    // The bill stays locked until the payment is recorded. A new checkout replaces any the
+    // patient left open, so only one at a time claims the balance.
+    let mut tx = state
+        .db_pool
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    let bill = sqlx::query_as::<_, Bill>(
+        "SELECT * FROM bills WHERE id = $1 AND ($2::uuid IS NULL OR hospital_id = $2) FOR UPDATE",
+    )
+    .bind(bill_id)
+    .bind(hospital_id)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    supersede_online_payments(&mut tx, bill.id).await?;
+    let amount = validate_payment_amount(&bill, payload.amount)?;
 
-    let (paystack_url, paystack_secret_key) = get_paystack_config()?;
+    let payment = sqlx::query_as::<_, Payment>(
+        "INSERT INTO payments (id, bill_id, reference, amount, currency, method, status) VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING *",
+    )
+    .bind(Uuid::new_v4())
+    .bind(bill.id)
+    .bind(create_random_string(12))
+    .bind(amount)
+    .bind(&bill.currency)
+    .bind(PaymentMethod::Online)
+    .bind(PaymentStatus::Pending)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
 
-    match bill.status {
-        BillStatus::Paid => {
-            return Err(AppError::DatabaseError(
-                "Bill has already been paid".to_string(),
-            ));
+    let settings = payment_settings_for_appointment(&state, bill.appointment_id).await?;
+    let initialized = match gateway_for(&state, settings.as_ref()) {
+        Ok(gateway) => {
+            gateway
+                .initialize(InitializePayment {
+                    email: payload.email,
+                    amount: amount.to_minor_units(&bill.currency)?,
+                    currency: bill.currency.clone(),
+                    reference: payment.reference.clone(),
+                    callback_url: settings.as_ref().and_then(|s| s.callback_url.clone()),
+                    subaccount: settings.and_then(|s| s.paystack_subaccount),
+                })
+                .await
+        }
+        Err(e) => Err(e),
+    };
+    // A checkout the gateway never opened cannot be paid, so it must not hold the balance
+    let authorization_url = match initialized {
+        Ok(url) => url,
+        Err(e) => {
+            sqlx::query("UPDATE payments SET status = $1 WHERE id = $2 AND status = $3")
+                .bind(PaymentStatus::Failed)
+                .bind(payment.id)
+                .bind(PaymentStatus::Pending)
+                .execute(&state.db_pool)
+                .await
+                .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+            warn!(payment_id = %payment.id, error = %e, "could not start checkout");
+            return Err(e);
         }
-        _ => {}
+    };
+
+    Ok(AuthorizationResponse {
+        authorization_url,
+        reference: payment.reference,
+        payment_id: payment.id,
+        amount,
+    })
+}
+
+// Records money collected at the front desk (cash, POS or bank transfer). It counts towards the
+// bill straight away, as the cashier has already received it.
+pub async fn record_offline_payment(
+    state: SharedState,
+    bill_id: String,
+    payload: RecordPaymentRequest,
+    collected_by: Uuid,
+    hospital_id: Option<Uuid>,
+) -> Result<Payment, AppError> {
+    let bill_id = Uuid::parse_str(&bill_id).map_err(|e| AppError::ParsingError(e.to_string()))?;
+    if payload.method == PaymentMethod::Online {
+        return Err(AppError::UnProcessableEntity {
+            field: "method".to_string(),
+            message: "Online payments are taken through /billing/pay".to_string(),
+        });
+    }
+    let receipt_number = payload.receipt_number.trim();
+    if receipt_number.is_empty() {
+        return Err(AppError::UnProcessableEntity {
+            field: "receipt_number".to_string(),
+            message: "Receipt number is required".to_string(),
+        });
     }
-    let reference = &bill.reference;
-    let paystack_request = PayStackRequest {
-        email: payload.email,
-        amount: (bill.amount * 100.0) as u64, // Convert to kobo
-        reference: reference.clone(),
-        callback_url: "https://yourapp.com/payment/callback".to_string(),
+
+    let mut tx = state
+        .db_pool
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    let bill = sqlx::query_as::<_, Bill>(
+        "SELECT * FROM bills WHERE id = $1 AND ($2::uuid IS NULL OR hospital_id = $2) FOR UPDATE",
+    )
+    .bind(bill_id)
+    .bind(hospital_id)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    // The patient paid at the desk instead, so their open checkouts no longer hold the balance
+    supersede_online_payments(&mut tx, bill.id).await?;
+    let amount = validate_payment_amount(&bill, payload.amount)?;
+
+    let payment = sqlx::query_as::<_, Payment>(
+        "INSERT INTO payments (id, bill_id, reference, amount, currency, method, status, receipt_number, collected_by, paid_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, NOW()) RETURNING *",
+    )
+    .bind(Uuid::new_v4())
+    .bind(bill.id)
+    .bind(create_random_string(12))
+    .bind(amount)
+    .bind(&bill.currency)
+    .bind(payload.method)
+    .bind(PaymentStatus::Succeeded)
+    .bind(receipt_number)
+    .bind(collected_by)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| match e.as_database_error() {
+        Some(db_error) if db_error.is_unique_violation() => AppError::Conflict(format!(
+            "Receipt {} has already been recorded",
+            receipt_number
+        )),
+        _ => AppError::DatabaseError(e.to_string()),
+    })?;
+    credit_bill(&mut tx, &bill, amount).await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    info!(
+        bill_id = %bill.id,
+        payment_id = %payment.id,
+        method = ?payment.method,
+        %amount,
+        %collected_by,
+        "offline payment recorded"
+    );
+    Ok(payment)
+}
+
+// The amount of a new payment: the requested instalment, or the whole outstanding balance
+fn validate_payment_amount(bill: &Bill, requested: Option<Money>) -> Result<Money, AppError> {
+    if !bill.status.is_payable() {
+        return Err(AppError::Conflict(format!(
+            "A bill that is {:?} cannot take payments",
+            bill.status
+        )));
+    }
+    let outstanding = bill.outstanding();
+    let amount = requested.unwrap_or(outstanding);
+    if !amount.is_positive() || amount > outstanding {
+        return Err(AppError::UnProcessableEntity {
+            field: "amount".to_string(),
+            message: format!("Payment must be more than zero and at most {outstanding}"),
+        });
+    }
+    amount.to_minor_units(&bill.currency)?;
+    Ok(amount)
+}
+
+// Expires the bill's open online checkouts. One the patient completes anyway still counts when
+// the gateway reports it, as an overpayment if the bill was paid some other way meanwhile.
+async fn supersede_online_payments(conn: &mut PgConnection, bill_id: Uuid) -> Result<(), AppError> {
+    let superseded = sqlx::query(
+        "UPDATE payments SET status = $1 WHERE bill_id = $2 AND method = $3 AND status = $4",
+    )
+    .bind(PaymentStatus::Expired)
+    .bind(bill_id)
+    .bind(PaymentMethod::Online)
+    .bind(PaymentStatus::Pending)
+    .execute(conn)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?
+    .rows_affected();
+    if superseded > 0 {
+        info!(%bill_id, superseded, "open checkouts replaced");
+    }
+    Ok(())
+}
+
+// The gateway acting for a hospital: with its own secret key when it has set one, otherwise
+// the platform's
+fn gateway_for(
+    state: &SharedState,
+    settings: Option<&PaymentSettings>,
+) -> Result<Arc<dyn PaymentGateway>, AppError> {
+    match settings.and_then(|s| s.paystack_secret_key.as_deref()) {
+        Some(encrypted) => Ok(state.payment_gateway.with_secret_key(SecretString::from(
+            state.settings_cipher.decrypt(encrypted)?,
+        ))),
+        None => Ok(state.payment_gateway.clone()),
+    }
+}
+
+// The gateway for the hospital that billed the payment with this reference
+async fn gateway_for_payment(
+    state: &SharedState,
+    reference: &str,
+) -> Result<Arc<dyn PaymentGateway>, AppError> {
+    let appointment_id = sqlx::query_scalar::<_, Uuid>(
+        "SELECT b.appointment_id FROM payments p JOIN bills b ON b.id = p.bill_id WHERE p.reference = $1",
+    )
+    .bind(reference)
+    .fetch_optional(&state.db_pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    let settings = match appointment_id {
+        Some(appointment_id) => payment_settings_for_appointment(state, appointment_id).await?,
+        None => None,
     };
-    let client = Client::new();
-    let response = client
-        .post(&format!("{}/transaction/initialize", &paystack_url))
-        .header("Authorization", format!("Bearer {}", &paystack_secret_key))
-        .json(&paystack_request)
-        .send()
-        .await
-        .map_err(|e| {
-            AppError::InternalServerError(format!("Failed to send request to Paystack: {}", e))
-        })?;
-
-    if response.status().is_success() {
-        let response_json: Value = response.json().await.map_err(|e| {
-            AppError::ParsingError(format!("Failed to parse Paystack response: {}", e))
-        })?;
-        let authorization_url = response_json["data"]["authorization_url"]
-            .as_str()
-            .ok_or_else(|| {
-                AppError::ParsingError(
-                    "Missing 'authorization_url' in Paystack response".to_string(),
-                )
-            })?
-            .to_string();
+    gateway_for(state, settings.as_ref())
+}
+
+// Webhooks for hospitals with their own Paystack account are delivered with the hospital's id,
+// as they are signed with that account's key
+pub async fn handle_payment_webhook(
+    state: SharedState,
+    hospital_id: Option<Uuid>,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<(), AppError> {
+    let settings = match hospital_id {
+        Some(hospital_id) => payment_settings_for_hospital(&state, hospital_id).await?,
+        None => None,
+    };
+    match gateway_for(&state, settings.as_ref())?.parse_webhook(headers, body)? {
+        Some(event) => process_payment_event(state, hospital_id, event).await,
+        None => Ok(()),
+    }
+}
+
+// Applies a verified event to its payment. Each (event, reference) pair is acted on once, so
+// the provider's redeliveries are acknowledged without touching the payment again. `account` is
+// the hospital whose own key signed the event, None for the platform key; events for payments
+// taken on another account are ignored, so one hospital cannot settle another's payments.
+async fn process_payment_event(
+    state: SharedState,
+    account: Option<Uuid>,
+    event: WebhookEvent,
+) -> Result<(), AppError> {
+    let reference = &event.reference;
+    let mut tx = state
+        .db_pool
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    match payment_account(&mut tx, reference).await? {
+        None => {
+            warn!(event = %event.event, %reference, "payment event for unknown payment reference");
+            return Ok(());
+        }
+        Some(expected) if expected != account => {
+            warn!(
+                event = %event.event,
+                %reference,
+                signed_for = ?account,
+                "payment event signed with another account's key"
+            );
+            return Ok(());
+        }
+        Some(_) => (),
+    }
 
-        Ok(AuthorizationResponse {
-            authorization_url,
-            reference: reference.clone(),
+    let recorded = sqlx::query(
+        "INSERT INTO payment_events (id, event, reference, payload) VALUES ($1, $2, $3, $4) ON CONFLICT (event, reference) DO NOTHING",
+    )
+    .bind(Uuid::new_v4())
+    .bind(&event.event)
+    .bind(reference)
+    .bind(&event.payload)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?
+    .rows_affected();
+    if recorded == 0 {
+        info!(event = %event.event, %reference, "payment event already processed");
+        return Ok(());
+    }
+
+    apply_charge_outcome(&mut tx, reference, event.outcome, false).await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    Ok(())
+}
+
+// The hospital whose own Paystack key signs the events of a payment, or None when its hospital
+// takes payments on the platform key. The outer None means there is no such payment.
+async fn payment_account(
+    conn: &mut PgConnection,
+    reference: &str,
+) -> Result<Option<Option<Uuid>>, AppError> {
+    sqlx::query_scalar::<_, Option<Uuid>>(
+        "SELECT CASE WHEN s.paystack_secret_key IS NOT NULL THEN b.hospital_id END FROM payments p \
+         JOIN bills b ON b.id = p.bill_id \
+         LEFT JOIN hospital_payment_settings s ON s.hospital_id = b.hospital_id \
+         WHERE p.reference = $1",
+    )
+    .bind(reference)
+    .fetch_optional(conn)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))
+}
+
+// Locks a payment and then its bill, always in that order
+async fn lock_payment(
+    conn: &mut PgConnection,
+    reference: &str,
+) -> Result<Option<(Payment, Bill)>, AppError> {
+    let payment =
+        sqlx::query_as::<_, Payment>("SELECT * FROM payments WHERE reference = $1 FOR UPDATE")
+            .bind(reference)
+            .fetch_optional(&mut *conn)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    let Some(payment) = payment else {
+        return Ok(None);
+    };
+    let bill = sqlx::query_as::<_, Bill>("SELECT * FROM bills WHERE id = $1 FOR UPDATE")
+        .bind(payment.bill_id)
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    Ok(Some((payment, bill)))
+}
+
+// Moves a pending payment according to what the gateway reports for it, crediting its bill on
+// success. Unfinished transactions only expire the payment when `expire_unpaid` is set and an
+// earlier check found it unpaid too. Returns false when there is no payment with this reference.
+async fn apply_charge_outcome(
+    conn: &mut PgConnection,
+    reference: &str,
+    outcome: ChargeOutcome,
+    expire_unpaid: bool,
+) -> Result<bool, AppError> {
+    let Some((payment, bill)) = lock_payment(&mut *conn, reference).await? else {
+        return Ok(false);
+    };
+    // Only pending payments move, except that a checkout completed after we expired it is still
+    // money received; any other late or repeated report changes nothing
+    let late_success = payment.status == PaymentStatus::Expired
+        && matches!(outcome, ChargeOutcome::Succeeded { .. });
+    if payment.status != PaymentStatus::Pending && !late_success {
+        return Ok(true);
+    }
+
+    let next = match outcome {
+        ChargeOutcome::Succeeded { amount, currency } => {
+            let expected = payment.amount.to_minor_units(&payment.currency)?;
+            if amount != expected || currency != payment.currency {
+                // Leave the payment open for someone to look at rather than settle it wrongly
+                warn!(
+                    %reference,
+                    paid = amount,
+                    paid_currency = %currency,
+                    expected,
+                    currency = %payment.currency,
+                    "charge does not match payment"
+                );
+                return Ok(true);
+            }
+            PaymentStatus::Succeeded
+        }
+        ChargeOutcome::Failed(reason) => {
+            info!(
+                payment_id = %payment.id,
+                %reference,
+                reason = reason.as_deref().unwrap_or("unknown"),
+                "payment failed"
+            );
+            PaymentStatus::Failed
+        }
+        // Paystack reports checkouts still in progress as abandoned too, so one check is not
+        // enough to give up on a payment
+        ChargeOutcome::Abandoned | ChargeOutcome::Missing
+            if expire_unpaid && payment.last_verified_at.is_some() =>
+        {
+            PaymentStatus::Expired
+        }
+        _ => return Ok(true),
+    };
+
+    sqlx::query(
+        "UPDATE payments SET status = $1, paid_at = CASE WHEN $1 = 'Succeeded' THEN NOW() END WHERE id = $2",
+    )
+    .bind(next)
+    .bind(payment.id)
+    .execute(&mut *conn)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    if next == PaymentStatus::Succeeded {
+        credit_bill(conn, &bill, payment.amount).await?;
+        if late_success {
+            warn!(bill_id = %bill.id, payment_id = %payment.id, amount = %payment.amount, "payment received after it expired");
+        } else {
+            info!(bill_id = %bill.id, payment_id = %payment.id, amount = %payment.amount, "payment received");
+        }
+    }
+    Ok(true)
+}
+
+// Sets what the insurer pays on a bill, e.g. when a claim is rejected or approved for less, and
+// moves the bill back to awaiting payment if the patient now owes more than they paid
+pub async fn set_insurer_amount(
+    conn: &mut PgConnection,
+    bill_id: Uuid,
+    insurer_amount: Money,
+) -> Result<Bill, AppError> {
+    let bill = sqlx::query_as::<_, Bill>("SELECT * FROM bills WHERE id = $1 FOR UPDATE")
+        .bind(bill_id)
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    let patient_amount = bill.amount - insurer_amount;
+    let status = match bill.status {
+        BillStatus::Pending | BillStatus::PartiallyPaid | BillStatus::Paid => {
+            if bill.amount_paid >= patient_amount {
+                BillStatus::Paid
+            } else if bill.amount_paid.is_positive() {
+                BillStatus::PartiallyPaid
+            } else {
+                BillStatus::Pending
+            }
+        }
+        status => status,
+    };
+    sqlx::query_as::<_, Bill>(
+        "UPDATE bills SET insurer_amount = $1, status = $2, paid_at = CASE WHEN $2 = 'Paid' THEN COALESCE(paid_at, NOW()) WHEN $2 IN ('Pending', 'PartiallyPaid') THEN NULL ELSE paid_at END WHERE id = $3 RETURNING *",
+    )
+    .bind(insurer_amount)
+    .bind(status)
+    .bind(bill.id)
+    .fetch_one(conn)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))
+}
+
+// Adds a successful payment to its bill. The caller must hold the bill's row lock. Money that
+// arrives for a bill that was cancelled in the meantime is recorded but leaves the bill
+// cancelled, so it shows as refundable; anything paid beyond what the patient owes is flagged
+// for a refund the same way.
+async fn credit_bill(conn: &mut PgConnection, bill: &Bill, amount: Money) -> Result<(), AppError> {
+    let amount_paid = bill.amount_paid + amount;
+    if bill.status == BillStatus::Cancelled {
+        warn!(bill_id = %bill.id, %amount, "payment received for a cancelled bill");
+    } else if amount_paid > bill.patient_amount() {
+        warn!(
+            bill_id = %bill.id,
+            %amount,
+            overpaid = %(amount_paid - bill.patient_amount()),
+            "payment exceeds what the patient owes"
+        );
+    }
+    let status = match bill.status {
+        BillStatus::Pending | BillStatus::PartiallyPaid => {
+            if amount_paid >= bill.patient_amount() {
+                BillStatus::Paid
+            } else {
+                BillStatus::PartiallyPaid
+            }
+        }
+        status => status,
+    };
+    sqlx::query(
+        "UPDATE bills SET amount_paid = $1, status = $2, paid_at = CASE WHEN $2 = 'Paid' THEN NOW() ELSE paid_at END WHERE id = $3",
+    )
+    .bind(amount_paid)
+    .bind(status)
+    .bind(bill.id)
+    .execute(conn)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    Ok(())
+}
+
+// Checks a payment with the gateway and settles it; returns the bill as it now stands.
+// We never settle a payment from a client redirect alone.
+pub async fn verify_payment(
+    state: SharedState,
+    reference: String,
+    hospital_id: Option<Uuid>,
+) -> Result<Bill, AppError> {
+    let known = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS (SELECT 1 FROM payments p JOIN bills b ON b.id = p.bill_id WHERE p.reference = $1 AND ($2::uuid IS NULL OR b.hospital_id = $2))",
+    )
+    .bind(&reference)
+    .bind(hospital_id)
+    .fetch_one(&state.db_pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    if !known {
+        return Err(AppError::NotFound(format!(
+            "Payment with reference {} not found",
+            reference
+        )));
+    }
+    let outcome = gateway_for_payment(&state, &reference)
+        .await?
+        .verify(&reference)
+        .await?;
+    settle_payment(&state, &reference, outcome, false)
+        .await?
+        .ok_or_else(|| {
+            AppError::NotFound(format!("Payment with reference {} not found", reference))
         })
+}
+
+async fn settle_payment(
+    state: &SharedState,
+    reference: &str,
+    outcome: ChargeOutcome,
+    expire_unpaid: bool,
+) -> Result<Option<Bill>, AppError> {
+    let mut tx = state
+        .db_pool
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    if !apply_charge_outcome(&mut tx, reference, outcome, expire_unpaid).await? {
+        return Ok(None);
+    }
+    sqlx::query("UPDATE payments SET last_verified_at = NOW() WHERE reference = $1")
+        .bind(reference)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    let bill = sqlx::query_as::<_, Bill>(
+        "SELECT b.* FROM bills b JOIN payments p ON p.bill_id = b.id WHERE p.reference = $1",
+    )
+    .bind(reference)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    Ok(Some(bill))
+}
+
+// Checks online payments that are still pending `pending_minutes` after they were started or
+// last checked, settling those the gateway has an answer for. A payment is expired once two
+// checks that far apart found it unpaid. A payment that fails to reconcile is logged and retried
+// on the next sweep.
+pub async fn reconcile_pending_payments(
+    state: &SharedState,
+    pending_minutes: i32,
+) -> Result<(), AppError> {
+    let references = sqlx::query_scalar::<_, String>(
+        "SELECT reference FROM payments WHERE status = $1 AND method = $2 AND COALESCE(last_verified_at, created_at) < NOW() - make_interval(mins => $3) ORDER BY COALESCE(last_verified_at, created_at) LIMIT 100",
+    )
+    .bind(PaymentStatus::Pending)
+    .bind(PaymentMethod::Online)
+    .bind(pending_minutes)
+    .fetch_all(&state.db_pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    for reference in references {
+        let outcome = match gateway_for_payment(state, &reference).await {
+            Ok(gateway) => gateway.verify(&reference).await,
+            Err(e) => Err(e),
+        };
+        let result = match outcome {
+            Ok(outcome) => settle_payment(state, &reference, outcome, true).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            warn!(%reference, error = %e, "failed to reconcile payment");
+        }
+    }
+    Ok(())
+}
+
+// Background task started with the server
+pub async fn run_reconciliation(state: SharedState, interval_seconds: u64, pending_minutes: i32) {
+    let mut interval = tokio::time::interval(Duration::from_secs(interval_seconds.max(1)));
+    loop {
+        interval.tick().await;
+        if let Err(e) = reconcile_pending_payments(&state, pending_minutes).await {
+            warn!(error = %e, "payment reconciliation sweep failed");
+        }
+    }
+}
+
+// Cancels a bill nothing has been paid on, freeing its orders for the next bill. Bills with
+// money on them are refunded instead.
+pub async fn cancel_bill(
+    state: SharedState,
+    bill_id: String,
+    hospital_id: Option<Uuid>,
+) -> Result<Bill, AppError> {
+    let id = Uuid::parse_str(&bill_id).map_err(|e| AppError::ParsingError(e.to_string()))?;
+    let bill = sqlx::query_as::<_, Bill>(
+        "WITH cancelled AS (UPDATE bills SET status = $1 WHERE id = $2 AND status = $3 AND ($6::uuid IS NULL OR hospital_id = $6) RETURNING *), \
+         released AS (UPDATE appointment_orders SET bill_id = NULL WHERE bill_id IN (SELECT id FROM cancelled)), \
+         withdrawn AS (UPDATE insurance_claims SET status = $4 WHERE bill_id IN (SELECT id FROM cancelled) AND status = $5) \
+         SELECT * FROM cancelled",
+    )
+    .bind(BillStatus::Cancelled)
+    .bind(id)
+    .bind(BillStatus::Pending)
+    .bind(ClaimStatus::Withdrawn)
+    .bind(ClaimStatus::Pending)
+    .bind(hospital_id)
+    .fetch_optional(&state.db_pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    match bill {
+        Some(bill) => Ok(bill),
+        None => {
+            // Tell a missing bill apart from one that can no longer be cancelled
+            let bill = get_bill_by_id(state, bill_id, hospital_id).await?;
+            Err(AppError::Conflict(format!(
+                "A bill that is {:?} cannot be cancelled",
+                bill.status
+            )))
+        }
+    }
+}
+
+// Cancels the unpaid bills of a cancelled appointment, on the caller's transaction
+pub async fn cancel_open_bills(
+    conn: &mut PgConnection,
+    appointment_id: Uuid,
+) -> Result<(), AppError> {
+    sqlx::query(
+        "WITH cancelled AS (UPDATE bills SET status = $1 WHERE appointment_id = $2 AND status = $3 RETURNING id), \
+         withdrawn AS (UPDATE insurance_claims SET status = $4 WHERE bill_id IN (SELECT id FROM cancelled) AND status = $5) \
+         UPDATE appointment_orders SET bill_id = NULL WHERE bill_id IN (SELECT id FROM cancelled)",
+    )
+    .bind(BillStatus::Cancelled)
+    .bind(appointment_id)
+    .bind(BillStatus::Pending)
+    .bind(ClaimStatus::Withdrawn)
+    .bind(ClaimStatus::Pending)
+    .execute(conn)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    Ok(())
+}
+
+// Refunds part or all of what was paid on a bill, newest payment first, through the payment
+// gateway. Refunding closes the bill to further payments. The refunds are recorded as pending
+// while the bill is locked, so concurrent requests cannot refund more than was paid, and the
+// gateway is only called once that is committed.
+pub async fn refund_bill(
+    state: SharedState,
+    bill_id: Uuid,
+    amount: Option<Money>,
+    reason: Option<String>,
+    requested_by: Option<Uuid>,
+    hospital_id: Option<Uuid>,
+) -> Result<RefundList, AppError> {
+    let mut tx = state
+        .db_pool
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    let bill = sqlx::query_as::<_, Bill>(
+        "SELECT * FROM bills WHERE id = $1 AND ($2::uuid IS NULL OR hospital_id = $2) FOR UPDATE",
+    )
+    .bind(bill_id)
+    .bind(hospital_id)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    let payments = sqlx::query_as::<_, Payment>(
+        "SELECT * FROM payments WHERE bill_id = $1 AND status = $2 AND refunded < amount ORDER BY paid_at DESC FOR UPDATE",
+    )
+    .bind(bill.id)
+    .bind(PaymentStatus::Succeeded)
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    let refundable: Money = payments
+        .iter()
+        .map(|payment| payment.amount - payment.refunded)
+        .sum();
+    if !refundable.is_positive() {
+        return Err(AppError::Conflict(
+            "Nothing has been paid on this bill that can be refunded".to_string(),
+        ));
+    }
+    let amount = amount.unwrap_or(refundable);
+    if !amount.is_positive() || amount > refundable {
+        return Err(AppError::UnProcessableEntity {
+            field: "amount".to_string(),
+            message: format!("Refund must be more than zero and at most {refundable}"),
+        });
+    }
+    amount.to_minor_units(&bill.currency)?;
+    let settings = payment_settings_for_appointment(&state, bill.appointment_id).await?;
+    let gateway = gateway_for(&state, settings.as_ref())?;
+
+    let mut refunds = Vec::new();
+    let mut remaining = amount;
+    for payment in &payments {
+        if !remaining.is_positive() {
+            break;
+        }
+        let portion = remaining.min(payment.amount - payment.refunded);
+        // Offline payments are paid back at the desk, so only online ones wait on the gateway
+        let status = if payment.method == PaymentMethod::Online {
+            REFUND_PENDING
+        } else {
+            REFUND_MANUAL
+        };
+        let refund = sqlx::query_as::<_, Refund>(
+            "INSERT INTO refunds (id, bill_id, payment_id, amount, reason, status, requested_by) VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING *",
+        )
+        .bind(Uuid::new_v4())
+        .bind(bill.id)
+        .bind(payment.id)
+        .bind(portion)
+        .bind(&reason)
+        .bind(status)
+        .bind(requested_by)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        sqlx::query("UPDATE payments SET refunded = refunded + $1 WHERE id = $2")
+            .bind(portion)
+            .bind(payment.id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        remaining = remaining - portion;
+        refunds.push((refund, payment));
+    }
+
+    let status = if amount == refundable {
+        BillStatus::Refunded
+    } else {
+        BillStatus::PartiallyRefunded
+    };
+    sqlx::query("UPDATE bills SET status = $1 WHERE id = $2")
+        .bind(status)
+        .bind(bill.id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    let mut recorded = Vec::with_capacity(refunds.len());
+    let mut refunds = refunds.into_iter();
+    while let Some((refund, payment)) = refunds.next() {
+        if refund.status != REFUND_PENDING {
+            recorded.push(refund);
+            continue;
+        }
+        let result = match refund.amount.to_minor_units(&payment.currency) {
+            Ok(minor_units) => gateway.refund(&payment.reference, Some(minor_units)).await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(gateway_refund) => {
+                let refund = sqlx::query_as::<_, Refund>(
+                    "UPDATE refunds SET provider_refund_id = $1, status = $2 WHERE id = $3 RETURNING *",
+                )
+                .bind(&gateway_refund.id)
+                .bind(&gateway_refund.status)
+                .bind(refund.id)
+                .fetch_one(&state.db_pool)
+                .await
+                .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+                recorded.push(refund);
+            }
+            Err(e) => {
+                // Neither this refund nor the ones after it reached the gateway
+                let unsent: Vec<Refund> = std::iter::once(refund)
+                    .chain(refunds.map(|(refund, _)| refund))
+                    .filter(|refund| refund.status == REFUND_PENDING)
+                    .collect();
+                release_refunds(&state, &bill, &unsent).await?;
+                return Err(e);
+            }
+        }
+    }
+
+    info!(bill_id = %bill.id, amount = %amount, "bill refunded");
+    Ok(RefundList { refunds: recorded })
+}
+
+// Marks refunds the gateway never made as failed and gives their amounts back to the payments.
+// The bill returns to the status it had before if nothing else of it was refunded.
+async fn release_refunds(
+    state: &SharedState,
+    bill: &Bill,
+    refunds: &[Refund],
+) -> Result<(), AppError> {
+    let mut tx = state
+        .db_pool
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    sqlx::query("SELECT id FROM bills WHERE id = $1 FOR UPDATE")
+        .bind(bill.id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    for refund in refunds {
+        sqlx::query("UPDATE refunds SET status = $1 WHERE id = $2")
+            .bind(REFUND_FAILED)
+            .bind(refund.id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        sqlx::query("UPDATE payments SET refunded = refunded - $1 WHERE id = $2")
+            .bind(refund.amount)
+            .bind(refund.payment_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    }
+    let (paid, refunded) = sqlx::query_as::<_, (Money, Money)>(
+        "SELECT COALESCE(SUM(amount), 0), COALESCE(SUM(refunded), 0) FROM payments WHERE bill_id = $1 AND status = $2",
+    )
+    .bind(bill.id)
+    .bind(PaymentStatus::Succeeded)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    let status = if !refunded.is_positive() {
+        bill.status
+    } else if refunded < paid {
+        BillStatus::PartiallyRefunded
     } else {
-        let error_message = response
-            .text()
-            .await
-            .unwrap_or_else(|_| "Unknown error".to_string());
-        Err(AppError::InternalServerError(format!(
-            "Paystack API returned an error: {}",
-            error_message
-        )))
+        BillStatus::Refunded
+    };
+    sqlx::query("UPDATE bills SET status = $1 WHERE id = $2")
+        .bind(status)
+        .bind(bill.id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    warn!(bill_id = %bill.id, count = refunds.len(), "refunds failed at the gateway");
+    Ok(())
+}
+
+pub async fn get_refunds(
+    state: SharedState,
+    bill_id: String,
+    hospital_id: Option<Uuid>,
+) -> Result<RefundList, AppError> {
+    let bill = get_bill_by_id(state.clone(), bill_id, hospital_id).await?;
+    let refunds =
+        sqlx::query_as::<_, Refund>("SELECT * FROM refunds WHERE bill_id = $1 ORDER BY created_at")
+            .bind(bill.id)
+            .fetch_all(&state.db_pool)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    Ok(RefundList { refunds })
+}
+
+// Refunds whatever is left of the payments on a cancelled appointment's bills, as long as it was
+// cancelled at least REFUND_CUTOFF_HOURS before the visit
+pub async fn refund_cancelled_appointment(
+    state: SharedState,
+    appointment: &Appointment,
+    cancelled_by: Option<Uuid>,
+) -> Result<(), AppError> {
+    if appointment.time - Utc::now() < ChronoDuration::hours(REFUND_CUTOFF_HOURS) {
+        return Ok(());
+    }
+    // Bills the insurer covered in full have nothing of the patient's to return
+    let bill_ids = sqlx::query_scalar::<_, Uuid>(
+        "SELECT id FROM bills WHERE appointment_id = $1 AND status IN ($2, $3, $4) AND amount_paid > 0",
+    )
+    .bind(appointment.id)
+    .bind(BillStatus::PartiallyPaid)
+    .bind(BillStatus::Paid)
+    .bind(BillStatus::PartiallyRefunded)
+    .fetch_all(&state.db_pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    for bill_id in bill_ids {
+        refund_bill(
+            state.clone(),
+            bill_id,
+            None,
+            Some("Appointment cancelled".to_string()),
+            cancelled_by,
+            None,
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+// A patient's bills, payments and refunds in one currency, oldest first, with the running
+// balance they owe. Cancelled bills are left out.
+pub async fn get_account_statement(
+    state: SharedState,
+    patient_id: Uuid,
+    currency: Option<String>,
+    hospital_id: Option<Uuid>,
+) -> Result<AccountStatement, AppError> {
+    // In the currency the patient's hospital bills in unless another is asked for
+    let currency = match currency {
+        Some(currency) => currency,
+        None => {
+            let patient_hospital = sqlx::query_scalar::<_, Option<Uuid>>(
+                "SELECT hospital_id FROM patients WHERE id = $1",
+            )
+            .bind(patient_id)
+            .fetch_optional(&state.db_pool)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?
+            .flatten();
+            let settings = match patient_hospital {
+                Some(patient_hospital) => {
+                    payment_settings_for_hospital(&state, patient_hospital).await?
+                }
+                None => None,
+            };
+            settings
+                .map(|settings| settings.default_currency)
+                .unwrap_or_else(|| DEFAULT_CURRENCY.to_string())
+        }
+    };
+    let bills = sqlx::query_as::<_, Bill>(
+        "SELECT b.* FROM bills b JOIN appointments a ON a.id = b.appointment_id WHERE a.patient_id = $1 AND b.currency = $2 AND b.status <> $3 \
+         AND ($4::uuid IS NULL OR b.hospital_id = $4)",
+    )
+    .bind(patient_id)
+    .bind(&currency)
+    .bind(BillStatus::Cancelled)
+    .bind(hospital_id)
+    .fetch_all(&state.db_pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    let bill_ids: Vec<Uuid> = bills.iter().map(|bill| bill.id).collect();
+    let payments = sqlx::query_as::<_, Payment>(
+        "SELECT * FROM payments WHERE bill_id = ANY($1) AND status = $2",
+    )
+    .bind(&bill_ids)
+    .bind(PaymentStatus::Succeeded)
+    .fetch_all(&state.db_pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    let refunds = sqlx::query_as::<_, Refund>(
+        "SELECT * FROM refunds WHERE bill_id = ANY($1) AND status <> $2",
+    )
+    .bind(&bill_ids)
+    .bind(REFUND_FAILED)
+    .fetch_all(&state.db_pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    // (date, kind, bill, reference, amount, change to the balance)
+    let mut lines = Vec::with_capacity(bills.len() + payments.len() + refunds.len());
+    for bill in &bills {
+        lines.push((
+            bill.created_at,
+            StatementEntryKind::Bill,
+            bill.id,
+            bill.reference.clone(),
+            bill.patient_amount(),
+            bill.patient_amount(),
+        ));
+        // A refund closes the bill, so whatever was never paid is no longer owed
+        let closed_at = refunds
+            .iter()
+            .filter(|refund| refund.bill_id == bill.id)
+            .map(|refund| refund.created_at)
+            .max();
+        if let Some(closed_at) = closed_at
+            && bill.patient_amount() > bill.amount_paid
+        {
+            let unpaid = bill.patient_amount() - bill.amount_paid;
+            lines.push((
+                closed_at,
+                StatementEntryKind::WriteOff,
+                bill.id,
+                bill.reference.clone(),
+                unpaid,
+                -unpaid,
+            ));
+        }
+    }
+    for payment in &payments {
+        lines.push((
+            payment.paid_at.unwrap_or(payment.created_at),
+            StatementEntryKind::Payment,
+            payment.bill_id,
+            payment.reference.clone(),
+            payment.amount,
+            -payment.amount,
+        ));
+    }
+    for refund in &refunds {
+        lines.push((
+            refund.created_at,
+            StatementEntryKind::Refund,
+            refund.bill_id,
+            refund
+                .provider_refund_id
+                .clone()
+                .unwrap_or_else(|| refund.id.to_string()),
+            refund.amount,
+            Money::ZERO,
+        ));
+    }
+    lines.sort_by_key(|line| (line.0, line.1 as u8));
+
+    let mut balance = Money::ZERO;
+    let entries = lines
+        .into_iter()
+        .map(|(date, kind, bill_id, reference, amount, change)| {
+            balance = balance + change;
+            StatementEntry {
+                date,
+                kind,
+                bill_id,
+                reference,
+                amount,
+                balance,
+            }
+        })
+        .collect();
+
+    Ok(AccountStatement {
+        patient_id,
+        currency,
+        total_billed: bills.iter().map(Bill::patient_amount).sum(),
+        total_paid: payments.iter().map(|payment| payment.amount).sum(),
+        total_refunded: refunds.iter().map(|refund| refund.amount).sum(),
+        balance,
+        entries,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app_state::AppState;
+    use crate::billing::gateway::FakeGateway;
+    use crate::mailer::LogMailer;
+    use crate::utils::SettingsCipher;
+    use sqlx::PgPool;
+
+    // A pending bill for 10000.00 at `hospital_id` and a pending online payment of all of it
+    async fn pending_payment(pool: &PgPool, hospital_id: Option<Uuid>) -> (Uuid, String) {
+        let (patient_id, doctor_id, appointment_id, bill_id) = (
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+        );
+        let reference = create_random_string(12);
+        sqlx::query("INSERT INTO patients (id, name, age, card_id, gender) VALUES ($1, 'Ada Obi', 40, $2, 'Female')")
+            .bind(patient_id)
+            .bind(patient_id.to_string())
+            .execute(pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO doctors (id, name, specialization, available_days) VALUES ($1, 'Dr Okafor', 'General Practice', ARRAY['Monday'])")
+            .bind(doctor_id)
+            .execute(pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO appointments (id, patient_id, doctor_id, time, purpose, status, price) VALUES ($1, $2, $3, NOW(), 'Checkup', 'Scheduled', 10000)")
+            .bind(appointment_id)
+            .bind(patient_id)
+            .bind(doctor_id)
+            .execute(pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO bills (id, reference, appointment_id, amount, subtotal, currency, status, hospital_id) VALUES ($1, $2, $3, 10000, 10000, 'NGN', 'Pending', $4)")
+            .bind(bill_id)
+            .bind(bill_id.to_string())
+            .bind(appointment_id)
+            .bind(hospital_id)
+            .execute(pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO payments (id, bill_id, reference, amount, currency, method, status, created_at) VALUES ($1, $2, $3, 10000, 'NGN', 'Online', 'Pending', NOW() - INTERVAL '1 hour')")
+            .bind(Uuid::new_v4())
+            .bind(bill_id)
+            .bind(&reference)
+            .execute(pool)
+            .await
+            .unwrap();
+        (bill_id, reference)
+    }
+
+    async fn payment_status(pool: &PgPool, reference: &str) -> PaymentStatus {
+        sqlx::query_scalar("SELECT status FROM payments WHERE reference = $1")
+            .bind(reference)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[sqlx::test]
+    async fn expires_abandoned_payments_only_after_a_second_check(pool: PgPool) {
+        let state = Arc::new(AppState::new(
+            pool.clone(),
+            Arc::new(FakeGateway::default()),
+            Arc::new(LogMailer),
+            None,
+            SettingsCipher::default(),
+        ));
+        let (bill_id, reference) = pending_payment(&pool, None).await;
+
+        // The first check of a slow checkout leaves it pending
+        settle_payment(&state, &reference, ChargeOutcome::Abandoned, true)
+            .await
+            .unwrap();
+        assert_eq!(
+            payment_status(&pool, &reference).await,
+            PaymentStatus::Pending
+        );
+
+        // Still unpaid on the next check, so it expires and stops holding the balance
+        settle_payment(&state, &reference, ChargeOutcome::Abandoned, true)
+            .await
+            .unwrap();
+        assert_eq!(
+            payment_status(&pool, &reference).await,
+            PaymentStatus::Expired
+        );
+
+        // Paying afterwards still counts
+        let bill = settle_payment(
+            &state,
+            &reference,
+            ChargeOutcome::Succeeded {
+                amount: 1_000_000,
+                currency: "NGN".to_string(),
+            },
+            false,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!(bill.id, bill_id);
+        assert_eq!(bill.status, BillStatus::Paid);
+        assert_eq!(bill.amount_paid, Money::from_hundredths(1_000_000));
+        assert_eq!(
+            payment_status(&pool, &reference).await,
+            PaymentStatus::Succeeded
+        );
+    }
+
+    #[sqlx::test]
+    async fn sweep_waits_between_checks(pool: PgPool) {
+        let state = Arc::new(AppState::new(
+            pool.clone(),
+            Arc::new(FakeGateway::default()),
+            Arc::new(LogMailer),
+            None,
+            SettingsCipher::default(),
+        ));
+        let (_, reference) = pending_payment(&pool, None).await;
+
+        // The fake gateway never saw this reference, so each check finds it missing
+        reconcile_pending_payments(&state, 30).await.unwrap();
+        assert_eq!(
+            payment_status(&pool, &reference).await,
+            PaymentStatus::Pending
+        );
+        // Checked a moment ago, so the next sweep leaves it alone
+        reconcile_pending_payments(&state, 30).await.unwrap();
+        assert_eq!(
+            payment_status(&pool, &reference).await,
+            PaymentStatus::Pending
+        );
+
+        sqlx::query("UPDATE payments SET last_verified_at = NOW() - INTERVAL '31 minutes' WHERE reference = $1")
+            .bind(&reference)
+            .execute(&pool)
+            .await
+            .unwrap();
+        reconcile_pending_payments(&state, 30).await.unwrap();
+        assert_eq!(
+            payment_status(&pool, &reference).await,
+            PaymentStatus::Expired
+        );
+    }
+
+    #[sqlx::test]
+    async fn new_checkout_replaces_the_open_one(pool: PgPool) {
+        let state = Arc::new(AppState::new(
+            pool.clone(),
+            Arc::new(FakeGateway::default()),
+            Arc::new(LogMailer),
+            None,
+            SettingsCipher::default(),
+        ));
+        let (bill_id, abandoned) = pending_payment(&pool, None).await;
+        let checkout = || PayBillRequest {
+            bill_id: bill_id.to_string(),
+            email: "ada@example.com".to_string(),
+            amount: None,
+        };
+
+        let response = pay_bill(state.clone(), checkout(), None).await.unwrap();
+        assert_eq!(response.amount, Money::from_hundredths(1_000_000));
+        assert_eq!(
+            payment_status(&pool, &abandoned).await,
+            PaymentStatus::Expired
+        );
+        assert_eq!(
+            payment_status(&pool, &response.reference).await,
+            PaymentStatus::Pending
+        );
+
+        // A checkout the gateway cannot open is failed rather than left holding the balance
+        let hospital_id = insert_hospital(&pool, true).await;
+        sqlx::query("UPDATE appointments SET hospital_id = $1 FROM bills b WHERE b.appointment_id = appointments.id AND b.id = $2")
+            .bind(hospital_id)
+            .bind(bill_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert!(pay_bill(state, checkout(), None).await.is_err());
+        let statuses: Vec<PaymentStatus> = sqlx::query_scalar(
+            "SELECT status FROM payments WHERE bill_id = $1 ORDER BY created_at",
+        )
+        .bind(bill_id)
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        assert_eq!(
+            statuses,
+            [
+                PaymentStatus::Expired,
+                PaymentStatus::Expired,
+                PaymentStatus::Failed
+            ]
+        );
+    }
+
+    #[sqlx::test]
+    async fn desk_payment_replaces_the_open_checkout(pool: PgPool) {
+        let state = Arc::new(AppState::new(
+            pool.clone(),
+            Arc::new(FakeGateway::default()),
+            Arc::new(LogMailer),
+            None,
+            SettingsCipher::default(),
+        ));
+        let (bill_id, reference) = pending_payment(&pool, None).await;
+
+        let payment = record_offline_payment(
+            state.clone(),
+            bill_id.to_string(),
+            RecordPaymentRequest {
+                method: PaymentMethod::Cash,
+                amount: None,
+                receipt_number: "R-0001".to_string(),
+            },
+            Uuid::new_v4(),
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(payment.amount, Money::from_hundredths(1_000_000));
+        assert_eq!(
+            payment_status(&pool, &reference).await,
+            PaymentStatus::Expired
+        );
+
+        // Completing the checkout anyway is kept as an overpayment on the paid bill
+        let outcome = ChargeOutcome::Succeeded {
+            amount: 1_000_000,
+            currency: "NGN".to_string(),
+        };
+        let bill = settle_payment(&state, &reference, outcome, false)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(bill.status, BillStatus::Paid);
+        assert_eq!(bill.amount_paid, Money::from_hundredths(2_000_000));
+    }
+
+    async fn insert_hospital(pool: &PgPool, own_key: bool) -> Uuid {
+        let id = Uuid::new_v4();
+        sqlx::query("INSERT INTO hospitals (id, name, address, phone) VALUES ($1, 'Lagos General', '1 Marina Road', '08000000000')")
+            .bind(id)
+            .execute(pool)
+            .await
+            .unwrap();
+        if own_key {
+            sqlx::query("INSERT INTO hospital_payment_settings (hospital_id, paystack_secret_key) VALUES ($1, 'encrypted')")
+                .bind(id)
+                .execute(pool)
+                .await
+                .unwrap();
+        }
+        id
+    }
+
+    #[sqlx::test]
+    async fn finds_bills_only_in_the_callers_hospital(pool: PgPool) {
+        let state = Arc::new(AppState::new(
+            pool.clone(),
+            Arc::new(FakeGateway::default()),
+            Arc::new(LogMailer),
+            None,
+            SettingsCipher::default(),
+        ));
+        let own = insert_hospital(&pool, false).await;
+        let other = insert_hospital(&pool, false).await;
+        let (bill_id, reference) = pending_payment(&pool, Some(own)).await;
+
+        let result = get_bill_by_id(state.clone(), bill_id.to_string(), Some(other)).await;
+        assert!(matches!(result, Err(AppError::DatabaseError(_))));
+        let result = cancel_bill(state.clone(), bill_id.to_string(), Some(other)).await;
+        assert!(matches!(result, Err(AppError::DatabaseError(_))));
+        let result = verify_payment(state.clone(), reference, Some(other)).await;
+        assert!(matches!(result, Err(AppError::NotFound(_))));
+
+        let bill = get_bill_by_id(state.clone(), bill_id.to_string(), Some(own))
+            .await
+            .unwrap();
+        assert_eq!(bill.status, BillStatus::Pending);
+        // Super admins are not limited to one hospital
+        assert!(
+            get_bill_by_id(state, bill_id.to_string(), None)
+                .await
+                .is_ok()
+        );
+    }
+
+    #[sqlx::test]
+    async fn statement_defaults_to_the_hospitals_currency(pool: PgPool) {
+        let state = Arc::new(AppState::new(
+            pool.clone(),
+            Arc::new(FakeGateway::default()),
+            Arc::new(LogMailer),
+            None,
+            SettingsCipher::default(),
+        ));
+        let hospital_id = insert_hospital(&pool, false).await;
+        sqlx::query("INSERT INTO hospital_payment_settings (hospital_id, default_currency) VALUES ($1, 'GHS')")
+            .bind(hospital_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let (bill_id, _) = pending_payment(&pool, Some(hospital_id)).await;
+        let patient_id: Uuid = sqlx::query_scalar(
+            "UPDATE patients p SET hospital_id = $1 FROM appointments a JOIN bills b ON b.appointment_id = a.id \
+             WHERE b.id = $2 AND p.id = a.patient_id RETURNING p.id",
+        )
+        .bind(hospital_id)
+        .bind(bill_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+
+        let statement = get_account_statement(state.clone(), patient_id, None, None)
+            .await
+            .unwrap();
+        assert_eq!(statement.currency, "GHS");
+        assert!(statement.entries.is_empty());
+        let statement = get_account_statement(state, patient_id, Some("NGN".to_string()), None)
+            .await
+            .unwrap();
+        assert_eq!(statement.total_billed, Money::from_hundredths(1_000_000));
+    }
+
+    fn charge_success(reference: &str) -> WebhookEvent {
+        WebhookEvent {
+            event: "charge.success".to_string(),
+            reference: reference.to_string(),
+            outcome: ChargeOutcome::Succeeded {
+                amount: 1_000_000,
+                currency: "NGN".to_string(),
+            },
+            payload: serde_json::json!({}),
+        }
+    }
+
+    #[sqlx::test]
+    async fn ignores_events_signed_for_another_account(pool: PgPool) {
+        let state = Arc::new(AppState::new(
+            pool.clone(),
+            Arc::new(FakeGateway::default()),
+            Arc::new(LogMailer),
+            None,
+            SettingsCipher::default(),
+        ));
+        let own_key = insert_hospital(&pool, true).await;
+        let other_key = insert_hospital(&pool, true).await;
+        let platform = insert_hospital(&pool, false).await;
+        let (_, own_reference) = pending_payment(&pool, Some(own_key)).await;
+        let (_, platform_reference) = pending_payment(&pool, Some(platform)).await;
+
+        // Another hospital's key, or the platform's, cannot settle a payment taken on its own key
+        for account in [Some(other_key), None] {
+            process_payment_event(state.clone(), account, charge_success(&own_reference))
+                .await
+                .unwrap();
+            assert_eq!(
+                payment_status(&pool, &own_reference).await,
+                PaymentStatus::Pending
+            );
+        }
+        // Nor can a hospital's key settle one taken on the platform's
+        process_payment_event(
+            state.clone(),
+            Some(own_key),
+            charge_success(&platform_reference),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            payment_status(&pool, &platform_reference).await,
+            PaymentStatus::Pending
+        );
+
+        // The rejected deliveries were not recorded, so the genuine ones still apply
+        process_payment_event(state.clone(), Some(own_key), charge_success(&own_reference))
+            .await
+            .unwrap();
+        assert_eq!(
+            payment_status(&pool, &own_reference).await,
+            PaymentStatus::Succeeded
+        );
+        process_payment_event(state, None, charge_success(&platform_reference))
+            .await
+            .unwrap();
+        assert_eq!(
+            payment_status(&pool, &platform_reference).await,
+            PaymentStatus::Succeeded
+        );
+    }
+
+    async fn paid_bill(state: &SharedState, pool: &PgPool, charged: bool) -> Uuid {
+        let (bill_id, reference) = pending_payment(pool, None).await;
+        if charged {
+            state
+                .payment_gateway
+                .initialize(InitializePayment {
+                    email: "ada@example.com".to_string(),
+                    amount: 1_000_000,
+                    currency: "NGN".to_string(),
+                    reference: reference.clone(),
+                    callback_url: None,
+                    subaccount: None,
+                })
+                .await
+                .unwrap();
+        }
+        let outcome = ChargeOutcome::Succeeded {
+            amount: 1_000_000,
+            currency: "NGN".to_string(),
+        };
+        settle_payment(state, &reference, outcome, false)
+            .await
+            .unwrap();
+        bill_id
+    }
+
+    async fn refunded(pool: &PgPool, bill_id: Uuid) -> Money {
+        sqlx::query_scalar("SELECT refunded FROM payments WHERE bill_id = $1")
+            .bind(bill_id)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[sqlx::test]
+    async fn refunds_through_the_gateway(pool: PgPool) {
+        let state = Arc::new(AppState::new(
+            pool.clone(),
+            Arc::new(FakeGateway::default()),
+            Arc::new(LogMailer),
+            None,
+            SettingsCipher::default(),
+        ));
+        let bill_id = paid_bill(&state, &pool, true).await;
+
+        let half = Money::from_hundredths(500_000);
+        let list = refund_bill(state.clone(), bill_id, Some(half), None, None, None)
+            .await
+            .unwrap();
+        assert_eq!(list.refunds.len(), 1);
+        assert_eq!(list.refunds[0].status, "processed");
+        assert!(list.refunds[0].provider_refund_id.is_some());
+        assert_eq!(refunded(&pool, bill_id).await, half);
+        let bill = get_bill_by_id(state.clone(), bill_id.to_string(), None)
+            .await
+            .unwrap();
+        assert_eq!(bill.status, BillStatus::PartiallyRefunded);
+
+        // More than is left is refused before anything is recorded
+        let result = refund_bill(state.clone(), bill_id, Some(bill.amount), None, None, None).await;
+        assert!(matches!(result, Err(AppError::UnProcessableEntity { .. })));
+
+        refund_bill(state.clone(), bill_id, None, None, None, None)
+            .await
+            .unwrap();
+        assert_eq!(refunded(&pool, bill_id).await, bill.amount);
+        let bill = get_bill_by_id(state, bill_id.to_string(), None)
+            .await
+            .unwrap();
+        assert_eq!(bill.status, BillStatus::Refunded);
+    }
+
+    #[sqlx::test]
+    async fn releases_refunds_the_gateway_refuses(pool: PgPool) {
+        let state = Arc::new(AppState::new(
+            pool.clone(),
+            Arc::new(FakeGateway::default()),
+            Arc::new(LogMailer),
+            None,
+            SettingsCipher::default(),
+        ));
+        // The fake gateway never took this charge, so it has nothing to refund
+        let bill_id = paid_bill(&state, &pool, false).await;
+
+        let result = refund_bill(state.clone(), bill_id, None, None, None, None).await;
+        assert!(matches!(result, Err(AppError::NotFound(_))));
+
+        // The refund is kept as failed, and the money can still be refunded
+        let statuses: Vec<String> =
+            sqlx::query_scalar("SELECT status FROM refunds WHERE bill_id = $1")
+                .bind(bill_id)
+                .fetch_all(&pool)
+                .await
+                .unwrap();
+        assert_eq!(statuses, [REFUND_FAILED]);
+        assert_eq!(refunded(&pool, bill_id).await, Money::ZERO);
+        let bill = get_bill_by_id(state, bill_id.to_string(), None)
+            .await
+            .unwrap();
+        assert_eq!(bill.status, BillStatus::Paid);
+    }
+
+    // Moves the visit on a bill `hours_ahead` from now and returns its appointment
+    async fn visit_in(state: &SharedState, bill_id: Uuid, hours_ahead: i64) -> Appointment {
+        let appointment_id: Uuid = sqlx::query_scalar(
+            "UPDATE appointments SET time = NOW() + make_interval(hours => $2) \
+             WHERE id = (SELECT appointment_id FROM bills WHERE id = $1) RETURNING id",
+        )
+        .bind(bill_id)
+        .bind(hours_ahead as i32)
+        .fetch_one(&state.db_pool)
+        .await
+        .unwrap();
+        get_appointment_by_id(state.clone(), appointment_id.to_string(), None)
+            .await
+            .unwrap()
+    }
+
+    #[sqlx::test]
+    async fn refunds_visits_cancelled_in_time(pool: PgPool) {
+        let state = Arc::new(AppState::new(
+            pool.clone(),
+            Arc::new(FakeGateway::default()),
+            Arc::new(LogMailer),
+            None,
+            SettingsCipher::default(),
+        ));
+        let early = paid_bill(&state, &pool, true).await;
+        let late = paid_bill(&state, &pool, true).await;
+
+        let appointment = visit_in(&state, early, REFUND_CUTOFF_HOURS + 1).await;
+        refund_cancelled_appointment(state.clone(), &appointment, None)
+            .await
+            .unwrap();
+        let bill = get_bill_by_id(state.clone(), early.to_string(), None)
+            .await
+            .unwrap();
+        assert_eq!(bill.status, BillStatus::Refunded);
+        assert_eq!(refunded(&pool, early).await, bill.amount);
+        let list = get_refunds(state.clone(), early.to_string(), None)
+            .await
+            .unwrap();
+        assert_eq!(list.refunds.len(), 1);
+        assert_eq!(
+            list.refunds[0].reason.as_deref(),
+            Some("Appointment cancelled")
+        );
+
+        // Inside the cutoff the payment is kept
+        let appointment = visit_in(&state, late, REFUND_CUTOFF_HOURS - 1).await;
+        refund_cancelled_appointment(state.clone(), &appointment, None)
+            .await
+            .unwrap();
+        assert_eq!(refunded(&pool, late).await, Money::ZERO);
+        let bill = get_bill_by_id(state, late.to_string(), None).await.unwrap();
+        assert_eq!(bill.status, BillStatus::Paid);
     }
 }
@@ -1,32 +1,101 @@
 use crate::errors::AppError;
+use crate::money::Money;
+use rust_decimal::Decimal;
 use std::{env, fmt::Display, str::FromStr};
 
 // pub const DEFAULT_REFERENCE_LENGTH: usize = 12;
 
-pub const DEFAULT_APPOINTMENT_PRICE: f64 = 10000.00; // Default price for an appointment
+pub const DEFAULT_APPOINTMENT_PRICE: Money = Money::from_hundredths(1_000_000); // 10000.00
+
+pub const DEFAULT_CURRENCY: &str = "NGN";
+
+pub const VAT_PERCENT: Decimal = Decimal::from_parts(75, 0, 0, false, 1); // 7.5%, on taxable lines only
+
+pub const MAX_SERIES_OCCURRENCES: i32 = 52; // A year of weekly visits
+
+pub const DEFAULT_TIMEZONE: &str = "WAT"; // Used to place calendar days when no offset is given
+
+pub const REFUND_CUTOFF_HOURS: i64 = 24; // Cancelling at least this long before a paid visit refunds it
+
+pub const IDEMPOTENCY_KEY_TTL_HOURS: i64 = 24; // How long a POST response is kept for replaying to retries
+
+pub const IDEMPOTENCY_IN_FLIGHT_SECONDS: i64 = 300; // After this, a request that never finished no longer holds its key
+
+pub const ACCESS_TOKEN_MINUTES: i64 = 15; // Lifetime of a bearer token; clients renew it with their refresh token
+
+pub const REFRESH_TOKEN_DAYS: i64 = 30;
+
+pub const OPAQUE_TOKEN_LENGTH: usize = 64; // Refresh, password reset and email verification tokens
+
+pub const PASSWORD_RESET_TOKEN_MINUTES: i64 = 60;
+
+pub const EMAIL_VERIFICATION_TOKEN_HOURS: i64 = 48;
+
+pub const INVITATION_TOKEN_HOURS: i64 = 72;
+
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+
+pub const DEFAULT_MAILER: &str = "log"; // Or "file" to save emails under MAIL_DIR
+
+pub const DEFAULT_MAIL_DIR: &str = "mail";
+
+pub const DEFAULT_RECONCILE_INTERVAL_SECONDS: u64 = 300;
+
+pub const DEFAULT_PENDING_BILL_MINUTES: i32 = 30; // How long an online payment waits between checks with the gateway
+
+pub const DEFAULT_PAYMENT_GATEWAY: &str = "paystack"; // Or "fake" to take payments in-process
+
+pub const DEFAULT_PAYSTACK_URL: &str = "https://api.paystack.co";
 
 pub struct AppConfig {
     pub database_url: String,
     pub server_port: u16,
+    pub payment_gateway: String,
     pub paystack_url: String, // For billing and payment processing
-    pub paystack_secret_key: String,
+    pub paystack_secret_key: Option<String>, // Only needed with the Paystack gateway
     pub secret_key: String,
+    pub settings_encryption_key: Option<String>, // 64 hex characters; encrypts hospitals' payment keys
+    pub reconcile_interval_seconds: u64, // How often pending bills are checked with Paystack
+    pub pending_bill_minutes: i32,
+    pub mailer: String,
+    pub mail_dir: String,
+    pub app_url: Option<String>, // Front end that emailed links point at
 }
 
 impl AppConfig {
     pub fn from_env() -> Result<Self, AppError> {
         let database_url = get_env_var("DATABASE_URL")?;
         let server_port = get_env_var("SERVER_PORT")?;
-        let paystack_url = get_env_var("PAYSTACK_PAYMENT_URL")?;
-        let paystack_secret_key = get_env_var("PAYSTACK_SECRET_KEY")?;
+        let payment_gateway =
+            get_env_var_or("PAYMENT_GATEWAY", DEFAULT_PAYMENT_GATEWAY.to_string())?;
+        let paystack_url =
+            get_env_var_or("PAYSTACK_PAYMENT_URL", DEFAULT_PAYSTACK_URL.to_string())?;
+        let paystack_secret_key = get_optional_env_var("PAYSTACK_SECRET_KEY")?;
         let secret_key = get_env_var("SECRET_KEY")?;
+        let settings_encryption_key = get_optional_env_var("SETTINGS_ENCRYPTION_KEY")?;
+        let reconcile_interval_seconds = get_env_var_or(
+            "RECONCILE_INTERVAL_SECONDS",
+            DEFAULT_RECONCILE_INTERVAL_SECONDS,
+        )?;
+        let pending_bill_minutes =
+            get_env_var_or("PENDING_BILL_MINUTES", DEFAULT_PENDING_BILL_MINUTES)?;
+        let mailer = get_env_var_or("MAILER", DEFAULT_MAILER.to_string())?;
+        let mail_dir = get_env_var_or("MAIL_DIR", DEFAULT_MAIL_DIR.to_string())?;
+        let app_url = get_optional_env_var("APP_URL")?;
 
         Ok(Self {
             database_url,
             server_port,
+            payment_gateway,
             paystack_url,
             paystack_secret_key,
             secret_key,
+            settings_encryption_key,
+            reconcile_interval_seconds,
+            pending_bill_minutes,
+            mailer,
+            mail_dir,
+            app_url,
         })
     }
 }
@@ -43,3 +112,22 @@ where
         ))
     })
 }
+
+fn get_optional_env_var<T: FromStr>(key: &str) -> Result<Option<T>, AppError>
+where
+    T::Err: Display,
+{
+    match get_env_var(key) {
+        Ok(value) => Ok(Some(value)),
+        Err(AppError::MissingEnvironmentVarible(_)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+// Like get_env_var, but optional settings fall back to `default` when unset
+fn get_env_var_or<T: FromStr>(key: &str, default: T) -> Result<T, AppError>
+where
+    T::Err: Display,
+{
+    Ok(get_optional_env_var(key)?.unwrap_or(default))
+}
@@ -1,4 +1,5 @@
 use crate::app_state::SharedState;
+use crate::auth::headers::ClaimsHeader;
 use crate::doctor::models::CreateDoctor;
 use crate::doctor::service;
 use crate::errors::AppError;
@@ -12,8 +13,11 @@ use serde_json::json;
 use std::collections::HashMap;
 
 // Get all doctors handler
-pub async fn get_all_doctors_handler(State(state): State<SharedState>) -> impl IntoResponse {
-    match service::get_all_doctors(state).await {
+pub async fn get_all_doctors_handler(
+    State(state): State<SharedState>,
+    claims: ClaimsHeader,
+) -> impl IntoResponse {
+    match service::get_all_doctors(state, claims.tenant()).await {
         Ok(doctor_list) => Json(doctor_list).into_response(),
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
@@ -26,9 +30,22 @@ pub async fn get_all_doctors_handler(State(state): State<SharedState>) -> impl I
 // Get doctor by ID handler
 pub async fn get_doctor_by_id_handler(
     State(state): State<SharedState>,
+    claims: ClaimsHeader,
     axum::extract::Path(doctor_id): axum::extract::Path<String>,
 ) -> impl IntoResponse {
     match service::get_doctor_by_id(state, doctor_id).await {
+        // Doctors of other hospitals are reported as missing
+        Ok(doctor)
+            if claims
+                .tenant()
+                .is_some_and(|id| doctor.hospital_id != Some(id)) =>
+        {
+            (
+                StatusCode::NOT_FOUND,
+                Json(json!({"error": "Doctor not found"})),
+            )
+                .into_response()
+        }
         Ok(doctor) => Json(doctor).into_response(),
         Err(e) => match e {
             AppError::DatabaseError(_) => (
@@ -53,21 +70,35 @@ pub async fn get_doctor_by_id_handler(
 // Create a new doctor handler
 pub async fn create_doctor_handler(
     State(state): State<SharedState>,
+    claims: ClaimsHeader,
     Json(payload): Json<CreateDoctor>,
 ) -> impl IntoResponse {
-    match service::create_doctor(state, payload).await {
+    match service::create_doctor(state, payload, claims).await {
         Ok(doctor) => (StatusCode::CREATED, Json(doctor)).into_response(),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({"error": e.to_string()})),
-        )
-            .into_response(),
+        Err(e) => match e {
+            AppError::ParsingError(e) => (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response(),
+            AppError::UnProcessableEntity { field, message } => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(json!({"error": format!("{}: {}", field, message)})),
+            )
+                .into_response(),
+            _ => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response(),
+        },
     }
 }
 
 // Get doctors available on a specific day and time handler
 pub async fn get_available_doctors_handler(
     State(state): State<SharedState>,
+    claims: ClaimsHeader,
     Query(params): Query<HashMap<String, String>>,
 ) -> impl IntoResponse {
     let day = match params.get("day") {
@@ -81,12 +112,21 @@ pub async fn get_available_doctors_handler(
         }
     };
 
-    match service::get_available_doctors(state, day).await {
+    let specialization = params.get("specialization").cloned();
+
+    match service::get_available_doctors(state, day, specialization, claims.tenant()).await {
         Ok(doctor_list) => Json(doctor_list).into_response(),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({"error": e.to_string()})),
-        )
-            .into_response(),
+        Err(e) => match e {
+            AppError::ParsingError(e) => (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response(),
+            _ => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response(),
+        },
     }
 }
@@ -1,24 +1,38 @@
+use crate::utils::{hospital_offset, weekday_name};
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveTime, Timelike};
 use serde::{Deserialize, Serialize};
 use sqlx::prelude::FromRow;
+use sqlx::types::Json;
 use uuid::Uuid;
 
+pub const DEFAULT_SLOT_MINUTES: i32 = 30;
+
 #[derive(Debug, Serialize, FromRow)]
 pub struct Doctor {
     pub id: Uuid,
     pub name: String,
     pub specialization: String,
-    pub visiting_hours: String,
     pub available_days: Vec<String>,
     pub hospital_id: Option<Uuid>,
     pub user_id: Option<Uuid>,
+    pub working_hours: Json<Vec<WorkingWindow>>,
+    pub slot_minutes: i32,
+}
+
+// A block of clinic time on a given weekday, in the hospital's local time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkingWindow {
+    pub day: String,
+    pub start: NaiveTime,
+    pub end: NaiveTime,
 }
 
 #[derive(Deserialize)]
 pub struct CreateDoctor {
     pub name: String,
     pub specialization: String,
-    pub visiting_hours: String,
-    pub available_days: Vec<String>,
+    pub working_hours: Vec<WorkingWindow>,
+    pub slot_minutes: Option<i32>,
     pub hospital_id: Option<Uuid>,
     pub user_id: Option<Uuid>,
 }
@@ -43,14 +57,101 @@ pub struct DoctorList {
 
 impl Doctor {
     pub fn new(data: CreateDoctor) -> Self {
+        // available_days is kept in sync with the windows so day lookups stay a simple ANY()
+        let mut available_days: Vec<String> = Vec::new();
+        for window in &data.working_hours {
+            if !available_days.contains(&window.day) {
+                available_days.push(window.day.clone());
+            }
+        }
         Self {
             id: Uuid::new_v4(),
             name: data.name,
             specialization: data.specialization,
-            visiting_hours: data.visiting_hours,
-            available_days: data.available_days,
+            available_days,
             hospital_id: data.hospital_id,
             user_id: data.user_id,
+            working_hours: Json(data.working_hours),
+            slot_minutes: data.slot_minutes.unwrap_or(DEFAULT_SLOT_MINUTES),
+        }
+    }
+
+    pub fn slot_duration(&self) -> Duration {
+        Duration::minutes(self.slot_minutes as i64)
+    }
+
+    // True when `at` is the start of one of the doctor's slots. Working hours are in hospital
+    // time, so `at` is compared in that offset whichever one it was given in.
+    pub fn has_slot_at(&self, at: &DateTime<FixedOffset>) -> bool {
+        let at = at.with_timezone(&hospital_offset());
+        let day = weekday_name(at.weekday());
+        let time = at.time();
+        if time.second() != 0 || time.nanosecond() != 0 {
+            return false;
+        }
+        self.working_hours.iter().any(|window| {
+            if window.day != day || time < window.start {
+                return false;
+            }
+            let offset = (time - window.start).num_minutes();
+            let slot_end = time.overflowing_add_signed(self.slot_duration()).0;
+            offset % self.slot_minutes as i64 == 0 && slot_end > time && slot_end <= window.end
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn doctor() -> Doctor {
+        Doctor {
+            id: Uuid::new_v4(),
+            name: "Dr Okafor".to_string(),
+            specialization: "General Practice".to_string(),
+            available_days: vec!["Monday".to_string()],
+            hospital_id: None,
+            user_id: None,
+            working_hours: Json(vec![WorkingWindow {
+                day: "Monday".to_string(),
+                start: NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+                end: NaiveTime::from_hms_opt(12, 0, 0).unwrap(),
+            }]),
+            slot_minutes: 30,
+        }
+    }
+
+    #[test]
+    fn compares_slots_in_hospital_time() {
+        let doctor = doctor();
+        // 2026-03-16 is a Monday; working hours are 09:00-12:00 WAT
+        for at in [
+            "2026-03-16T09:00:00+01:00",
+            "2026-03-16T08:00:00+00:00",
+            "2026-03-16T11:30:00+02:00",
+        ] {
+            assert!(
+                doctor.has_slot_at(&DateTime::parse_from_rfc3339(at).unwrap()),
+                "{at}"
+            );
+        }
+        for at in [
+            // 12:30 WAT, after hours
+            "2026-03-16T11:30:00+00:00",
+            // 20:00 WAT
+            "2026-03-16T09:00:00-10:00",
+            // Not on a slot boundary
+            "2026-03-16T09:15:00+01:00",
+        ] {
+            assert!(
+                !doctor.has_slot_at(&DateTime::parse_from_rfc3339(at).unwrap()),
+                "{at}"
+            );
         }
+        // Sunday 21:00 WAT
+        assert!(
+            !doctor
+                .has_slot_at(&DateTime::parse_from_rfc3339("2026-03-16T09:00:00+13:00").unwrap())
+        );
     }
 }
@@ -1,19 +1,31 @@
 use crate::app_state::{AppState, SharedState};
+use crate::auth::permissions::{Permission, require};
 use crate::doctor::handlers::{
     create_doctor_handler, get_all_doctors_handler, get_available_doctors_handler,
     get_doctor_by_id_handler,
 };
+use crate::idempotency::idempotent;
 use axum::Router;
-use axum::routing::get;
+use axum::routing::{get, post};
 use std::sync::Arc;
 
 pub fn doctor_router(state: SharedState) -> Router<Arc<AppState>> {
     Router::new()
-        .route(
-            "/",
-            get(get_all_doctors_handler).post(create_doctor_handler),
-        )
-        .route("/check/available", get(get_available_doctors_handler))
-        .route("/<id>", get(get_doctor_by_id_handler))
+        .merge(require(
+            &state,
+            Permission::ViewDoctors,
+            Router::new()
+                .route("/", get(get_all_doctors_handler))
+                .route("/check/available", get(get_available_doctors_handler))
+                .route("/{id}", get(get_doctor_by_id_handler)),
+        ))
+        .merge(require(
+            &state,
+            Permission::ManageDoctors,
+            idempotent(
+                &state,
+                Router::new().route("/", post(create_doctor_handler)),
+            ),
+        ))
         .with_state(state)
 }
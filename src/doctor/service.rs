@@ -1,14 +1,71 @@
+use crate::admin::service::validate_user_link;
 use crate::app_state::SharedState;
-use crate::doctor::models::{CreateDoctor, Doctor, DoctorList};
+use crate::auth::headers::ClaimsHeader;
+use crate::doctor::models::{CreateDoctor, Doctor, DoctorList, WorkingWindow};
 use crate::errors::AppError;
 use crate::utils::is_valid_day;
+use sqlx::QueryBuilder;
+use uuid::Uuid;
 
-// Get all doctors
-pub async fn get_all_doctors(state: SharedState) -> Result<DoctorList, AppError> {
-    let doctors = sqlx::query_as::<_, Doctor>("SELECT * FROM doctors")
-        .fetch_all(&state.db_pool)
-        .await
-        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+const MIN_SLOT_MINUTES: i32 = 5;
+const MAX_SLOT_MINUTES: i32 = 240;
+
+fn validate_working_hours(windows: &[WorkingWindow], slot_minutes: i32) -> Result<(), AppError> {
+    if !(MIN_SLOT_MINUTES..=MAX_SLOT_MINUTES).contains(&slot_minutes) {
+        return Err(AppError::UnProcessableEntity {
+            field: "slot_minutes".to_string(),
+            message: format!(
+                "Slot length must be between {MIN_SLOT_MINUTES} and {MAX_SLOT_MINUTES} minutes"
+            ),
+        });
+    }
+    if windows.is_empty() {
+        return Err(AppError::UnProcessableEntity {
+            field: "working_hours".to_string(),
+            message: "At least one working window is required".to_string(),
+        });
+    }
+    for (i, window) in windows.iter().enumerate() {
+        if !is_valid_day(&window.day) {
+            return Err(AppError::ParsingError(format!(
+                "Invalid day format in working_hours: {}",
+                window.day
+            )));
+        }
+        if (window.end - window.start).num_minutes() < slot_minutes as i64 {
+            return Err(AppError::UnProcessableEntity {
+                field: "working_hours".to_string(),
+                message: format!(
+                    "Window {}-{} on {} must end after its start and fit at least one slot",
+                    window.start, window.end, window.day
+                ),
+            });
+        }
+        let overlaps = windows[i + 1..].iter().any(|other| {
+            other.day == window.day && other.start < window.end && window.start < other.end
+        });
+        if overlaps {
+            return Err(AppError::UnProcessableEntity {
+                field: "working_hours".to_string(),
+                message: format!("Overlapping working windows on {}", window.day),
+            });
+        }
+    }
+    Ok(())
+}
+
+// Get all doctors of a hospital, or of every hospital when `hospital_id` is None
+pub async fn get_all_doctors(
+    state: SharedState,
+    hospital_id: Option<Uuid>,
+) -> Result<DoctorList, AppError> {
+    let doctors = sqlx::query_as::<_, Doctor>(
+        "SELECT * FROM doctors WHERE ($1::UUID IS NULL OR hospital_id = $1) ORDER BY name",
+    )
+    .bind(hospital_id)
+    .fetch_all(&state.db_pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
     Ok(DoctorList { doctors })
 }
 
@@ -24,45 +81,61 @@ pub async fn get_doctor_by_id(state: SharedState, doctor_id: String) -> Result<D
     Ok(doctor)
 }
 
-// Create a new doctor
+// Create a new doctor in the caller's hospital; only super admins choose the hospital
 pub async fn create_doctor(
     state: SharedState,
     doctor_data: CreateDoctor,
+    claims: ClaimsHeader,
 ) -> Result<Doctor, AppError> {
-    let doctor = Doctor::new(doctor_data);
-    let all_days_valid = doctor.available_days.iter().all(|day| is_valid_day(day));
-    if !all_days_valid {
-        return Err(AppError::ParsingError(
-            "One or more invalid day formats in available_days".to_string(),
-        ));
+    let mut doctor = Doctor::new(doctor_data);
+    if let Some(hospital_id) = claims.tenant() {
+        doctor.hospital_id = Some(hospital_id);
     }
-    sqlx::query("INSERT INTO doctors (id, name, specialization, visiting_hours, available_days) VALUES ($1, $2, $3, $4, $5)")
+    validate_working_hours(&doctor.working_hours, doctor.slot_minutes)?;
+    validate_user_link(&state, doctor.user_id, doctor.hospital_id).await?;
+    sqlx::query("INSERT INTO doctors (id, name, specialization, available_days, working_hours, slot_minutes, hospital_id, user_id) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)")
         .bind(doctor.id)
         .bind(&doctor.name)
         .bind(&doctor.specialization)
-        .bind(&doctor.visiting_hours)
         .bind(&doctor.available_days as &[String])
+        .bind(&doctor.working_hours)
+        .bind(doctor.slot_minutes)
+        .bind(doctor.hospital_id)
+        .bind(doctor.user_id)
         .execute(&state.db_pool)
         .await
         .map_err(|e| AppError::DatabaseError(e.to_string()))?;
     Ok(doctor)
 }
 
-// Get doctors available on a specific day and time
+// Get doctors available on a specific day and time, optionally narrowed to a specialization
 // This will be used for appointment scheduling to show available doctors based on the selected day and time
 pub async fn get_available_doctors(
     state: SharedState,
     day: String,
+    specialization: Option<String>,
+    hospital_id: Option<Uuid>,
 ) -> Result<DoctorList, AppError> {
     if !is_valid_day(&day) {
         return Err(AppError::ParsingError("Invalid day format".to_string()));
     }
-    let doctors =
-        sqlx::query_as::<_, Doctor>("SELECT * FROM doctors WHERE $1 = ANY(available_days)")
-            .bind(day)
-            .fetch_all(&state.db_pool)
-            .await
-            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    let mut builder = QueryBuilder::new("SELECT * FROM doctors WHERE ");
+    builder.push_bind(day);
+    builder.push(" = ANY(available_days)");
+    if let Some(hospital_id) = hospital_id {
+        builder.push(" AND hospital_id = ");
+        builder.push_bind(hospital_id);
+    }
+    if let Some(specialization) = specialization {
+        builder.push(" AND LOWER(specialization) = LOWER(");
+        builder.push_bind(specialization);
+        builder.push(")");
+    }
+    let doctors = builder
+        .build_query_as::<Doctor>()
+        .fetch_all(&state.db_pool)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
     Ok(DoctorList { doctors })
 }
 
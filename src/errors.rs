@@ -19,6 +19,10 @@ pub enum AppError {
     DatabaseError(String),
     #[error("Unauthorized: {0}")]
     Unauthorized(String),
+    #[error("Conflict: {0}")]
+    Conflict(String),
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
 }
 
 impl IntoResponse for AppError {
@@ -26,6 +30,8 @@ impl IntoResponse for AppError {
         let (status, message) = match &self {
             AppError::NotFound(msg) => (StatusCode::NOT_FOUND, msg.clone()),
             AppError::Unauthorized(msg) => (StatusCode::UNAUTHORIZED, msg.clone()),
+            AppError::Conflict(msg) => (StatusCode::CONFLICT, msg.clone()),
+            AppError::Forbidden(msg) => (StatusCode::FORBIDDEN, msg.clone()),
             AppError::UnProcessableEntity { field, message } => (
                 StatusCode::UNPROCESSABLE_ENTITY,
                 format!("{field} - {message}"),
@@ -0,0 +1,367 @@
+// Idempotency-Key support for the create endpoints. The first response for a user's key is
+// stored and replayed to retries of the same request, so a client on a flaky network can safely
+// resend e.g. a booking or a bill without creating it twice.
+use crate::app_state::SharedState;
+use crate::auth::headers::ClaimsHeader;
+use crate::config::{IDEMPOTENCY_IN_FLIGHT_SECONDS, IDEMPOTENCY_KEY_TTL_HOURS};
+use axum::{
+    Json, Router,
+    body::{Body, HttpBody, to_bytes},
+    extract::{FromRequestParts, Request, State},
+    http::{HeaderName, HeaderValue, Method, StatusCode, header},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Duration, Utc};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use sqlx::FromRow;
+use std::time::Duration as StdDuration;
+use tracing::warn;
+use uuid::Uuid;
+
+const IDEMPOTENCY_KEY: HeaderName = HeaderName::from_static("idempotency-key");
+const IDEMPOTENCY_REPLAYED: HeaderName = HeaderName::from_static("idempotency-replayed");
+const MAX_KEY_LENGTH: usize = 255;
+const MAX_BODY_BYTES: usize = 2 * 1024 * 1024; // Axum's default request body limit; responses too
+
+#[derive(FromRow)]
+struct StoredResponse {
+    request_hash: String,
+    response_status: Option<i16>,
+    response_content_type: Option<String>,
+    response_body: Option<Vec<u8>>,
+    created_at: DateTime<Utc>,
+}
+
+fn error_response(status: StatusCode, message: &str) -> Response {
+    (status, Json(json!({"error": message}))).into_response()
+}
+
+// Puts the POST routes in `routes` behind Idempotency-Key support. Wrap it in `require`, so
+// only signed-in users get here: responses such as logins are never stored.
+pub fn idempotent<S>(state: &SharedState, routes: Router<S>) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    routes.route_layer(middleware::from_fn_with_state(state.clone(), idempotency))
+}
+
+// Requests that are not POSTs or carry no key pass straight through. Keys are scoped to the
+// user in the bearer token.
+async fn idempotency(State(state): State<SharedState>, request: Request, next: Next) -> Response {
+    if request.method() != Method::POST {
+        return next.run(request).await;
+    }
+    let key = match request.headers().get(&IDEMPOTENCY_KEY) {
+        None => return next.run(request).await,
+        Some(value) => match value.to_str() {
+            Ok(key) if !key.trim().is_empty() && key.len() <= MAX_KEY_LENGTH => {
+                key.trim().to_string()
+            }
+            _ => {
+                return error_response(
+                    StatusCode::BAD_REQUEST,
+                    "Idempotency-Key must be 1 to 255 visible characters",
+                );
+            }
+        },
+    };
+
+    let (mut parts, body) = request.into_parts();
+    let user_id = match ClaimsHeader::from_request_parts(&mut parts, &state).await {
+        Ok(claims) => claims.sub,
+        Err(e) => return e.into_response(),
+    };
+    let body = match to_bytes(body, MAX_BODY_BYTES).await {
+        Ok(body) => body,
+        Err(_) => {
+            return error_response(StatusCode::PAYLOAD_TOO_LARGE, "Request body is too large");
+        }
+    };
+    let mut hasher = Sha256::new();
+    hasher.update(parts.method.as_str());
+    hasher.update(b" ");
+    hasher.update(parts.uri.to_string());
+    hasher.update(b"\n");
+    hasher.update(&body);
+    let request_hash = hex::encode(hasher.finalize());
+
+    match claim_key(&state, user_id, &key, &request_hash).await {
+        Ok(None) => {}
+        Ok(Some(stored)) => return replay(stored, &request_hash),
+        Err(e) => {
+            warn!(error = %e, "could not look up idempotency key");
+            return error_response(StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error");
+        }
+    }
+
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+    if response
+        .body()
+        .size_hint()
+        .upper()
+        .is_none_or(|size| size > MAX_BODY_BYTES as u64)
+    {
+        // Only small, complete bodies are kept; a retry of this request runs again
+        warn!("response too large to keep for idempotency key");
+        release_key(&state, user_id, &key).await;
+        return response;
+    }
+    let (parts, body) = response.into_parts();
+    let body = match to_bytes(body, MAX_BODY_BYTES).await {
+        Ok(body) => body,
+        Err(e) => {
+            warn!(error = %e, "could not read response for idempotency key");
+            release_key(&state, user_id, &key).await;
+            return error_response(StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error");
+        }
+    };
+
+    // Server errors are not kept, so the client can retry once we have recovered
+    if parts.status.is_server_error() {
+        release_key(&state, user_id, &key).await;
+    } else {
+        let content_type = parts
+            .headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok());
+        let stored = sqlx::query(
+            "UPDATE idempotency_keys SET response_status = $1, response_content_type = $2, response_body = $3 WHERE user_id = $4 AND key = $5",
+        )
+        .bind(parts.status.as_u16() as i16)
+        .bind(content_type)
+        .bind(body.as_ref())
+        .bind(user_id)
+        .bind(&key)
+        .execute(&state.db_pool)
+        .await;
+        if let Err(e) = stored {
+            warn!(error = %e, "could not store response for idempotency key");
+        }
+    }
+    Response::from_parts(parts, Body::from(body))
+}
+
+// Reserves the key for this request. Returns None when the caller should go ahead, or what is
+// already stored for the key. Expired keys, and keys held by a request that never finished,
+// are taken over.
+async fn claim_key(
+    state: &SharedState,
+    user_id: Uuid,
+    key: &str,
+    request_hash: &str,
+) -> Result<Option<StoredResponse>, sqlx::Error> {
+    let claimed = sqlx::query(
+        "INSERT INTO idempotency_keys (user_id, key, request_hash, expires_at) VALUES ($1, $2, $3, $4) \
+         ON CONFLICT (user_id, key) DO UPDATE SET request_hash = EXCLUDED.request_hash, \
+         response_status = NULL, response_content_type = NULL, response_body = NULL, \
+         created_at = NOW(), expires_at = EXCLUDED.expires_at \
+         WHERE idempotency_keys.expires_at < NOW() \
+         OR (idempotency_keys.response_status IS NULL AND idempotency_keys.created_at < $5)",
+    )
+    .bind(user_id)
+    .bind(key)
+    .bind(request_hash)
+    .bind(Utc::now() + Duration::hours(IDEMPOTENCY_KEY_TTL_HOURS))
+    .bind(Utc::now() - Duration::seconds(IDEMPOTENCY_IN_FLIGHT_SECONDS))
+    .execute(&state.db_pool)
+    .await?;
+    if claimed.rows_affected() == 1 {
+        return Ok(None);
+    }
+    sqlx::query_as::<_, StoredResponse>(
+        "SELECT request_hash, response_status, response_content_type, response_body, created_at \
+         FROM idempotency_keys WHERE user_id = $1 AND key = $2",
+    )
+    .bind(user_id)
+    .bind(key)
+    .fetch_optional(&state.db_pool)
+    .await
+}
+
+async fn release_key(state: &SharedState, user_id: Uuid, key: &str) {
+    let released = sqlx::query(
+        "DELETE FROM idempotency_keys WHERE user_id = $1 AND key = $2 AND response_status IS NULL",
+    )
+    .bind(user_id)
+    .bind(key)
+    .execute(&state.db_pool)
+    .await;
+    if let Err(e) = released {
+        warn!(error = %e, "could not release idempotency key");
+    }
+}
+
+fn replay(stored: StoredResponse, request_hash: &str) -> Response {
+    if stored.request_hash != request_hash {
+        return error_response(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "Idempotency-Key has already been used with a different request",
+        );
+    }
+    let (Some(status), Some(body)) = (stored.response_status, stored.response_body) else {
+        let retry_after = (stored.created_at + Duration::seconds(IDEMPOTENCY_IN_FLIGHT_SECONDS)
+            - Utc::now())
+        .num_seconds()
+        .clamp(1, IDEMPOTENCY_IN_FLIGHT_SECONDS);
+        let mut response = error_response(
+            StatusCode::CONFLICT,
+            "A request with this Idempotency-Key is still being processed",
+        );
+        response
+            .headers_mut()
+            .insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+        return response;
+    };
+
+    let mut response = Response::new(Body::from(body));
+    *response.status_mut() =
+        StatusCode::from_u16(status as u16).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    if let Some(content_type) = stored
+        .response_content_type
+        .and_then(|value| HeaderValue::from_str(&value).ok())
+    {
+        response
+            .headers_mut()
+            .insert(header::CONTENT_TYPE, content_type);
+    }
+    response
+        .headers_mut()
+        .insert(IDEMPOTENCY_REPLAYED, HeaderValue::from_static("true"));
+    response
+}
+
+// Background task started with the server; drops keys past their TTL
+pub async fn purge_expired_keys(state: SharedState) {
+    let mut interval = tokio::time::interval(StdDuration::from_secs(3600));
+    loop {
+        interval.tick().await;
+        let purged = sqlx::query("DELETE FROM idempotency_keys WHERE expires_at < NOW()")
+            .execute(&state.db_pool)
+            .await;
+        if let Err(e) = purged {
+            warn!(error = %e, "could not purge expired idempotency keys");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::admin::models::UserRole;
+    use crate::test_support::TestApp;
+    use reqwest::{Method, StatusCode};
+    use serde_json::{Value, json};
+    use sqlx::PgPool;
+
+    fn patient(name: &str) -> Value {
+        json!({"name": name, "age": 30, "gender": "Female"})
+    }
+
+    async fn create_patient(
+        app: &TestApp,
+        token: &str,
+        key: &str,
+        body: Value,
+    ) -> reqwest::Response {
+        app.request(Method::POST, token, "/patients")
+            .header("Idempotency-Key", key)
+            .json(&body)
+            .send()
+            .await
+            .unwrap()
+    }
+
+    async fn count(app: &TestApp, query: &str) -> i64 {
+        sqlx::query_scalar(query)
+            .fetch_one(&app.pool)
+            .await
+            .unwrap()
+    }
+
+    #[sqlx::test]
+    async fn replays_the_first_response_to_retries(pool: PgPool) {
+        let app = TestApp::spawn(pool).await;
+        let hospital = app.create_hospital("Lagos General").await;
+        let token = app.login_as(hospital.id, UserRole::Receptionist).await;
+
+        let first = create_patient(&app, &token, "retry-1", patient("Ada Obi")).await;
+        assert_eq!(first.status(), StatusCode::CREATED);
+        assert!(first.headers().get("idempotency-replayed").is_none());
+        let first: Value = first.json().await.unwrap();
+
+        let retry = create_patient(&app, &token, "retry-1", patient("Ada Obi")).await;
+        assert_eq!(retry.status(), StatusCode::CREATED);
+        assert_eq!(retry.headers()["idempotency-replayed"], "true");
+        let retry: Value = retry.json().await.unwrap();
+        assert_eq!(retry["id"], first["id"]);
+        assert_eq!(
+            count(&app, "SELECT COUNT(*) FROM patients WHERE name = 'Ada Obi'").await,
+            1
+        );
+
+        // Keys belong to a user, so someone else's request with the same key runs
+        let other = app.login_as(hospital.id, UserRole::Receptionist).await;
+        let response = create_patient(&app, &other, "retry-1", patient("Ada Obi")).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert!(response.headers().get("idempotency-replayed").is_none());
+        assert_eq!(
+            count(&app, "SELECT COUNT(*) FROM patients WHERE name = 'Ada Obi'").await,
+            2
+        );
+    }
+
+    #[sqlx::test]
+    async fn rejects_a_key_reused_for_another_request(pool: PgPool) {
+        let app = TestApp::spawn(pool).await;
+        let hospital = app.create_hospital("Lagos General").await;
+        let token = app.login_as(hospital.id, UserRole::Receptionist).await;
+
+        let first = create_patient(&app, &token, "reused", patient("Ada Obi")).await;
+        assert_eq!(first.status(), StatusCode::CREATED);
+        let second = create_patient(&app, &token, "reused", patient("Chidi Obi")).await;
+        assert_eq!(second.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(
+            count(
+                &app,
+                "SELECT COUNT(*) FROM patients WHERE name = 'Chidi Obi'"
+            )
+            .await,
+            0
+        );
+
+        let blank = create_patient(&app, &token, " ", patient("Chidi Obi")).await;
+        assert_eq!(blank.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[sqlx::test]
+    async fn keeps_nothing_for_anonymous_requests(pool: PgPool) {
+        let app = TestApp::spawn(pool).await;
+
+        let response = app
+            .client
+            .post(format!("{}/auth/login", app.base_url))
+            .header("Idempotency-Key", "login-1")
+            .json(&json!({"email": "nobody@example.com", "password": "password123"}))
+            .send()
+            .await
+            .unwrap();
+        assert_ne!(response.status(), StatusCode::OK);
+
+        // Create endpoints still need a token, key or not
+        let response = app
+            .client
+            .post(format!("{}/patients", app.base_url))
+            .header("Idempotency-Key", "anonymous-1")
+            .json(&patient("Ada Obi"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        assert_eq!(
+            count(&app, "SELECT COUNT(*) FROM idempotency_keys").await,
+            0
+        );
+    }
+}
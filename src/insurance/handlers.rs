@@ -0,0 +1,302 @@
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::{StatusCode, header},
+    response::IntoResponse,
+};
+use chrono::NaiveDate;
+use serde_json::json;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::app_state::SharedState;
+use crate::auth::headers::ClaimsHeader;
+use crate::auth::permissions::ensure_patient_access;
+use crate::errors::AppError;
+use crate::insurance::models::{
+    ClaimStatus, CreateInsurancePolicy, CreateInsuranceProvider, UpdateClaimStatus,
+};
+use crate::insurance::service;
+
+pub async fn create_provider_handler(
+    State(state): State<SharedState>,
+    claims: ClaimsHeader,
+    Json(payload): Json<CreateInsuranceProvider>,
+) -> impl IntoResponse {
+    match service::create_provider(state, payload, claims).await {
+        Ok(provider) => (StatusCode::CREATED, Json(provider)).into_response(),
+        Err(e) => match e {
+            AppError::Unauthorized(e) => {
+                (StatusCode::UNAUTHORIZED, Json(json!({"error": e}))).into_response()
+            }
+            AppError::UnProcessableEntity { field, message } => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(json!({"error": format!("{}: {}", field, message)})),
+            )
+                .into_response(),
+            AppError::Conflict(e) => {
+                (StatusCode::CONFLICT, Json(json!({"error": e}))).into_response()
+            }
+            _ => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response(),
+        },
+    }
+}
+
+pub async fn get_providers_handler(
+    State(state): State<SharedState>,
+    claims: ClaimsHeader,
+) -> impl IntoResponse {
+    match service::get_providers(state, claims).await {
+        Ok(providers) => Json(providers).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn create_policy_handler(
+    State(state): State<SharedState>,
+    claims: ClaimsHeader,
+    Json(payload): Json<CreateInsurancePolicy>,
+) -> impl IntoResponse {
+    if let Err(e) = ensure_patient_access(&state, &claims, payload.patient_id).await {
+        return e.into_response();
+    }
+    match service::create_policy(state, payload, claims).await {
+        Ok(policy) => (StatusCode::CREATED, Json(policy)).into_response(),
+        Err(e) => match e {
+            AppError::NotFound(e) => {
+                (StatusCode::NOT_FOUND, Json(json!({"error": e}))).into_response()
+            }
+            AppError::UnProcessableEntity { field, message } => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(json!({"error": format!("{}: {}", field, message)})),
+            )
+                .into_response(),
+            AppError::Conflict(e) => {
+                (StatusCode::CONFLICT, Json(json!({"error": e}))).into_response()
+            }
+            _ => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response(),
+        },
+    }
+}
+
+pub async fn get_policies_handler(
+    State(state): State<SharedState>,
+    claims: ClaimsHeader,
+    Query(params): Query<HashMap<String, String>>,
+) -> impl IntoResponse {
+    let patient_id = match params.get("patient_id").map(|s| Uuid::parse_str(s)) {
+        Some(Ok(id)) => Some(id),
+        Some(Err(e)) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": format!("Invalid patient_id: {}", e)})),
+            )
+                .into_response();
+        }
+        None => None,
+    };
+
+    match service::get_policies(state, patient_id, claims).await {
+        Ok(policies) => Json(policies).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn get_claims_handler(
+    State(state): State<SharedState>,
+    claims: ClaimsHeader,
+    Query(params): Query<HashMap<String, String>>,
+) -> impl IntoResponse {
+    let provider_id = match params.get("provider_id").map(|s| Uuid::parse_str(s)) {
+        Some(Ok(id)) => Some(id),
+        Some(Err(e)) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": format!("Invalid provider_id: {}", e)})),
+            )
+                .into_response();
+        }
+        None => None,
+    };
+    let status = match params
+        .get("status")
+        .map(|s| serde_json::from_value::<ClaimStatus>(json!(s)))
+    {
+        Some(Ok(status)) => Some(status),
+        Some(Err(_)) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "Invalid status"})),
+            )
+                .into_response();
+        }
+        None => None,
+    };
+
+    match service::get_claims(state, provider_id, status, claims).await {
+        Ok(claims) => Json(claims).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn get_claim_by_id_handler(
+    State(state): State<SharedState>,
+    claims: ClaimsHeader,
+    Path(claim_id): Path<String>,
+) -> impl IntoResponse {
+    let claim_id = match Uuid::parse_str(&claim_id) {
+        Ok(id) => id,
+        Err(_) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "Invalid claim ID format"})),
+            )
+                .into_response();
+        }
+    };
+
+    match service::get_claim_by_id(state, claim_id, claims).await {
+        Ok(claim) => Json(claim).into_response(),
+        Err(e) => match e {
+            AppError::NotFound(e) => {
+                (StatusCode::NOT_FOUND, Json(json!({"error": e}))).into_response()
+            }
+            _ => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response(),
+        },
+    }
+}
+
+pub async fn update_claim_status_handler(
+    State(state): State<SharedState>,
+    claims: ClaimsHeader,
+    Path(claim_id): Path<String>,
+    Json(payload): Json<UpdateClaimStatus>,
+) -> impl IntoResponse {
+    let claim_id = match Uuid::parse_str(&claim_id) {
+        Ok(id) => id,
+        Err(_) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "Invalid claim ID format"})),
+            )
+                .into_response();
+        }
+    };
+
+    match service::update_claim_status(state, claim_id, payload, claims).await {
+        Ok(claim) => Json(claim).into_response(),
+        Err(e) => match e {
+            AppError::NotFound(e) => {
+                (StatusCode::NOT_FOUND, Json(json!({"error": e}))).into_response()
+            }
+            AppError::UnProcessableEntity { field, message } => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(json!({"error": format!("{}: {}", field, message)})),
+            )
+                .into_response(),
+            AppError::Conflict(e) => {
+                (StatusCode::CONFLICT, Json(json!({"error": e}))).into_response()
+            }
+            _ => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response(),
+        },
+    }
+}
+
+// CSV of a provider's claims for services from `from` to `to` (YYYY-MM-DD, inclusive)
+pub async fn export_claims_handler(
+    State(state): State<SharedState>,
+    claims: ClaimsHeader,
+    Path(provider_id): Path<String>,
+    Query(params): Query<HashMap<String, String>>,
+) -> impl IntoResponse {
+    let provider_id = match Uuid::parse_str(&provider_id) {
+        Ok(id) => id,
+        Err(_) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "Invalid provider ID format"})),
+            )
+                .into_response();
+        }
+    };
+    let mut period = Vec::with_capacity(2);
+    for name in ["from", "to"] {
+        match params
+            .get(name)
+            .map(|s| NaiveDate::parse_from_str(s, "%Y-%m-%d"))
+        {
+            Some(Ok(date)) => period.push(date),
+            Some(Err(e)) => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({"error": format!("Invalid {}: {}", name, e)})),
+                )
+                    .into_response();
+            }
+            None => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({"error": format!("Missing '{}' query parameter", name)})),
+                )
+                    .into_response();
+            }
+        }
+    }
+
+    match service::export_claims(state, provider_id, period[0], period[1], claims).await {
+        Ok(export) => (
+            [
+                (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"{}\"", export.filename),
+                ),
+            ],
+            export.csv,
+        )
+            .into_response(),
+        Err(e) => match e {
+            AppError::NotFound(e) => {
+                (StatusCode::NOT_FOUND, Json(json!({"error": e}))).into_response()
+            }
+            AppError::UnProcessableEntity { field, message } => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(json!({"error": format!("{}: {}", field, message)})),
+            )
+                .into_response(),
+            _ => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response(),
+        },
+    }
+}
@@ -0,0 +1,169 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
+use sqlx::{FromRow, Type};
+use uuid::Uuid;
+
+use crate::billing::models::ItemKind;
+use crate::money::Money;
+
+#[derive(Serialize, FromRow)]
+pub struct InsuranceProvider {
+    pub id: Uuid,
+    pub hospital_id: Uuid,
+    pub name: String,
+    pub code: String, // The HMO's short code, unique per hospital
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateInsuranceProvider {
+    pub name: String,
+    pub code: String,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct InsuranceProviderList {
+    pub providers: Vec<InsuranceProvider>,
+}
+
+// Share of a kind of line item the insurer pays. Kinds without a rule are not covered.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CoverageRule {
+    pub kind: ItemKind,
+    pub percent: Decimal,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct InsurancePolicy {
+    pub id: Uuid,
+    pub patient_id: Uuid,
+    pub provider_id: Uuid,
+    pub policy_number: String,
+    pub coverage: Json<Vec<CoverageRule>>,
+    pub annual_limit: Option<Money>, // Most the insurer pays per calendar year
+    pub valid_from: NaiveDate,
+    pub valid_until: Option<NaiveDate>,
+    pub is_active: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateInsurancePolicy {
+    pub patient_id: Uuid,
+    pub provider_id: Uuid,
+    pub policy_number: String,
+    pub coverage: Vec<CoverageRule>,
+    pub annual_limit: Option<Money>,
+    pub valid_from: NaiveDate,
+    pub valid_until: Option<NaiveDate>,
+}
+
+#[derive(Serialize)]
+pub struct InsurancePolicyList {
+    pub policies: Vec<InsurancePolicy>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[sqlx(type_name = "VARCHAR", rename_all = "PascalCase")]
+pub enum ClaimStatus {
+    Pending, // Opened with the bill, not yet sent to the HMO
+    Submitted,
+    Approved,
+    Rejected,
+    Paid,
+    Withdrawn, // The bill was cancelled before the claim was sent
+}
+
+impl ClaimStatus {
+    // Pending -> Submitted -> Approved -> Paid, or Submitted -> Rejected.
+    // Paid, Rejected and Withdrawn are final.
+    pub fn can_transition_to(self, next: Self) -> bool {
+        use ClaimStatus::*;
+        matches!(
+            (self, next),
+            (Pending, Submitted) | (Submitted, Approved | Rejected) | (Approved, Paid)
+        )
+    }
+}
+
+#[derive(Serialize, FromRow)]
+pub struct InsuranceClaim {
+    pub id: Uuid,
+    pub bill_id: Uuid,
+    pub policy_id: Uuid,
+    pub provider_id: Uuid,
+    pub amount: Money, // What was claimed: the bill's insurer portion
+    pub approved_amount: Option<Money>, // What the HMO agreed to pay
+    pub status: ClaimStatus,
+    pub reason: Option<String>,
+    pub submitted_at: Option<DateTime<Utc>>,
+    pub decided_at: Option<DateTime<Utc>>,
+    pub paid_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateClaimStatus {
+    pub status: ClaimStatus,
+    pub approved_amount: Option<Money>, // When approving; defaults to the full claim
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct InsuranceClaimList {
+    pub claims: Vec<InsuranceClaim>,
+}
+
+// A claim as it appears in an HMO's batch export
+#[derive(FromRow)]
+pub struct ClaimExportRow {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub bill_reference: String,
+    pub service_date: DateTime<Utc>,
+    pub patient_name: String,
+    pub card_id: String,
+    pub policy_number: String,
+    pub currency: String,
+    pub bill_amount: Money,
+    pub amount: Money,
+    pub approved_amount: Option<Money>,
+    pub status: ClaimStatus,
+}
+
+// A batch of claims ready to send to an HMO
+pub struct ClaimExport {
+    pub filename: String,
+    pub csv: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ClaimStatus::{self, *};
+
+    #[test]
+    fn allows_only_the_claim_process() {
+        let all = [Pending, Submitted, Approved, Rejected, Paid, Withdrawn];
+        let allowed = [
+            (Pending, Submitted),
+            (Submitted, Approved),
+            (Submitted, Rejected),
+            (Approved, Paid),
+        ];
+        for from in all {
+            for to in all {
+                assert_eq!(
+                    ClaimStatus::can_transition_to(from, to),
+                    allowed.contains(&(from, to)),
+                    "{from:?} -> {to:?}"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use crate::app_state::{AppState, SharedState};
+use crate::auth::permissions::{Permission, require};
+use crate::idempotency::idempotent;
+use crate::insurance::handlers::{
+    create_policy_handler, create_provider_handler, export_claims_handler, get_claim_by_id_handler,
+    get_claims_handler, get_policies_handler, get_providers_handler, update_claim_status_handler,
+};
+use axum::Router;
+use axum::routing::{get, post, put};
+use std::sync::Arc;
+
+pub fn insurance_router(state: SharedState) -> Router<Arc<AppState>> {
+    Router::new()
+        .merge(require(
+            &state,
+            Permission::ViewInsurance,
+            Router::new()
+                .route("/providers", get(get_providers_handler))
+                .route("/policies", get(get_policies_handler))
+                .route("/claims", get(get_claims_handler))
+                .route("/claims/{id}", get(get_claim_by_id_handler)),
+        ))
+        .merge(require(
+            &state,
+            Permission::ManagePolicies,
+            idempotent(
+                &state,
+                Router::new().route("/policies", post(create_policy_handler)),
+            ),
+        ))
+        .merge(require(
+            &state,
+            Permission::ManageClaims,
+            Router::new()
+                .route("/providers/{id}/claims/export", get(export_claims_handler))
+                .route("/claims/{id}/status", put(update_claim_status_handler))
+                .merge(idempotent(
+                    &state,
+                    Router::new().route("/providers", post(create_provider_handler)),
+                )),
+        ))
+        .with_state(state)
+}
@@ -0,0 +1,752 @@
+use chrono::{Datelike, Duration, NaiveDate, Utc};
+use rust_decimal::Decimal;
+use sqlx::PgConnection;
+use sqlx::types::Json;
+use std::collections::HashSet;
+use std::fmt::Write;
+use uuid::Uuid;
+
+use crate::admin::models::UserRole;
+use crate::app_state::SharedState;
+use crate::auth::headers::ClaimsHeader;
+use crate::billing::models::{Bill, BillItem, BillStatus};
+use crate::billing::service::set_insurer_amount;
+use crate::errors::AppError;
+use crate::insurance::models::{
+    ClaimExport, ClaimExportRow, ClaimStatus, CreateInsurancePolicy, CreateInsuranceProvider,
+    InsuranceClaim, InsuranceClaimList, InsurancePolicy, InsurancePolicyList, InsuranceProvider,
+    InsuranceProviderList, UpdateClaimStatus,
+};
+use crate::money::Money;
+
+pub async fn create_provider(
+    state: SharedState,
+    data: CreateInsuranceProvider,
+    claims: ClaimsHeader,
+) -> Result<InsuranceProvider, AppError> {
+    if !matches!(claims.role, UserRole::Admin) {
+        return Err(AppError::Unauthorized(
+            "Only admin users can add insurance providers".to_string(),
+        ));
+    }
+    let name = data.name.trim();
+    let code = data.code.trim().to_uppercase();
+    if name.is_empty() || code.is_empty() {
+        return Err(AppError::UnProcessableEntity {
+            field: if name.is_empty() { "name" } else { "code" }.to_string(),
+            message: "Name and code are required".to_string(),
+        });
+    }
+
+    sqlx::query_as::<_, InsuranceProvider>(
+        "INSERT INTO insurance_providers (id, hospital_id, name, code, email, phone) VALUES ($1, $2, $3, $4, $5, $6) RETURNING *",
+    )
+    .bind(Uuid::new_v4())
+    .bind(claims.hospital_id)
+    .bind(name)
+    .bind(&code)
+    .bind(data.email)
+    .bind(data.phone)
+    .fetch_one(&state.db_pool)
+    .await
+    .map_err(|e| match e.as_database_error() {
+        Some(db_error) if db_error.is_unique_violation() => {
+            AppError::Conflict(format!("A provider with code {code} already exists"))
+        }
+        _ => AppError::DatabaseError(e.to_string()),
+    })
+}
+
+pub async fn get_providers(
+    state: SharedState,
+    claims: ClaimsHeader,
+) -> Result<InsuranceProviderList, AppError> {
+    let providers = sqlx::query_as::<_, InsuranceProvider>(
+        "SELECT * FROM insurance_providers WHERE hospital_id = $1 ORDER BY name",
+    )
+    .bind(claims.hospital_id)
+    .fetch_all(&state.db_pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    Ok(InsuranceProviderList { providers })
+}
+
+// Providers are only visible to the hospital that contracts with them
+async fn get_provider(
+    state: &SharedState,
+    provider_id: Uuid,
+    hospital_id: Uuid,
+) -> Result<InsuranceProvider, AppError> {
+    sqlx::query_as::<_, InsuranceProvider>(
+        "SELECT * FROM insurance_providers WHERE id = $1 AND hospital_id = $2",
+    )
+    .bind(provider_id)
+    .bind(hospital_id)
+    .fetch_optional(&state.db_pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?
+    .ok_or_else(|| AppError::NotFound(format!("Insurance provider {provider_id} not found")))
+}
+
+pub async fn create_policy(
+    state: SharedState,
+    data: CreateInsurancePolicy,
+    claims: ClaimsHeader,
+) -> Result<InsurancePolicy, AppError> {
+    get_provider(&state, data.provider_id, claims.hospital_id).await?;
+    validate_policy(&data)?;
+
+    sqlx::query_as::<_, InsurancePolicy>(
+        "INSERT INTO insurance_policies (id, patient_id, provider_id, policy_number, coverage, annual_limit, valid_from, valid_until) VALUES ($1, $2, $3, $4, $5, $6, $7, $8) RETURNING *",
+    )
+    .bind(Uuid::new_v4())
+    .bind(data.patient_id)
+    .bind(data.provider_id)
+    .bind(data.policy_number.trim())
+    .bind(Json(&data.coverage))
+    .bind(data.annual_limit)
+    .bind(data.valid_from)
+    .bind(data.valid_until)
+    .fetch_one(&state.db_pool)
+    .await
+    .map_err(|e| match e.as_database_error() {
+        Some(db_error) if db_error.is_unique_violation() => AppError::Conflict(format!(
+            "Policy {} is already registered with this provider",
+            data.policy_number.trim()
+        )),
+        Some(db_error) if db_error.is_foreign_key_violation() => {
+            AppError::NotFound(format!("Patient {} not found", data.patient_id))
+        }
+        _ => AppError::DatabaseError(e.to_string()),
+    })
+}
+
+fn validate_policy(data: &CreateInsurancePolicy) -> Result<(), AppError> {
+    if data.policy_number.trim().is_empty() {
+        return Err(AppError::UnProcessableEntity {
+            field: "policy_number".to_string(),
+            message: "Policy number is required".to_string(),
+        });
+    }
+    if data.coverage.is_empty() {
+        return Err(AppError::UnProcessableEntity {
+            field: "coverage".to_string(),
+            message: "A policy must cover at least one kind of item".to_string(),
+        });
+    }
+    let mut kinds = HashSet::new();
+    for rule in &data.coverage {
+        if rule.percent <= Decimal::ZERO || rule.percent > Decimal::ONE_HUNDRED {
+            return Err(AppError::UnProcessableEntity {
+                field: "coverage".to_string(),
+                message: format!(
+                    "Coverage for {:?} must be more than 0 and at most 100 percent",
+                    rule.kind
+                ),
+            });
+        }
+        if !kinds.insert(rule.kind) {
+            return Err(AppError::UnProcessableEntity {
+                field: "coverage".to_string(),
+                message: format!("{:?} is covered more than once", rule.kind),
+            });
+        }
+    }
+    if data.annual_limit.is_some_and(|limit| !limit.is_positive()) {
+        return Err(AppError::UnProcessableEntity {
+            field: "annual_limit".to_string(),
+            message: "Annual limit must be greater than zero".to_string(),
+        });
+    }
+    if data
+        .valid_until
+        .is_some_and(|valid_until| valid_until < data.valid_from)
+    {
+        return Err(AppError::UnProcessableEntity {
+            field: "valid_until".to_string(),
+            message: "A policy cannot end before it starts".to_string(),
+        });
+    }
+    Ok(())
+}
+
+pub async fn get_policies(
+    state: SharedState,
+    patient_id: Option<Uuid>,
+    claims: ClaimsHeader,
+) -> Result<InsurancePolicyList, AppError> {
+    let policies = sqlx::query_as::<_, InsurancePolicy>(
+        "SELECT pol.* FROM insurance_policies pol JOIN insurance_providers p ON p.id = pol.provider_id \
+         WHERE p.hospital_id = $1 AND ($2::UUID IS NULL OR pol.patient_id = $2) ORDER BY pol.created_at DESC",
+    )
+    .bind(claims.hospital_id)
+    .bind(patient_id)
+    .fetch_all(&state.db_pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    Ok(InsurancePolicyList { policies })
+}
+
+// Works out the insurer's share of a new bill from the patient's policy: the chosen one, or else
+// their most recent policy valid on the day of service. The share follows each line's coverage
+// rule, capped by what is left of the policy's annual limit. Returns the policy to claim
+// against, if any of the bill is covered.
+pub async fn cover_bill(
+    conn: &mut PgConnection,
+    bill: &mut Bill,
+    items: &[BillItem],
+    patient_id: Uuid,
+    policy_id: Option<Uuid>,
+    service_date: NaiveDate,
+) -> Result<Option<InsurancePolicy>, AppError> {
+    // Locking the policy keeps concurrent bills from both using the same headroom. Only
+    // policies with providers the bill's hospital works with can cover it.
+    let policy = match policy_id {
+        Some(policy_id) => {
+            let policy = sqlx::query_as::<_, InsurancePolicy>(
+                "SELECT pol.* FROM insurance_policies pol JOIN insurance_providers p ON p.id = pol.provider_id \
+                 WHERE pol.id = $1 AND pol.patient_id = $2 AND p.hospital_id = $3 FOR UPDATE OF pol",
+            )
+            .bind(policy_id)
+            .bind(patient_id)
+            .bind(bill.hospital_id)
+            .fetch_optional(&mut *conn)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?
+            .ok_or_else(|| AppError::UnProcessableEntity {
+                field: "policy_id".to_string(),
+                message: "The patient has no such insurance policy".to_string(),
+            })?;
+            let valid = policy.is_active
+                && policy.valid_from <= service_date
+                && policy.valid_until.is_none_or(|until| until >= service_date);
+            if !valid {
+                return Err(AppError::UnProcessableEntity {
+                    field: "policy_id".to_string(),
+                    message: format!("The policy is not valid on {service_date}"),
+                });
+            }
+            policy
+        }
+        None => {
+            let policy = sqlx::query_as::<_, InsurancePolicy>(
+                "SELECT pol.* FROM insurance_policies pol JOIN insurance_providers p ON p.id = pol.provider_id \
+                 WHERE pol.patient_id = $1 AND pol.is_active AND pol.valid_from <= $2 \
+                 AND (pol.valid_until IS NULL OR pol.valid_until >= $2) AND p.hospital_id = $3 \
+                 ORDER BY pol.created_at DESC LIMIT 1 FOR UPDATE OF pol",
+            )
+            .bind(patient_id)
+            .bind(service_date)
+            .bind(bill.hospital_id)
+            .fetch_optional(&mut *conn)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+            match policy {
+                Some(policy) => policy,
+                None => return Ok(None),
+            }
+        }
+    };
+
+    let mut covered = Money::ZERO;
+    for item in items {
+        if let Some(rule) = policy.coverage.iter().find(|rule| rule.kind == item.kind) {
+            covered = covered + item.total.percent(rule.percent).round_for(&bill.currency)?;
+        }
+    }
+    if let Some(limit) = policy.annual_limit {
+        let year_start = NaiveDate::from_ymd_opt(service_date.year(), 1, 1)
+            .ok_or_else(|| AppError::ParsingError(format!("Invalid date {service_date}")))?;
+        let used = sqlx::query_scalar::<_, Money>(
+            "SELECT COALESCE(SUM(COALESCE(c.approved_amount, c.amount)), 0) FROM insurance_claims c \
+             JOIN bills b ON b.id = c.bill_id JOIN appointments a ON a.id = b.appointment_id \
+             WHERE c.policy_id = $1 AND c.status NOT IN ($2, $3) AND a.time >= $4 AND a.time < $5",
+        )
+        .bind(policy.id)
+        .bind(ClaimStatus::Rejected)
+        .bind(ClaimStatus::Withdrawn)
+        .bind(year_start.and_hms_opt(0, 0, 0).map(|start| start.and_utc()))
+        .bind(
+            NaiveDate::from_ymd_opt(service_date.year() + 1, 1, 1)
+                .and_then(|end| end.and_hms_opt(0, 0, 0))
+                .map(|end| end.and_utc()),
+        )
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        covered = covered.min((limit - used).max(Money::ZERO));
+    }
+    if !covered.is_positive() {
+        return Ok(None);
+    }
+
+    bill.insurer_amount = covered;
+    bill.policy_id = Some(policy.id);
+    if !bill.patient_amount().is_positive() {
+        // Nothing left for the patient to pay
+        bill.status = BillStatus::Paid;
+        bill.paid_at = Some(Utc::now());
+    }
+    Ok(Some(policy))
+}
+
+// Opens the claim for a bill's insurer portion, on the caller's transaction
+pub async fn open_claim(
+    conn: &mut PgConnection,
+    bill: &Bill,
+    policy: &InsurancePolicy,
+) -> Result<InsuranceClaim, AppError> {
+    sqlx::query_as::<_, InsuranceClaim>(
+        "INSERT INTO insurance_claims (id, bill_id, policy_id, provider_id, amount, status) VALUES ($1, $2, $3, $4, $5, $6) RETURNING *",
+    )
+    .bind(Uuid::new_v4())
+    .bind(bill.id)
+    .bind(policy.id)
+    .bind(policy.provider_id)
+    .bind(bill.insurer_amount)
+    .bind(ClaimStatus::Pending)
+    .fetch_one(conn)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))
+}
+
+pub async fn get_claims(
+    state: SharedState,
+    provider_id: Option<Uuid>,
+    status: Option<ClaimStatus>,
+    claims: ClaimsHeader,
+) -> Result<InsuranceClaimList, AppError> {
+    let claims = sqlx::query_as::<_, InsuranceClaim>(
+        "SELECT c.* FROM insurance_claims c JOIN insurance_providers p ON p.id = c.provider_id \
+         WHERE p.hospital_id = $1 AND ($2::UUID IS NULL OR c.provider_id = $2) \
+         AND ($3::VARCHAR IS NULL OR c.status = $3) ORDER BY c.created_at DESC",
+    )
+    .bind(claims.hospital_id)
+    .bind(provider_id)
+    .bind(status)
+    .fetch_all(&state.db_pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    Ok(InsuranceClaimList { claims })
+}
+
+pub async fn get_claim_by_id(
+    state: SharedState,
+    claim_id: Uuid,
+    claims: ClaimsHeader,
+) -> Result<InsuranceClaim, AppError> {
+    sqlx::query_as::<_, InsuranceClaim>(
+        "SELECT c.* FROM insurance_claims c JOIN insurance_providers p ON p.id = c.provider_id \
+         WHERE c.id = $1 AND p.hospital_id = $2",
+    )
+    .bind(claim_id)
+    .bind(claims.hospital_id)
+    .fetch_optional(&state.db_pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?
+    .ok_or_else(|| AppError::NotFound(format!("Claim {claim_id} not found")))
+}
+
+// Moves a claim along as the HMO responds. Whatever the HMO will not pay, on a rejection or a
+// partial approval, is moved onto the patient's side of the bill.
+pub async fn update_claim_status(
+    state: SharedState,
+    claim_id: Uuid,
+    data: UpdateClaimStatus,
+    claims: ClaimsHeader,
+) -> Result<InsuranceClaim, AppError> {
+    let mut tx = state
+        .db_pool
+        .begin()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    let mut claim = sqlx::query_as::<_, InsuranceClaim>(
+        "SELECT c.* FROM insurance_claims c JOIN insurance_providers p ON p.id = c.provider_id \
+         WHERE c.id = $1 AND p.hospital_id = $2 FOR UPDATE OF c",
+    )
+    .bind(claim_id)
+    .bind(claims.hospital_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?
+    .ok_or_else(|| AppError::NotFound(format!("Claim {claim_id} not found")))?;
+    if !claim.status.can_transition_to(data.status) {
+        return Err(AppError::Conflict(format!(
+            "A claim that is {:?} cannot be moved to {:?}",
+            claim.status, data.status
+        )));
+    }
+
+    let now = Utc::now();
+    match data.status {
+        ClaimStatus::Submitted => claim.submitted_at = Some(now),
+        ClaimStatus::Approved => {
+            let approved = data.approved_amount.unwrap_or(claim.amount);
+            if approved.is_negative() || approved > claim.amount {
+                return Err(AppError::UnProcessableEntity {
+                    field: "approved_amount".to_string(),
+                    message: format!("Approved amount must be between 0 and {}", claim.amount),
+                });
+            }
+            if approved < claim.amount {
+                set_insurer_amount(&mut tx, claim.bill_id, approved).await?;
+            }
+            claim.approved_amount = Some(approved);
+            claim.decided_at = Some(now);
+        }
+        ClaimStatus::Rejected => {
+            if data
+                .reason
+                .as_deref()
+                .is_none_or(|reason| reason.trim().is_empty())
+            {
+                return Err(AppError::UnProcessableEntity {
+                    field: "reason".to_string(),
+                    message: "A rejected claim needs the HMO's reason".to_string(),
+                });
+            }
+            set_insurer_amount(&mut tx, claim.bill_id, Money::ZERO).await?;
+            claim.approved_amount = Some(Money::ZERO);
+            claim.decided_at = Some(now);
+        }
+        ClaimStatus::Paid => claim.paid_at = Some(now),
+        ClaimStatus::Pending | ClaimStatus::Withdrawn => {}
+    }
+    if data.reason.is_some() {
+        claim.reason = data.reason;
+    }
+
+    let claim = sqlx::query_as::<_, InsuranceClaim>(
+        "UPDATE insurance_claims SET status = $1, approved_amount = $2, reason = $3, submitted_at = $4, decided_at = $5, paid_at = $6 WHERE id = $7 RETURNING *",
+    )
+    .bind(data.status)
+    .bind(claim.approved_amount)
+    .bind(&claim.reason)
+    .bind(claim.submitted_at)
+    .bind(claim.decided_at)
+    .bind(claim.paid_at)
+    .bind(claim.id)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    Ok(claim)
+}
+
+// A provider's claims for services between `from` and `to` inclusive, as CSV for sending to the
+// HMO. Withdrawn claims are left out.
+pub async fn export_claims(
+    state: SharedState,
+    provider_id: Uuid,
+    from: NaiveDate,
+    to: NaiveDate,
+    claims: ClaimsHeader,
+) -> Result<ClaimExport, AppError> {
+    if to < from {
+        return Err(AppError::UnProcessableEntity {
+            field: "to".to_string(),
+            message: "The period cannot end before it starts".to_string(),
+        });
+    }
+    let provider = get_provider(&state, provider_id, claims.hospital_id).await?;
+    let start = from
+        .and_hms_opt(0, 0, 0)
+        .map(|start| start.and_utc())
+        .ok_or_else(|| AppError::ParsingError(format!("Invalid date {from}")))?;
+    let end = (to + Duration::days(1))
+        .and_hms_opt(0, 0, 0)
+        .map(|end| end.and_utc())
+        .ok_or_else(|| AppError::ParsingError(format!("Invalid date {to}")))?;
+
+    let rows = sqlx::query_as::<_, ClaimExportRow>(
+        "SELECT c.id, c.created_at, b.reference AS bill_reference, a.time AS service_date, \
+         pt.name AS patient_name, pt.card_id, pol.policy_number, b.currency, b.amount AS bill_amount, \
+         c.amount, c.approved_amount, c.status \
+         FROM insurance_claims c \
+         JOIN bills b ON b.id = c.bill_id \
+         JOIN appointments a ON a.id = b.appointment_id \
+         JOIN patients pt ON pt.id = a.patient_id \
+         JOIN insurance_policies pol ON pol.id = c.policy_id \
+         WHERE c.provider_id = $1 AND c.status <> $2 AND a.time >= $3 AND a.time < $4 \
+         ORDER BY a.time, c.created_at",
+    )
+    .bind(provider.id)
+    .bind(ClaimStatus::Withdrawn)
+    .bind(start)
+    .bind(end)
+    .fetch_all(&state.db_pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    let mut csv = String::from(
+        "claim_id,claimed_on,service_date,bill_reference,patient_name,card_id,policy_number,currency,bill_amount,claimed_amount,approved_amount,status\n",
+    );
+    for row in &rows {
+        let fields = [
+            row.id.to_string(),
+            row.created_at.date_naive().to_string(),
+            row.service_date.date_naive().to_string(),
+            row.bill_reference.clone(),
+            row.patient_name.clone(),
+            row.card_id.clone(),
+            row.policy_number.clone(),
+            row.currency.clone(),
+            row.bill_amount.to_string(),
+            row.amount.to_string(),
+            row.approved_amount
+                .map(|amount| amount.to_string())
+                .unwrap_or_default(),
+            format!("{:?}", row.status),
+        ];
+        let line: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+        // Writing to a String cannot fail
+        let _ = writeln!(csv, "{}", line.join(","));
+    }
+
+    Ok(ClaimExport {
+        filename: format!("{}-claims-{}-{}.csv", provider.code, from, to),
+        csv,
+    })
+}
+
+// Quotes a CSV field when it contains a separator, quote or line break
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{Hospital, TestApp};
+    use reqwest::{Method, StatusCode};
+    use serde_json::{Value, json};
+    use sqlx::PgPool;
+
+    // An HMO of `hospital` with a policy for its patient
+    async fn insure(app: &TestApp, hospital: &Hospital) -> (Uuid, Uuid) {
+        let (provider_id, policy_id) = (Uuid::new_v4(), Uuid::new_v4());
+        sqlx::query("INSERT INTO insurance_providers (id, hospital_id, name, code) VALUES ($1, $2, 'Hygeia HMO', 'HYG')")
+            .bind(provider_id)
+            .bind(hospital.id)
+            .execute(&app.pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO insurance_policies (id, patient_id, provider_id, policy_number, coverage, valid_from) VALUES ($1, $2, $3, 'HYG-0001', '[]', CURRENT_DATE)")
+            .bind(policy_id)
+            .bind(hospital.patient_id)
+            .bind(provider_id)
+            .execute(&app.pool)
+            .await
+            .unwrap();
+        (provider_id, policy_id)
+    }
+
+    // A claim for 8000.00 of `bill_id`'s 10000.00
+    async fn claim(
+        app: &TestApp,
+        bill_id: Uuid,
+        (provider_id, policy_id): (Uuid, Uuid),
+        status: ClaimStatus,
+    ) -> Uuid {
+        let claim_id = Uuid::new_v4();
+        sqlx::query("UPDATE bills SET insurer_amount = 8000, policy_id = $2 WHERE id = $1")
+            .bind(bill_id)
+            .bind(policy_id)
+            .execute(&app.pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO insurance_claims (id, bill_id, policy_id, provider_id, amount, status) VALUES ($1, $2, $3, $4, 8000, $5)")
+            .bind(claim_id)
+            .bind(bill_id)
+            .bind(policy_id)
+            .bind(provider_id)
+            .bind(status)
+            .execute(&app.pool)
+            .await
+            .unwrap();
+        claim_id
+    }
+
+    // Another bill for the hospital's patient, for a visit `days` from now
+    async fn bill_in(app: &TestApp, hospital: &Hospital, days: i32) -> Uuid {
+        let (appointment_id, bill_id) = (Uuid::new_v4(), Uuid::new_v4());
+        sqlx::query("INSERT INTO appointments (id, patient_id, doctor_id, time, purpose, status, price, hospital_id) VALUES ($1, $2, $3, NOW() + make_interval(days => $4), 'Checkup', 'Scheduled', 10000, $5)")
+            .bind(appointment_id)
+            .bind(hospital.patient_id)
+            .bind(hospital.doctor_id)
+            .bind(days)
+            .bind(hospital.id)
+            .execute(&app.pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO bills (id, reference, appointment_id, amount, subtotal, currency, status, hospital_id) VALUES ($1, $2, $3, 10000, 10000, 'NGN', 'Pending', $4)")
+            .bind(bill_id)
+            .bind(bill_id.to_string())
+            .bind(appointment_id)
+            .bind(hospital.id)
+            .execute(&app.pool)
+            .await
+            .unwrap();
+        bill_id
+    }
+
+    async fn set_status(app: &TestApp, token: &str, claim_id: Uuid, body: Value) -> StatusCode {
+        app.request(
+            Method::PUT,
+            token,
+            &format!("/insurance/claims/{claim_id}/status"),
+        )
+        .json(&body)
+        .send()
+        .await
+        .unwrap()
+        .status()
+    }
+
+    #[test]
+    fn quotes_csv_fields_only_when_needed() {
+        assert_eq!(csv_field("Ada Obi"), "Ada Obi");
+        assert_eq!(csv_field("Obi, Ada"), "\"Obi, Ada\"");
+        assert_eq!(csv_field("Ada \"Nne\" Obi"), "\"Ada \"\"Nne\"\" Obi\"");
+        assert_eq!(csv_field("line\nbreak"), "\"line\nbreak\"");
+    }
+
+    #[sqlx::test]
+    async fn moves_claims_through_the_hmo_process(pool: PgPool) {
+        let app = TestApp::spawn(pool).await;
+        let hospital = app.create_hospital("Lagos General").await;
+        let token = app.login_as(hospital.id, UserRole::Admin).await;
+        let insurer = insure(&app, &hospital).await;
+        let claim_id = claim(&app, hospital.bill_id, insurer, ClaimStatus::Pending).await;
+
+        // Nothing is decided before the claim is sent
+        assert_eq!(
+            set_status(&app, &token, claim_id, json!({"status": "Approved"})).await,
+            StatusCode::CONFLICT
+        );
+        assert_eq!(
+            set_status(&app, &token, claim_id, json!({"status": "Submitted"})).await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            set_status(&app, &token, claim_id, json!({"status": "Rejected"})).await,
+            StatusCode::UNPROCESSABLE_ENTITY
+        );
+        assert_eq!(
+            set_status(
+                &app,
+                &token,
+                claim_id,
+                json!({"status": "Approved", "approved_amount": "9000.00"})
+            )
+            .await,
+            StatusCode::UNPROCESSABLE_ENTITY
+        );
+
+        // A partial approval moves the rest onto the patient
+        assert_eq!(
+            set_status(
+                &app,
+                &token,
+                claim_id,
+                json!({"status": "Approved", "approved_amount": "6000.00", "reason": "Tariff cap"})
+            )
+            .await,
+            StatusCode::OK
+        );
+        let insurer_amount: Money =
+            sqlx::query_scalar("SELECT insurer_amount FROM bills WHERE id = $1")
+                .bind(hospital.bill_id)
+                .fetch_one(&app.pool)
+                .await
+                .unwrap();
+        assert_eq!(insurer_amount, Money::from_hundredths(600_000));
+
+        assert_eq!(
+            set_status(&app, &token, claim_id, json!({"status": "Paid"})).await,
+            StatusCode::OK
+        );
+        let claim: Value = app
+            .get(&token, &format!("/insurance/claims/{claim_id}"))
+            .await
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(claim["status"], "Paid");
+        assert_eq!(claim["reason"], "Tariff cap");
+        for field in ["submitted_at", "decided_at", "paid_at"] {
+            assert!(claim[field].is_string(), "{field}");
+        }
+
+        // Paid is final
+        assert_eq!(
+            set_status(&app, &token, claim_id, json!({"status": "Submitted"})).await,
+            StatusCode::CONFLICT
+        );
+    }
+
+    #[sqlx::test]
+    async fn exports_a_providers_claims_for_the_period(pool: PgPool) {
+        let app = TestApp::spawn(pool).await;
+        let hospital = app.create_hospital("Lagos General").await;
+        let token = app.login_as(hospital.id, UserRole::Admin).await;
+        sqlx::query("UPDATE patients SET name = 'Obi, Ada' WHERE id = $1")
+            .bind(hospital.patient_id)
+            .execute(&app.pool)
+            .await
+            .unwrap();
+        let insurer = insure(&app, &hospital).await;
+        // The visit a week out is in the period; the other two claims are not exported
+        let exported = claim(&app, hospital.bill_id, insurer, ClaimStatus::Submitted).await;
+        let withdrawn = bill_in(&app, &hospital, 8).await;
+        claim(&app, withdrawn, insurer, ClaimStatus::Withdrawn).await;
+        let later = bill_in(&app, &hospital, 40).await;
+        claim(&app, later, insurer, ClaimStatus::Submitted).await;
+
+        let today = Utc::now().date_naive();
+        let to = today + Duration::days(10);
+        let response = app
+            .get(
+                &token,
+                &format!(
+                    "/insurance/providers/{}/claims/export?from={today}&to={to}",
+                    insurer.0
+                ),
+            )
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()["content-disposition"],
+            format!("attachment; filename=\"HYG-claims-{today}-{to}.csv\"").as_str()
+        );
+        let csv = response.text().await.unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 2, "{csv}");
+        assert!(lines[0].starts_with("claim_id,claimed_on,service_date,"));
+        assert!(lines[1].starts_with(&exported.to_string()));
+        assert!(lines[1].contains(",\"Obi, Ada\","));
+        assert!(lines[1].ends_with(",HYG-0001,NGN,10000.00,8000.00,,Submitted"));
+
+        // A period that ends before it starts is refused
+        let response = app
+            .get(
+                &token,
+                &format!(
+                    "/insurance/providers/{}/claims/export?from={to}&to={today}",
+                    insurer.0
+                ),
+            )
+            .await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+}
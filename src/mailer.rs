@@ -0,0 +1,82 @@
+// Outgoing email. Services hand messages to the `Mailer` in the app state, so adding a real
+// provider (SMTP, Mailgun) means implementing the trait and naming it in `from_config`.
+use crate::config::AppConfig;
+use crate::errors::AppError;
+use async_trait::async_trait;
+use chrono::Utc;
+use std::fmt::Debug;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tracing::info;
+use uuid::Uuid;
+
+#[derive(Debug, Clone)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String, // Plain text
+}
+
+#[async_trait]
+pub trait Mailer: Debug + Send + Sync {
+    async fn send(&self, email: Email) -> Result<(), AppError>;
+}
+
+// Builds the mailer named by MAILER
+pub fn from_config(config: &AppConfig) -> Result<Arc<dyn Mailer>, AppError> {
+    match config.mailer.as_str() {
+        "log" => Ok(Arc::new(LogMailer)),
+        "file" => Ok(Arc::new(FileMailer::new(PathBuf::from(&config.mail_dir)))),
+        other => Err(AppError::ParsingError(format!(
+            "Unknown MAILER '{}', expected 'log' or 'file'",
+            other
+        ))),
+    }
+}
+
+// Logs every message instead of sending it
+#[derive(Debug)]
+pub struct LogMailer;
+
+#[async_trait]
+impl Mailer for LogMailer {
+    async fn send(&self, email: Email) -> Result<(), AppError> {
+        info!(to = %email.to, subject = %email.subject, body = %email.body, "email");
+        Ok(())
+    }
+}
+
+// Saves every message as a text file in `dir`, one per email, for local testing
+#[derive(Debug)]
+pub struct FileMailer {
+    dir: PathBuf,
+}
+
+impl FileMailer {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, email: Email) -> Result<(), AppError> {
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .map_err(|e| AppError::InternalServerError(e.to_string()))?;
+        let path = self.dir.join(format!(
+            "{}-{}.txt",
+            Utc::now().format("%Y%m%dT%H%M%S"),
+            Uuid::new_v4()
+        ));
+        let contents = format!(
+            "To: {}\nSubject: {}\n\n{}\n",
+            email.to, email.subject, email.body
+        );
+        tokio::fs::write(&path, contents)
+            .await
+            .map_err(|e| AppError::InternalServerError(e.to_string()))?;
+        info!("Email to {} saved to {}", email.to, path.display());
+        Ok(())
+    }
+}
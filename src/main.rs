@@ -7,8 +7,15 @@ mod billing;
 mod config;
 mod doctor;
 mod errors;
+mod idempotency;
+mod insurance;
+mod mailer;
+mod money;
+mod notifications;
 mod patient;
 mod router;
+#[cfg(test)]
+mod test_support;
 mod utils;
 
 use app::start_app;
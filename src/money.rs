@@ -0,0 +1,180 @@
+use crate::errors::AppError;
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display};
+use std::iter::Sum;
+use std::ops::{Add, Neg, Sub};
+
+// An exact amount in major units (naira, dollars), stored as NUMERIC and sent over JSON as a
+// string such as "10000.00". Amounts are accepted from JSON as either strings or numbers.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, sqlx::Type,
+)]
+#[serde(transparent)]
+#[sqlx(transparent)]
+pub struct Money(Decimal);
+
+// Number of decimal places in the currency's minor unit (ISO 4217)
+pub fn minor_unit_exponent(currency: &str) -> Result<u32, AppError> {
+    match currency {
+        "NGN" | "GHS" | "KES" | "ZAR" | "EGP" | "USD" | "EUR" | "GBP" => Ok(2),
+        "XOF" | "XAF" | "RWF" | "UGX" | "JPY" => Ok(0),
+        _ => Err(AppError::UnProcessableEntity {
+            field: "currency".to_string(),
+            message: format!("Unsupported currency: {currency}"),
+        }),
+    }
+}
+
+impl Money {
+    pub const ZERO: Money = Money(Decimal::ZERO);
+
+    // Amount with two decimal places from its hundredths, for use in constants
+    pub const fn from_hundredths(hundredths: u32) -> Self {
+        Self(Decimal::from_parts(hundredths, 0, 0, false, 2))
+    }
+
+    // Amount in the currency's minor unit (kobo for NGN), as payment providers expect.
+    // Fails instead of truncating when the amount is more precise than the currency allows.
+    pub fn to_minor_units(self, currency: &str) -> Result<i64, AppError> {
+        let exponent = minor_unit_exponent(currency)?;
+        let scaled = self
+            .0
+            .checked_mul(Decimal::from(10_i64.pow(exponent)))
+            .ok_or_else(|| AppError::ParsingError(format!("Amount {self} is too large")))?;
+        if !scaled.fract().is_zero() {
+            return Err(AppError::UnProcessableEntity {
+                field: "amount".to_string(),
+                message: format!("{self} has more decimal places than {currency} allows"),
+            });
+        }
+        scaled
+            .normalize()
+            .mantissa()
+            .try_into()
+            .map_err(|_| AppError::ParsingError(format!("Amount {self} is too large")))
+    }
+
+    pub fn times(self, quantity: i32) -> Self {
+        Self(self.0 * Decimal::from(quantity))
+    }
+
+    // `percent` of the amount, unrounded; round with round_for before storing it
+    pub fn percent(self, percent: Decimal) -> Self {
+        Self(self.0 * percent / Decimal::ONE_HUNDRED)
+    }
+
+    // Rounds half away from zero to the currency's minor unit
+    pub fn round_for(self, currency: &str) -> Result<Self, AppError> {
+        let exponent = minor_unit_exponent(currency)?;
+        Ok(Self(self.0.round_dp_with_strategy(
+            exponent,
+            RoundingStrategy::MidpointAwayFromZero,
+        )))
+    }
+
+    pub fn is_negative(self) -> bool {
+        self.0 < Decimal::ZERO
+    }
+
+    pub fn is_positive(self) -> bool {
+        self.0 > Decimal::ZERO
+    }
+}
+
+impl Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl Add for Money {
+    type Output = Money;
+
+    fn add(self, other: Money) -> Money {
+        Money(self.0 + other.0)
+    }
+}
+
+impl Sub for Money {
+    type Output = Money;
+
+    fn sub(self, other: Money) -> Money {
+        Money(self.0 - other.0)
+    }
+}
+
+impl Neg for Money {
+    type Output = Money;
+
+    fn neg(self) -> Money {
+        Money(-self.0)
+    }
+}
+
+impl Sum for Money {
+    fn sum<I: Iterator<Item = Money>>(iter: I) -> Money {
+        iter.fold(Money::ZERO, Add::add)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn money(amount: &str) -> Money {
+        serde_json::from_value(serde_json::json!(amount)).unwrap()
+    }
+
+    #[test]
+    fn serializes_as_a_decimal_string() {
+        assert_eq!(
+            serde_json::to_value(Money::from_hundredths(1_000_000)).unwrap(),
+            serde_json::json!("10000.00")
+        );
+        assert_eq!(serde_json::to_string(&money("0.10")).unwrap(), "\"0.10\"");
+    }
+
+    #[test]
+    fn deserializes_strings_and_numbers() {
+        assert_eq!(money("10000.00"), Money::from_hundredths(1_000_000));
+        let from_number: Money = serde_json::from_str("2500.5").unwrap();
+        assert_eq!(from_number, Money::from_hundredths(250_050));
+        assert!(serde_json::from_str::<Money>("\"ten naira\"").is_err());
+    }
+
+    #[test]
+    fn converts_to_minor_units() {
+        assert_eq!(money("10000.00").to_minor_units("NGN").unwrap(), 1_000_000);
+        assert_eq!(money("0.5").to_minor_units("NGN").unwrap(), 50);
+        assert_eq!(money("1500").to_minor_units("XOF").unwrap(), 1500);
+        assert_eq!(money("1500.00").to_minor_units("XOF").unwrap(), 1500);
+    }
+
+    #[test]
+    fn rejects_more_decimal_places_than_the_currency_has() {
+        assert!(matches!(
+            money("10.005").to_minor_units("NGN"),
+            Err(AppError::UnProcessableEntity { .. })
+        ));
+        assert!(matches!(
+            money("1500.50").to_minor_units("XOF"),
+            Err(AppError::UnProcessableEntity { .. })
+        ));
+        assert!(matches!(
+            money("10.00").to_minor_units("BTC"),
+            Err(AppError::UnProcessableEntity { .. })
+        ));
+    }
+
+    #[test]
+    fn rounds_half_away_from_zero_to_the_minor_unit() {
+        assert_eq!(money("10.005").round_for("NGN").unwrap(), money("10.01"));
+        assert_eq!(money("10.004").round_for("NGN").unwrap(), money("10.00"));
+        assert_eq!(money("-10.005").round_for("NGN").unwrap(), money("-10.01"));
+        assert_eq!(money("1500.5").round_for("XOF").unwrap(), money("1501"));
+        // VAT on an odd amount lands on a whole kobo
+        let vat = money("333.33").percent(Decimal::new(75, 1));
+        assert_eq!(vat.round_for("NGN").unwrap(), money("25.00"));
+    }
+}
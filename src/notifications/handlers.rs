@@ -0,0 +1,50 @@
+use crate::app_state::SharedState;
+use crate::auth::headers::ClaimsHeader;
+use crate::auth::permissions::ensure_patient_access;
+use crate::notifications::service;
+use axum::{
+    Json,
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use serde_json::json;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+pub async fn get_notifications_handler(
+    State(state): State<SharedState>,
+    claims: ClaimsHeader,
+    Query(params): Query<HashMap<String, String>>,
+) -> impl IntoResponse {
+    let patient_id = match params.get("patient_id").map(|s| Uuid::parse_str(s)) {
+        Some(Ok(id)) => id,
+        Some(Err(e)) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"Invalid patient_id": e.to_string()})),
+            )
+                .into_response();
+        }
+        None => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "Missing 'patient_id' query parameter"})),
+            )
+                .into_response();
+        }
+    };
+
+    if let Err(e) = ensure_patient_access(&state, &claims, patient_id).await {
+        return e.into_response();
+    }
+
+    match service::get_notifications(state, patient_id).await {
+        Ok(notifications) => Json(notifications).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
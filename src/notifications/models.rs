@@ -0,0 +1,17 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::FromRow;
+use uuid::Uuid;
+
+#[derive(Serialize, FromRow)]
+pub struct Notification {
+    pub id: Uuid,
+    pub patient_id: Uuid,
+    pub message: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+pub struct NotificationList {
+    pub notifications: Vec<Notification>,
+}
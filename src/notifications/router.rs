@@ -0,0 +1,16 @@
+use crate::app_state::{AppState, SharedState};
+use crate::auth::permissions::{Permission, require};
+use crate::notifications::handlers::get_notifications_handler;
+use axum::Router;
+use axum::routing::get;
+use std::sync::Arc;
+
+pub fn notifications_router(state: SharedState) -> Router<Arc<AppState>> {
+    Router::new()
+        .merge(require(
+            &state,
+            Permission::ViewNotifications,
+            Router::new().route("/", get(get_notifications_handler)),
+        ))
+        .with_state(state)
+}
@@ -0,0 +1,38 @@
+use crate::app_state::SharedState;
+use crate::errors::AppError;
+use crate::notifications::models::{Notification, NotificationList};
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+// Queue a message for the patient. Runs on the caller's connection so it commits or rolls
+// back together with the change it reports.
+pub async fn notify_patient(
+    conn: &mut PgConnection,
+    patient_id: Uuid,
+    message: String,
+) -> Result<(), AppError> {
+    sqlx::query("INSERT INTO notifications (id, patient_id, message) VALUES ($1, $2, $3)")
+        .bind(Uuid::new_v4())
+        .bind(patient_id)
+        .bind(&message)
+        .execute(conn)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    tracing::info!(%patient_id, %message, "patient notified");
+    Ok(())
+}
+
+// Get a patient's notifications, newest first
+pub async fn get_notifications(
+    state: SharedState,
+    patient_id: Uuid,
+) -> Result<NotificationList, AppError> {
+    let notifications = sqlx::query_as::<_, Notification>(
+        "SELECT * FROM notifications WHERE patient_id = $1 ORDER BY created_at DESC",
+    )
+    .bind(patient_id)
+    .fetch_all(&state.db_pool)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    Ok(NotificationList { notifications })
+}
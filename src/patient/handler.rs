@@ -1,4 +1,6 @@
 use crate::app_state::SharedState;
+use crate::auth::headers::ClaimsHeader;
+use crate::errors::AppError;
 use crate::patient::models::CreatePatient;
 use crate::patient::service::create_patient;
 use axum::{
@@ -8,8 +10,11 @@ use axum::{
 };
 use serde_json::json;
 
-pub async fn get_patients_handler(State(state): State<SharedState>) -> impl IntoResponse {
-    match crate::patient::service::get_patients(state).await {
+pub async fn get_patients_handler(
+    State(state): State<SharedState>,
+    claims: ClaimsHeader,
+) -> impl IntoResponse {
+    match crate::patient::service::get_patients(state, claims.tenant()).await {
         Ok(patients) => (StatusCode::OK, Json(patients)).into_response(),
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
@@ -21,14 +26,22 @@ pub async fn get_patients_handler(State(state): State<SharedState>) -> impl Into
 
 pub async fn create_patient_handler(
     State(state): State<SharedState>,
+    claims: ClaimsHeader,
     Json(payload): Json<CreatePatient>,
 ) -> impl IntoResponse {
-    match create_patient(state, payload).await {
+    match create_patient(state, payload, claims).await {
         Ok(patient) => (StatusCode::CREATED, Json(patient)).into_response(),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({"error": e.to_string()})),
-        )
-            .into_response(),
+        Err(e) => match e {
+            AppError::UnProcessableEntity { field, message } => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(json!({"error": format!("{}: {}", field, message)})),
+            )
+                .into_response(),
+            _ => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response(),
+        },
     }
 }
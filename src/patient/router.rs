@@ -1,12 +1,26 @@
 use std::sync::Arc;
 
 use crate::app_state::{AppState, SharedState};
+use crate::auth::permissions::{Permission, require};
+use crate::idempotency::idempotent;
 use crate::patient::handler::{create_patient_handler, get_patients_handler};
 use axum::Router;
-use axum::routing::get;
+use axum::routing::{get, post};
 
 pub fn patient_router(state: SharedState) -> Router<Arc<AppState>> {
     Router::new()
-        .route("/", get(get_patients_handler).post(create_patient_handler))
+        .merge(require(
+            &state,
+            Permission::ViewPatients,
+            Router::new().route("/", get(get_patients_handler)),
+        ))
+        .merge(require(
+            &state,
+            Permission::ManagePatients,
+            idempotent(
+                &state,
+                Router::new().route("/", post(create_patient_handler)),
+            ),
+        ))
         .with_state(state)
 }
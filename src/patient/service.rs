@@ -1,28 +1,44 @@
+use crate::admin::service::validate_user_link;
 use crate::app_state::SharedState;
+use crate::auth::headers::ClaimsHeader;
 use crate::errors::AppError;
 use crate::patient::models::{CreatePatient, Patient, PatientList};
+use uuid::Uuid;
 
-pub async fn get_patients(state: SharedState) -> Result<PatientList, AppError> {
-    let query = "SELECT * FROM patients";
+// Patients of a hospital, or of every hospital when `hospital_id` is None
+pub async fn get_patients(
+    state: SharedState,
+    hospital_id: Option<Uuid>,
+) -> Result<PatientList, AppError> {
+    let query = "SELECT * FROM patients WHERE ($1::UUID IS NULL OR hospital_id = $1) ORDER BY name";
     let patients = sqlx::query_as::<_, Patient>(query)
+        .bind(hospital_id)
         .fetch_all(&state.db_pool)
         .await
         .map_err(|e| AppError::DatabaseError(e.to_string()))?;
     Ok(PatientList { patients })
 }
 
+// Registers a patient with the caller's hospital; only super admins choose the hospital
 pub async fn create_patient(
     state: SharedState,
     patient_data: CreatePatient,
+    claims: ClaimsHeader,
 ) -> Result<Patient, AppError> {
-    let patient = Patient::new(patient_data);
-    let query = "INSERT INTO patients (id, name, age, card_id, gender) VALUES ($1, $2, $3, $4, $5)";
+    let mut patient = Patient::new(patient_data);
+    if let Some(hospital_id) = claims.tenant() {
+        patient.hospital_id = Some(hospital_id);
+    }
+    validate_user_link(&state, patient.user_id, patient.hospital_id).await?;
+    let query = "INSERT INTO patients (id, name, age, card_id, gender, hospital_id, user_id) VALUES ($1, $2, $3, $4, $5, $6, $7)";
     sqlx::query(query)
         .bind(patient.id)
         .bind(&patient.name)
         .bind(patient.age)
         .bind(&patient.card_id)
         .bind(&patient.gender)
+        .bind(patient.hospital_id)
+        .bind(patient.user_id)
         .execute(&state.db_pool)
         .await
         .map_err(|e| AppError::DatabaseError(e.to_string()))?;
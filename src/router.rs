@@ -2,6 +2,7 @@ use crate::patient::router::patient_router;
 use crate::{
     admin::router::admin_router, app_state::SharedState, appointments::router::appointments_router,
     auth::router::auth_router, billing::router::billing_router, doctor::router::doctor_router,
+    insurance::router::insurance_router, notifications::router::notifications_router,
 };
 use axum::{
     Router,
@@ -19,6 +20,8 @@ pub fn create_router(state: SharedState) -> Router {
         .nest("/doctors", doctor_router(state.clone()))
         .nest("/appointments", appointments_router(state.clone()))
         .nest("/billing", billing_router(state.clone()))
+        .nest("/insurance", insurance_router(state.clone()))
+        .nest("/notifications", notifications_router(state.clone()))
         .route("/health", get(health_handler))
         .route("/", get(hello))
         .with_state(state)
@@ -37,3 +40,6 @@ async fn health_handler() -> impl IntoResponse {
         })),
     )
 }
+
+#[cfg(test)]
+mod tests;
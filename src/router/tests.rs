@@ -0,0 +1,281 @@
+// Tenant isolation, exercised over HTTP
+use crate::admin::models::UserRole;
+use crate::test_support::TestApp;
+use reqwest::StatusCode;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+// Lists only ever hold the caller's own hospital's records
+async fn assert_listed(app: &TestApp, token: &str, path: &str, own: Uuid, other: Uuid) {
+    let response = app.get(token, path).await;
+    assert_eq!(response.status(), StatusCode::OK, "GET {}", path);
+    let body = response.text().await.unwrap();
+    assert!(
+        body.contains(&own.to_string()),
+        "GET {} is missing {}",
+        path,
+        own
+    );
+    assert!(
+        !body.contains(&other.to_string()),
+        "GET {} leaks {}",
+        path,
+        other
+    );
+}
+
+#[sqlx::test]
+async fn lists_only_the_callers_hospital(pool: PgPool) {
+    let app = TestApp::spawn(pool).await;
+    let a = app.create_hospital("Hospital A").await;
+    let b = app.create_hospital("Hospital B").await;
+    let token = app.login_as(a.id, UserRole::Admin).await;
+
+    assert_listed(&app, &token, "/patients", a.patient_id, b.patient_id).await;
+    assert_listed(&app, &token, "/doctors", a.doctor_id, b.doctor_id).await;
+    assert_listed(
+        &app,
+        &token,
+        "/appointments",
+        a.appointment_id,
+        b.appointment_id,
+    )
+    .await;
+
+    // Filtering by another hospital's patient or doctor finds nothing
+    for path in [
+        format!("/appointments?patient_id={}", b.patient_id),
+        format!("/appointments?doctor_id={}", b.doctor_id),
+    ] {
+        let response = app.get(&token, &path).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.text().await.unwrap();
+        assert!(
+            !body.contains(&b.appointment_id.to_string()),
+            "GET {} leaks",
+            path
+        );
+    }
+}
+
+#[sqlx::test]
+async fn cannot_read_another_hospitals_records(pool: PgPool) {
+    let app = TestApp::spawn(pool).await;
+    let a = app.create_hospital("Hospital A").await;
+    let b = app.create_hospital("Hospital B").await;
+    let token = app.login_as(a.id, UserRole::Admin).await;
+
+    for path in [
+        format!("/doctors/{}", b.doctor_id),
+        format!("/appointments/{}", b.appointment_id),
+        format!("/appointments/{}/events", b.appointment_id),
+        format!("/appointments/{}/orders", b.appointment_id),
+        format!("/billing/{}", b.bill_id),
+        format!("/billing/{}/refunds", b.bill_id),
+        format!("/billing/statement?patient_id={}", b.patient_id),
+    ] {
+        let response = app.get(&token, &path).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND, "GET {}", path);
+    }
+
+    // The caller's own records are still there
+    for path in [
+        format!("/doctors/{}", a.doctor_id),
+        format!("/appointments/{}", a.appointment_id),
+        format!("/billing/{}", a.bill_id),
+    ] {
+        let response = app.get(&token, &path).await;
+        assert_eq!(response.status(), StatusCode::OK, "GET {}", path);
+    }
+}
+
+#[sqlx::test]
+async fn cannot_change_another_hospitals_records(pool: PgPool) {
+    let app = TestApp::spawn(pool).await;
+    let a = app.create_hospital("Hospital A").await;
+    let b = app.create_hospital("Hospital B").await;
+    let token = app.login_as(a.id, UserRole::Admin).await;
+
+    let response = app
+        .put(
+            &token,
+            &format!("/appointments/{}/status?status=Cancelled", b.appointment_id),
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(
+        app.status_of("appointments", b.appointment_id).await,
+        "Scheduled"
+    );
+
+    let response = app
+        .post(&token, &format!("/billing/{}/cancel", b.bill_id))
+        .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(app.status_of("bills", b.bill_id).await, "Pending");
+
+    // Patients are registered with the caller's hospital whichever one the request names
+    let response = app
+        .post_json(
+            &token,
+            "/patients",
+            serde_json::json!({
+                "name": "Transferred patient",
+                "age": 30,
+                "gender": "Male",
+                "hospital_id": b.id,
+            }),
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let patient: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(patient["hospital_id"], serde_json::json!(a.id));
+}
+
+#[sqlx::test]
+async fn super_admin_reaches_every_hospital(pool: PgPool) {
+    let app = TestApp::spawn(pool).await;
+    let a = app.create_hospital("Hospital A").await;
+    let b = app.create_hospital("Hospital B").await;
+    let token = app.login_as(a.id, UserRole::SuperAdmin).await;
+
+    for (path, ids) in [
+        ("/patients", [a.patient_id, b.patient_id]),
+        ("/doctors", [a.doctor_id, b.doctor_id]),
+        ("/appointments", [a.appointment_id, b.appointment_id]),
+    ] {
+        let body = app.get(&token, path).await.text().await.unwrap();
+        for id in ids {
+            assert!(
+                body.contains(&id.to_string()),
+                "GET {} is missing {}",
+                path,
+                id
+            );
+        }
+    }
+    for path in [
+        format!("/doctors/{}", b.doctor_id),
+        format!("/appointments/{}", b.appointment_id),
+        format!("/billing/{}", b.bill_id),
+    ] {
+        let response = app.get(&token, &path).await;
+        assert_eq!(response.status(), StatusCode::OK, "GET {}", path);
+    }
+
+    let response = app
+        .post(&token, &format!("/billing/{}/cancel", b.bill_id))
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(app.status_of("bills", b.bill_id).await, "Cancelled");
+
+    let response = app
+        .put(
+            &token,
+            &format!("/appointments/{}/status?status=Cancelled", b.appointment_id),
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        app.status_of("appointments", b.appointment_id).await,
+        "Cancelled"
+    );
+}
+
+#[sqlx::test]
+async fn patients_keep_only_their_own_waitlist_entries(pool: PgPool) {
+    let app = TestApp::spawn(pool).await;
+    let a = app.create_hospital("Hospital A").await;
+    let token = app.login_as_patient(a.id, a.patient_id).await;
+    let other_patient = Uuid::new_v4();
+    sqlx::query("INSERT INTO patients (id, name, age, card_id, gender, hospital_id) VALUES ($1, 'Other patient', 30, $2, 'Male', $3)")
+        .bind(other_patient)
+        .bind(other_patient.to_string())
+        .bind(a.id)
+        .execute(&app.pool)
+        .await
+        .unwrap();
+    let join = |patient_id: Uuid| {
+        serde_json::json!({
+            "patient_id": patient_id,
+            "date": (chrono::Utc::now() + chrono::Duration::days(3)).format("%Y-%m-%d").to_string(),
+            "purpose": "Checkup",
+        })
+    };
+
+    let response = app
+        .post_json(&token, "/appointments/waitlist", join(a.patient_id))
+        .await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let own: serde_json::Value = response.json().await.unwrap();
+    let response = app
+        .post_json(&token, "/appointments/waitlist", join(other_patient))
+        .await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let staff = app.login_as(a.id, UserRole::Receptionist).await;
+    let response = app
+        .post_json(&staff, "/appointments/waitlist", join(other_patient))
+        .await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let other: serde_json::Value = response.json().await.unwrap();
+
+    // The list holds only the patient's own entry, and asking for someone else's is refused
+    let body = app
+        .get(&token, "/appointments/waitlist")
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(body.contains(own["id"].as_str().unwrap()));
+    assert!(!body.contains(other["id"].as_str().unwrap()));
+    let response = app
+        .get(
+            &token,
+            &format!("/appointments/waitlist?patient_id={}", other_patient),
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = app
+        .request(
+            reqwest::Method::DELETE,
+            &token,
+            &format!("/appointments/waitlist/{}", other["id"].as_str().unwrap()),
+        )
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = app
+        .request(
+            reqwest::Method::DELETE,
+            &token,
+            &format!("/appointments/waitlist/{}", own["id"].as_str().unwrap()),
+        )
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[sqlx::test]
+async fn refuses_another_hospitals_admin_pages(pool: PgPool) {
+    let app = TestApp::spawn(pool).await;
+    let a = app.create_hospital("Hospital A").await;
+    let b = app.create_hospital("Hospital B").await;
+    let token = app.login_as(a.id, UserRole::Admin).await;
+
+    for path in [
+        format!("/admin/hospitals/{}/prices", b.id),
+        format!("/admin/hospitals/{}/payment-settings", b.id),
+        format!("/admin/hospitals/{}/users", b.id),
+    ] {
+        let response = app.get(&token, &path).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN, "GET {}", path);
+    }
+    let response = app
+        .get(&token, &format!("/admin/hospitals/{}/prices", a.id))
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
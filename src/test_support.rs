@@ -0,0 +1,187 @@
+// Runs the API against a throwaway database for HTTP tests. `sqlx::test` creates the database
+// on the server in DATABASE_URL; tokens are signed with SECRET_KEY from the environment or
+// `.env`, as the server does.
+use crate::admin::models::UserRole;
+use crate::app_state::AppState;
+use crate::auth::models::Claims;
+use crate::billing::gateway::FakeGateway;
+use crate::mailer::LogMailer;
+use crate::router::create_router;
+use crate::utils::SettingsCipher;
+use chrono::{Duration, Utc};
+use reqwest::{Client, Method, RequestBuilder, Response};
+use serde_json::Value;
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+
+// One hospital's records: a patient with an appointment with one of its doctors, and its bill
+pub struct Hospital {
+    pub id: Uuid,
+    pub patient_id: Uuid,
+    pub doctor_id: Uuid,
+    pub appointment_id: Uuid,
+    pub bill_id: Uuid,
+}
+
+pub struct TestApp {
+    pub client: Client,
+    pub base_url: String,
+    pub pool: PgPool,
+}
+
+impl TestApp {
+    pub async fn spawn(pool: PgPool) -> Self {
+        dotenvy::dotenv().ok();
+        let state = Arc::new(AppState::new(
+            pool.clone(),
+            Arc::new(FakeGateway::default()),
+            Arc::new(LogMailer),
+            None,
+            SettingsCipher::default(),
+        ));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            axum::serve(listener, create_router(state)).await.unwrap();
+        });
+        Self {
+            client: Client::new(),
+            base_url,
+            pool,
+        }
+    }
+
+    // A user of `hospital_id` with `role`, and an access token for them
+    pub async fn login_as(&self, hospital_id: Uuid, role: UserRole) -> String {
+        let user_id = self.create_user(hospital_id, role).await;
+        token_for(user_id, hospital_id, role)
+    }
+
+    // A patient user linked to `patient_id`, and an access token for them
+    pub async fn login_as_patient(&self, hospital_id: Uuid, patient_id: Uuid) -> String {
+        let user_id = self.create_user(hospital_id, UserRole::Patient).await;
+        sqlx::query("UPDATE patients SET user_id = $1 WHERE id = $2")
+            .bind(user_id)
+            .bind(patient_id)
+            .execute(&self.pool)
+            .await
+            .unwrap();
+        token_for(user_id, hospital_id, UserRole::Patient)
+    }
+
+    async fn create_user(&self, hospital_id: Uuid, role: UserRole) -> Uuid {
+        let user_id = Uuid::new_v4();
+        sqlx::query(
+            "INSERT INTO users (id, name, email, password_hash, role, hospital_id) VALUES ($1, $2, $3, $4, $5, $6)",
+        )
+        .bind(user_id)
+        .bind(format!("{:?}", role))
+        .bind(format!("{}@example.com", user_id))
+        .bind("not-a-hash")
+        .bind(role)
+        .bind(hospital_id)
+        .execute(&self.pool)
+        .await
+        .unwrap();
+        user_id
+    }
+
+    pub async fn create_hospital(&self, name: &str) -> Hospital {
+        let hospital = Hospital {
+            id: Uuid::new_v4(),
+            patient_id: Uuid::new_v4(),
+            doctor_id: Uuid::new_v4(),
+            appointment_id: Uuid::new_v4(),
+            bill_id: Uuid::new_v4(),
+        };
+        sqlx::query("INSERT INTO hospitals (id, name, address, phone) VALUES ($1, $2, '1 Marina Road', '08000000000')")
+            .bind(hospital.id)
+            .bind(name)
+            .execute(&self.pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO patients (id, name, age, card_id, gender, hospital_id) VALUES ($1, $2, 40, $3, 'Female', $4)")
+            .bind(hospital.patient_id)
+            .bind(format!("{} patient", name))
+            .bind(hospital.patient_id.to_string())
+            .bind(hospital.id)
+            .execute(&self.pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO doctors (id, name, specialization, available_days, hospital_id) VALUES ($1, $2, 'General Practice', ARRAY['Monday'], $3)")
+            .bind(hospital.doctor_id)
+            .bind(format!("{} doctor", name))
+            .bind(hospital.id)
+            .execute(&self.pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO appointments (id, patient_id, doctor_id, time, purpose, status, price, hospital_id) VALUES ($1, $2, $3, $4, 'Checkup', 'Scheduled', 10000, $5)")
+            .bind(hospital.appointment_id)
+            .bind(hospital.patient_id)
+            .bind(hospital.doctor_id)
+            .bind(Utc::now() + Duration::days(7))
+            .bind(hospital.id)
+            .execute(&self.pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO bills (id, reference, appointment_id, amount, subtotal, currency, status, hospital_id) VALUES ($1, $2, $3, 10000, 10000, 'NGN', 'Pending', $4)")
+            .bind(hospital.bill_id)
+            .bind(hospital.bill_id.to_string())
+            .bind(hospital.appointment_id)
+            .bind(hospital.id)
+            .execute(&self.pool)
+            .await
+            .unwrap();
+        hospital
+    }
+
+    // A request to `path` signed with `token`, to add a body or headers to
+    pub fn request(&self, method: Method, token: &str, path: &str) -> RequestBuilder {
+        self.client
+            .request(method, format!("{}{}", self.base_url, path))
+            .bearer_auth(token)
+    }
+
+    pub async fn get(&self, token: &str, path: &str) -> Response {
+        self.request(Method::GET, token, path).send().await.unwrap()
+    }
+
+    pub async fn put(&self, token: &str, path: &str) -> Response {
+        self.request(Method::PUT, token, path).send().await.unwrap()
+    }
+
+    pub async fn post(&self, token: &str, path: &str) -> Response {
+        self.request(Method::POST, token, path)
+            .send()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_json(&self, token: &str, path: &str, body: Value) -> Response {
+        self.request(Method::POST, token, path)
+            .json(&body)
+            .send()
+            .await
+            .unwrap()
+    }
+
+    pub async fn status_of(&self, table: &str, id: Uuid) -> String {
+        sqlx::query_scalar(&format!("SELECT status FROM {} WHERE id = $1", table))
+            .bind(id)
+            .fetch_one(&self.pool)
+            .await
+            .unwrap()
+    }
+}
+
+fn token_for(user_id: Uuid, hospital_id: Uuid, role: UserRole) -> String {
+    Claims {
+        sub: user_id,
+        hospital_id,
+        role,
+        jti: Uuid::new_v4(),
+        exp: (Utc::now() + Duration::minutes(5)).timestamp() as usize,
+    }
+    .generate_token()
+}
@@ -1,8 +1,12 @@
-use crate::{config::AppConfig, errors::AppError};
-use bcrypt::{BcryptError, DEFAULT_COST, hash, verify};
-use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc, Weekday};
+use crate::config::{AppConfig, DEFAULT_TIMEZONE};
+use crate::errors::AppError;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use bcrypt::{DEFAULT_COST, hash, verify};
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveDate, Utc, Weekday};
 use rand::{RngExt, distr::Alphanumeric, rng};
-use serde_json::map;
+use sha2::{Digest, Sha256};
+use std::fmt;
 
 pub fn create_random_string(length: usize) -> String {
     let mut rng = rng();
@@ -25,11 +29,6 @@ pub fn is_valid_day(day: &str) -> bool {
     VALID_DAYS.contains(&day)
 }
 
-pub fn get_paystack_config() -> Result<(String, String), AppError> {
-    let app_config = AppConfig::from_env()?;
-    Ok((app_config.paystack_url, app_config.paystack_secret_key))
-}
-
 fn parse_weekday(day: &str) -> Option<Weekday> {
     match day {
         "Monday" => Some(Weekday::Mon),
@@ -43,6 +42,10 @@ fn parse_weekday(day: &str) -> Option<Weekday> {
     }
 }
 
+pub fn weekday_name(weekday: Weekday) -> &'static str {
+    VALID_DAYS[weekday.num_days_from_monday() as usize]
+}
+
 fn next_occurrence_of_weekday(weekday: Weekday) -> NaiveDate {
     let today = Utc::now().date_naive();
     let days_until = (weekday.num_days_from_monday() as i64
@@ -64,13 +67,27 @@ pub fn normalize_timezone(input: &str) -> String {
         .replace("GMT", "+00:00")
 }
 
-pub fn combine_day_and_time(day: &str, time_str: &str) -> Result<DateTime<Utc>, AppError> {
+// The hospital's offset from UTC (DEFAULT_TIMEZONE), which working hours are given in
+pub fn hospital_offset() -> FixedOffset {
+    normalize_timezone(DEFAULT_TIMEZONE)
+        .parse()
+        .unwrap_or(FixedOffset::east_opt(0).expect("UTC is a valid offset"))
+}
+
+// Keeps the caller's offset so the wall-clock time can be checked against working hours
+pub fn combine_day_and_time(day: &str, time_str: &str) -> Result<DateTime<FixedOffset>, AppError> {
     let weekday = parse_weekday(day).ok_or_else(|| AppError::UnProcessableEntity {
         field: "day".to_string(),
         message: "Invalid day provided".to_string(),
     })?;
 
-    let date = next_occurrence_of_weekday(weekday);
+    combine_date_and_time(next_occurrence_of_weekday(weekday), time_str)
+}
+
+pub fn combine_date_and_time(
+    date: NaiveDate,
+    time_str: &str,
+) -> Result<DateTime<FixedOffset>, AppError> {
     let normalized = normalize_timezone(time_str);
 
     // Build a full RFC3339 string and parse it
@@ -79,15 +96,71 @@ pub fn combine_day_and_time(day: &str, time_str: &str) -> Result<DateTime<Utc>,
     let parsed = DateTime::parse_from_rfc3339(&full_datetime_str).map_err(|e| {
         AppError::UnProcessableEntity {
             field: "time".to_string(),
-            message: format!(
-                "Could not parse time: {} {}",
-                full_datetime_str,
-                e.to_string()
-            ),
+            message: format!("Could not parse time: {} {}", full_datetime_str, e),
         }
     })?;
 
-    Ok(parsed.with_timezone(&Utc))
+    Ok(parsed)
+}
+
+// Resolves when an appointment should start. An explicit ISO date ("2026-03-16", combined with
+// `time`) or datetime ("2026-03-16T10:00:00+01:00") takes precedence; `day` is a convenience
+// for the next occurrence of that weekday. The result must lie in the future.
+pub fn resolve_appointment_time(
+    date: Option<&str>,
+    day: Option<&str>,
+    time: Option<&str>,
+) -> Result<DateTime<FixedOffset>, AppError> {
+    let missing_time = || AppError::UnProcessableEntity {
+        field: "time".to_string(),
+        message: "A time is required unless date includes one".to_string(),
+    };
+
+    let resolved = match (date, day) {
+        (Some(date), _) => {
+            let normalized = normalize_timezone(date);
+            match DateTime::parse_from_rfc3339(&normalized) {
+                Ok(datetime) => datetime,
+                Err(_) => {
+                    let date =
+                        NaiveDate::parse_from_str(&normalized, "%Y-%m-%d").map_err(|_| {
+                            AppError::UnProcessableEntity {
+                                field: "date".to_string(),
+                                message: format!(
+                                    "Expected YYYY-MM-DD or an RFC3339 datetime, got {normalized}"
+                                ),
+                            }
+                        })?;
+                    combine_date_and_time(date, time.ok_or_else(missing_time)?)?
+                }
+            }
+        }
+        (None, Some(day)) => combine_day_and_time(day, time.ok_or_else(missing_time)?)?,
+        (None, None) => {
+            return Err(AppError::UnProcessableEntity {
+                field: "date".to_string(),
+                message: "Either date or day must be provided".to_string(),
+            });
+        }
+    };
+
+    if let (Some(_), Some(day)) = (date, day)
+        && weekday_name(resolved.weekday()) != day
+    {
+        return Err(AppError::UnProcessableEntity {
+            field: "day".to_string(),
+            message: format!("{} is not a {day}", resolved.date_naive()),
+        });
+    }
+
+    if resolved <= Utc::now() {
+        return Err(AppError::UnProcessableEntity {
+            field: "date".to_string(),
+            message: "Appointments must be booked in the future".to_string(),
+        });
+    }
+
+    Ok(resolved)
 }
 
 pub fn hash_password(password: &str) -> Result<String, AppError> {
@@ -102,7 +175,78 @@ pub fn verify_password(password: &str, hashed: &str) -> Result<bool, AppError> {
     Ok(result)
 }
 
+// Tokens handed to clients are only stored as their SHA-256, so a leaked table cannot be replayed
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
 pub fn get_secret_key() -> Result<String, AppError> {
     let app_config = AppConfig::from_env()?;
     Ok(app_config.secret_key)
 }
+
+// Encrypts secrets kept in the database, such as hospitals' Paystack keys. Built once at startup
+// from SETTINGS_ENCRYPTION_KEY; without one, secrets can neither be stored nor read back.
+#[derive(Clone, Default)]
+pub struct SettingsCipher(Option<Aes256Gcm>);
+
+impl fmt::Debug for SettingsCipher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SettingsCipher")
+            .field(&self.0.as_ref().map(|_| "<key>"))
+            .finish()
+    }
+}
+
+impl SettingsCipher {
+    pub fn from_config(config: &AppConfig) -> Result<Self, AppError> {
+        let Some(key) = config.settings_encryption_key.as_deref() else {
+            return Ok(Self(None));
+        };
+        let key = hex::decode(key.trim())
+            .ok()
+            .filter(|key| key.len() == 32)
+            .ok_or_else(|| {
+                AppError::ParsingError(
+                    "SETTINGS_ENCRYPTION_KEY must be 64 hex characters".to_string(),
+                )
+            })?;
+        Ok(Self(Some(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(
+            &key,
+        )))))
+    }
+
+    fn cipher(&self) -> Result<&Aes256Gcm, AppError> {
+        self.0.as_ref().ok_or_else(|| {
+            AppError::MissingEnvironmentVarible("SETTINGS_ENCRYPTION_KEY".to_string())
+        })
+    }
+
+    // Encrypts a secret for storage with AES-256-GCM, as hex of the nonce followed by the
+    // ciphertext
+    pub fn encrypt(&self, secret: &str) -> Result<String, AppError> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher()?
+            .encrypt(&nonce, secret.as_bytes())
+            .map_err(|_| AppError::InternalServerError("Failed to encrypt secret".to_string()))?;
+        Ok(hex::encode([nonce.as_slice(), &ciphertext].concat()))
+    }
+
+    pub fn decrypt(&self, encrypted: &str) -> Result<String, AppError> {
+        let bytes = hex::decode(encrypted)
+            .ok()
+            .filter(|bytes| bytes.len() > 12)
+            .ok_or_else(|| AppError::ParsingError("Malformed encrypted secret".to_string()))?;
+        let (nonce, ciphertext) = bytes.split_at(12);
+        let secret = self
+            .cipher()?
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| {
+                AppError::InternalServerError(
+                    "Failed to decrypt secret; has SETTINGS_ENCRYPTION_KEY changed?".to_string(),
+                )
+            })?;
+        String::from_utf8(secret).map_err(|e| AppError::ParsingError(e.to_string()))
+    }
+}